@@ -0,0 +1,526 @@
+use crate::dns;
+use crate::dns::hostname::{in_zone, same_name};
+use crate::dns::message::Message;
+use std::str::FromStr;
+
+/// Resolver whose rules for accepting records from a response are checked against
+///
+/// Every policy discards records outside the zone cut the resolver believes it is talking to, and
+/// records in the answer section that are not part of the answer to the question. They differ in
+/// how much they trust the authority and additional sections of an authoritative answer.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Policy {
+    /// NS records in the authority section of an authoritative answer replace the cached
+    /// delegation, along with their in-bailiwick glue, as in the original Kaminsky attack
+    Bind,
+    /// NS records and glue from an answer are cached below the existing delegation and never
+    /// replace it, only referrals change where the resolver goes next
+    Unbound,
+    /// Trust ranking from section 5.4.1 of RFC 2181, authority NS records of an authoritative answer
+    /// outrank the glue the delegation was learned from but only for the zone the server is
+    /// authoritative for, while additional section data never replaces anything
+    Rfc2181,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "bind" => Ok(Policy::Bind),
+            "unbound" => Ok(Policy::Unbound),
+            "rfc2181" => Ok(Policy::Rfc2181),
+            _ => Err(format!(
+                "Unknown policy {}, please enter either bind, unbound, or rfc2181",
+                s
+            )),
+        };
+    }
+}
+
+impl std::fmt::Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Policy::Bind => "bind",
+            Policy::Unbound => "unbound",
+            Policy::Rfc2181 => "rfc2181",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+/// Section of a response a record was found in
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Section {
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Section::Answer => "answer",
+            Section::Authority => "authority",
+            Section::Additional => "additional",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+/// How a resolver treats a record from a response
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Classification {
+    /// the record is cached and used
+    Accepted,
+    /// the record is within the zone cut but the resolver ignores it, or caches it without letting
+    /// it replace anything
+    InBailiwick,
+    /// the record is outside the zone cut and discarded
+    OutOfBailiwick,
+    /// the record is cached and replaces data the resolver already trusts for names other than the
+    /// queried one, such as the delegation of the zone
+    Poisonous,
+}
+
+impl std::fmt::Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Classification::Accepted => "accepted",
+            Classification::InBailiwick => "in-bailiwick",
+            Classification::OutOfBailiwick => "out-of-bailiwick",
+            Classification::Poisonous => "poisonous",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+/// Classification of a single record from a response along with the rule that decided it
+#[derive(PartialEq, Debug)]
+pub struct RecordCheck {
+    pub section: Section,
+    pub record: dns::ResourceRecord,
+    pub classification: Classification,
+    pub reason: String,
+}
+
+impl std::fmt::Display for RecordCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "{} {}: {}, {}",
+            self.section, self.record, self.classification, self.reason
+        );
+    }
+}
+
+/// Classifies every record in the response to the query the way a resolver with the given policy
+/// would, when it believes the response comes from the nameservers of zone_cut
+///
+/// Responses that do not match the query are discarded as a whole by every resolver, so an error is
+/// returned for them instead.
+pub fn check(
+    query: &Message,
+    response: &Message,
+    zone_cut: &str,
+    policy: Policy,
+) -> Result<Vec<RecordCheck>, String> {
+    let question = match query.questions.first() {
+        None => return Err("The query has no question".to_string()),
+        Some(q) => q,
+    };
+    if !response.header.qr {
+        return Err("The response does not have the QR bit set".to_string());
+    }
+    if response.header.id != query.header.id {
+        return Err(format!(
+            "The response ID {} does not match the query ID {}",
+            response.header.id, query.header.id
+        ));
+    }
+    // the question is compared exactly so that 0x20 encoded names must be echoed as sent
+    let matches = match response.questions.first() {
+        None => false,
+        Some(q) => q.qname.to_string() == question.qname.to_string() && q.qtype == question.qtype,
+    };
+    if !matches {
+        return Err(format!(
+            "The response does not repeat the question for {} {}",
+            question.qname, question.qtype
+        ));
+    }
+
+    let qname = question.qname.to_string();
+    let chain = answer_chain(response, &qname, question.qtype, zone_cut);
+    let referral = !response.header.aa && chain.is_empty();
+
+    let mut checks = Vec::new();
+    for rr in &response.answers {
+        let (classification, reason) = if !in_zone(&rr.name.to_string(), zone_cut) {
+            outside(zone_cut)
+        } else if !chain.contains(rr) {
+            (
+                Classification::InBailiwick,
+                format!("not part of the answer to {}", qname),
+            )
+        } else if !response.header.aa && policy != Policy::Rfc2181 {
+            (
+                Classification::InBailiwick,
+                "answers without the AA bit are lame when they come from an authoritative server"
+                    .to_string(),
+            )
+        } else {
+            (
+                Classification::Accepted,
+                format!("part of the answer to {}", qname),
+            )
+        };
+        checks.push(RecordCheck {
+            section: Section::Answer,
+            record: rr.clone(),
+            classification,
+            reason,
+        });
+    }
+
+    for rr in &response.authorities {
+        let name = rr.name.to_string();
+        let (classification, reason) = if !in_zone(&name, zone_cut) {
+            outside(zone_cut)
+        } else if rr.rtype != dns::Type::NS {
+            (
+                Classification::Accepted,
+                "used to prove the answer or its absence".to_string(),
+            )
+        } else if !in_zone(&qname, &name) {
+            (
+                Classification::InBailiwick,
+                format!("the delegation of {} does not lead towards {}", name, qname),
+            )
+        } else if referral {
+            if same_name(&name, zone_cut) {
+                (
+                    Classification::InBailiwick,
+                    "a referral back to the zone cut is lame".to_string(),
+                )
+            } else {
+                (
+                    Classification::Accepted,
+                    format!(
+                        "referral from {} to {}",
+                        dns::dnssec::presentation_name(zone_cut),
+                        name
+                    ),
+                )
+            }
+        } else {
+            authority_ns(&name, zone_cut, response.header.aa, policy)
+        };
+        checks.push(RecordCheck {
+            section: Section::Authority,
+            record: rr.clone(),
+            classification,
+            reason,
+        });
+    }
+
+    for rr in &response.additionals {
+        let name = rr.name.to_string();
+        let (classification, reason) = if !in_zone(&name, zone_cut) {
+            outside(zone_cut)
+        } else if rr.rtype != dns::Type::A && rr.rtype != dns::Type::AAAA {
+            (
+                Classification::InBailiwick,
+                "only addresses of nameservers are taken from the additional section".to_string(),
+            )
+        } else {
+            glue(&name, &checks, policy)
+        };
+        checks.push(RecordCheck {
+            section: Section::Additional,
+            record: rr.clone(),
+            classification,
+            reason,
+        });
+    }
+
+    return Ok(checks);
+}
+
+/// Records in the answer section that answer the question, following CNAMEs within the zone cut
+fn answer_chain(
+    response: &Message,
+    qname: &str,
+    qtype: dns::Type,
+    zone_cut: &str,
+) -> Vec<dns::ResourceRecord> {
+    let mut chain = Vec::new();
+    let mut name = String::from(qname);
+    for _ in 0..=response.answers.len() {
+        if !in_zone(&name, zone_cut) {
+            break;
+        }
+        let records: Vec<&dns::ResourceRecord> = response
+            .answers
+            .iter()
+            .filter(|rr| same_name(&rr.name.to_string(), &name))
+            .collect();
+        chain.extend(
+            records
+                .iter()
+                .filter(|rr| rr.rtype == qtype)
+                .map(|rr| (*rr).clone()),
+        );
+        let cname = records
+            .iter()
+            .find_map(|rr| match dns::Record::from_rr(rr) {
+                Ok(dns::Record::CNAME(cname)) if qtype != dns::Type::CNAME => Some((rr, cname)),
+                _ => None,
+            });
+        match cname {
+            None => break,
+            Some((rr, cname)) => {
+                chain.push((*rr).clone());
+                name = cname.cname;
+            }
+        }
+    }
+    return chain;
+}
+
+/// Classifies an NS record from the authority section of an answer, or of a negative answer
+fn authority_ns(
+    name: &str,
+    zone_cut: &str,
+    authoritative: bool,
+    policy: Policy,
+) -> (Classification, String) {
+    if !authoritative {
+        return (
+            Classification::InBailiwick,
+            "authority data of a non-authoritative answer ranks below the cached delegation"
+                .to_string(),
+        );
+    }
+    return match policy {
+        Policy::Bind => (
+            Classification::Poisonous,
+            format!(
+                "authority NS records of an authoritative answer replace the cached delegation of {}",
+                name
+            ),
+        ),
+        Policy::Unbound => (
+            Classification::InBailiwick,
+            "authority NS records of an answer never replace the cached delegation".to_string(),
+        ),
+        Policy::Rfc2181 if same_name(name, zone_cut) => (
+            Classification::Poisonous,
+            "authority data of an authoritative answer outranks the glue the delegation was learned from"
+                .to_string(),
+        ),
+        Policy::Rfc2181 => (
+            Classification::InBailiwick,
+            format!(
+                "the nameservers of {} are not authoritative for the NS records of {}",
+                zone_cut, name
+            ),
+        ),
+    };
+}
+
+/// Classifies an address from the additional section by the NS records it is glue for
+fn glue(name: &str, checks: &[RecordCheck], policy: Policy) -> (Classification, String) {
+    let nameservers = checks
+        .iter()
+        .filter_map(|check| match dns::Record::from_rr(&check.record) {
+            Ok(dns::Record::NS(ns)) if same_name(&ns.ns, name) => Some((check.classification, ns)),
+            _ => None,
+        });
+    let mut rejected = None;
+    for (classification, ns) in nameservers {
+        match classification {
+            Classification::Accepted => {
+                return (
+                    Classification::Accepted,
+                    format!("glue for a nameserver of {}", ns.name),
+                )
+            }
+            Classification::Poisonous if policy == Policy::Bind => {
+                return (
+                    Classification::Poisonous,
+                    format!(
+                        "glue for a nameserver replacing the delegation of {}",
+                        ns.name
+                    ),
+                )
+            }
+            Classification::Poisonous => {
+                return (
+                    Classification::InBailiwick,
+                    "additional section data ranks lowest and never replaces cached addresses"
+                        .to_string(),
+                )
+            }
+            _ => rejected = Some(ns),
+        }
+    }
+    return match rejected {
+        Some(ns) => (
+            Classification::InBailiwick,
+            format!("glue for a nameserver of {} that was not accepted", ns.name),
+        ),
+        None => (
+            Classification::InBailiwick,
+            "not glue for any nameserver in the authority section".to_string(),
+        ),
+    };
+}
+
+fn outside(zone_cut: &str) -> (Classification, String) {
+    return (
+        Classification::OutOfBailiwick,
+        format!(
+            "outside the zone cut {}",
+            dns::dnssec::presentation_name(zone_cut)
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bailiwick::{check, Classification, Policy};
+    use crate::dns;
+    use crate::dns::message::Message;
+    use crate::payload::{PayloadOptions, PayloadStrategy};
+    use crate::rng;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn query() -> Message {
+        return dns::Query::new(vec!["abcdefg.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+    }
+
+    fn payload(options: &PayloadOptions, attacker_ns: &str) -> Message {
+        return options
+            .build(query(), "abcdefg.example.com", "example.com", attacker_ns)
+            .unwrap()
+            .to_message()
+            .unwrap();
+    }
+
+    fn classifications(response: &Message, zone_cut: &str, policy: Policy) -> Vec<Classification> {
+        let mut query = query();
+        query.header.id = response.header.id;
+        return check(&query, response, zone_cut, policy)
+            .unwrap()
+            .iter()
+            .map(|check| check.classification)
+            .collect();
+    }
+
+    #[test]
+    fn answer_payload_under_each_policy() {
+        let options = PayloadOptions {
+            glue_addrs: vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))],
+            ..PayloadOptions::new(PayloadStrategy::Glue)
+        };
+        let response = payload(&options, "ns.example.com");
+
+        use Classification::*;
+        assert_eq!(
+            vec![Accepted, Poisonous, Poisonous],
+            classifications(&response, "example.com", Policy::Bind)
+        );
+        assert_eq!(
+            vec![Accepted, InBailiwick, InBailiwick],
+            classifications(&response, "example.com", Policy::Unbound)
+        );
+        assert_eq!(
+            vec![Accepted, Poisonous, InBailiwick],
+            classifications(&response, "example.com", Policy::Rfc2181)
+        );
+        // the com servers are not authoritative for the NS records of example.com
+        assert_eq!(
+            vec![Accepted, InBailiwick, InBailiwick],
+            classifications(&response, "com", Policy::Rfc2181)
+        );
+    }
+
+    #[test]
+    fn out_of_bailiwick_records() {
+        let options = PayloadOptions {
+            glue_addrs: vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))],
+            ..PayloadOptions::new(PayloadStrategy::Answer)
+        };
+        let response = payload(&options, "ns.attacker.com");
+
+        use Classification::*;
+        assert_eq!(
+            vec![Accepted, Poisonous, OutOfBailiwick],
+            classifications(&response, "example.com", Policy::Bind)
+        );
+        assert_eq!(
+            vec![OutOfBailiwick, OutOfBailiwick, OutOfBailiwick],
+            classifications(&response, "example.net", Policy::Bind)
+        );
+    }
+
+    #[test]
+    fn referrals() {
+        let options = PayloadOptions {
+            glue_addrs: vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))],
+            ..PayloadOptions::new(PayloadStrategy::Referral)
+        };
+        let response = payload(&options, "ns.example.com");
+
+        use Classification::*;
+        for policy in &[Policy::Bind, Policy::Unbound, Policy::Rfc2181] {
+            // a referral from the root servers to example.com is followed and its glue used
+            assert_eq!(
+                vec![Accepted, Accepted],
+                classifications(&response, "", *policy)
+            );
+            // while a referral from example.com to itself leads nowhere
+            assert_eq!(
+                vec![InBailiwick, InBailiwick],
+                classifications(&response, "example.com", *policy)
+            );
+        }
+    }
+
+    #[test]
+    fn cname_chain() {
+        let options = PayloadOptions {
+            cname_target: Some("www.example.net".to_string()),
+            ..PayloadOptions::new(PayloadStrategy::Cname)
+        };
+        let response = payload(&options, "ns.example.com");
+
+        // the CNAME is accepted but its target is left for a separate query to example.net
+        use Classification::*;
+        assert_eq!(
+            vec![Accepted, OutOfBailiwick, Poisonous],
+            classifications(&response, "example.com", Policy::Bind)
+        );
+    }
+
+    #[test]
+    fn mismatched_responses() {
+        let response = payload(
+            &PayloadOptions::new(PayloadStrategy::Answer),
+            "ns.example.com",
+        );
+
+        let mut query = query();
+        query.header.id = response.header.id.wrapping_add(1);
+        assert!(check(&query, &response, "example.com", Policy::Bind).is_err());
+
+        let mut other = dns::Query::new(vec!["ABCDEFG.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        other.header.id = response.header.id;
+        assert!(check(&other, &response, "example.com", Policy::Bind).is_err());
+    }
+}
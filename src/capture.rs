@@ -0,0 +1,386 @@
+use crate::dns::message::Message;
+use pnet::datalink;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::udp::UdpPacket;
+use pnet::packet::Packet;
+use std::net::SocketAddrV4;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Link layer header types from the pcap file header, see https://www.tcpdump.org/linktypes.html
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// DNS message seen on the wire, along with when and between whom it was sent
+#[derive(PartialEq, Debug)]
+pub struct DnsPacket {
+    /// Time since the Unix epoch the packet was captured at
+    pub timestamp: Duration,
+    pub src: SocketAddrV4,
+    pub dst: SocketAddrV4,
+    pub message: Message,
+}
+
+/// DNS messages read from a capture
+#[derive(PartialEq, Debug)]
+pub struct DnsPackets {
+    pub packets: Vec<DnsPacket>,
+    /// Number of UDP payloads on the DNS port that could not be decoded as DNS messages
+    pub undecodable: usize,
+}
+
+/// Link layer frame read from a capture
+#[derive(PartialEq, Debug)]
+pub struct Frame {
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+
+/// Frames read from a pcap file along with the link layer they were captured on
+#[derive(PartialEq, Debug)]
+pub struct Pcap {
+    pub linktype: u32,
+    pub frames: Vec<Frame>,
+}
+
+impl Pcap {
+    pub fn load(path: &str) -> Result<Pcap, String> {
+        let contents = match std::fs::read(path) {
+            Err(e) => return Err(format!("Could not read capture file {}: {}", path, e)),
+            Ok(c) => c,
+        };
+        return Pcap::parse(&contents);
+    }
+
+    /// Parses a capture in the classic libpcap format
+    ///
+    /// Both byte orders and both microsecond and nanosecond timestamps are supported. Captures in
+    /// the pcapng format need converting first, e.g. with `editcap -F pcap`.
+    pub fn parse(buffer: &[u8]) -> Result<Pcap, String> {
+        const HEADER_LENGTH: usize = 24;
+        const RECORD_HEADER_LENGTH: usize = 16;
+
+        if buffer.len() < HEADER_LENGTH {
+            return Err("Capture file is too short to be a pcap file".to_string());
+        }
+
+        let magic = [buffer[0], buffer[1], buffer[2], buffer[3]];
+        let (big_endian, nanoseconds) = match magic {
+            [0xa1, 0xb2, 0xc3, 0xd4] => (true, false),
+            [0xd4, 0xc3, 0xb2, 0xa1] => (false, false),
+            [0xa1, 0xb2, 0x3c, 0x4d] => (true, true),
+            [0x4d, 0x3c, 0xb2, 0xa1] => (false, true),
+            [0x0a, 0x0d, 0x0d, 0x0a] => {
+                return Err("pcapng captures are not supported, please convert to pcap".to_string())
+            }
+            _ => return Err("Capture file is not a pcap file".to_string()),
+        };
+        let read_u32 = |offset: usize| {
+            let bytes = [
+                buffer[offset],
+                buffer[offset + 1],
+                buffer[offset + 2],
+                buffer[offset + 3],
+            ];
+            return if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            };
+        };
+
+        let linktype = read_u32(20);
+        let mut frames = Vec::new();
+        let mut offset = HEADER_LENGTH;
+        while offset < buffer.len() {
+            if offset + RECORD_HEADER_LENGTH > buffer.len() {
+                return Err(format!(
+                    "Packet record header at byte {} is truncated",
+                    offset
+                ));
+            }
+            let seconds = read_u32(offset) as u64;
+            let fraction = read_u32(offset + 4);
+            let length = read_u32(offset + 8) as usize;
+            offset += RECORD_HEADER_LENGTH;
+            if offset + length > buffer.len() {
+                return Err(format!("Packet record at byte {} is truncated", offset));
+            }
+
+            let nanos = if nanoseconds {
+                fraction
+            } else {
+                fraction.saturating_mul(1000)
+            };
+            frames.push(Frame {
+                timestamp: Duration::new(seconds, 0) + Duration::from_nanos(nanos as u64),
+                data: buffer[offset..offset + length].to_vec(),
+            });
+            offset += length;
+        }
+
+        return Ok(Pcap { linktype, frames });
+    }
+
+    /// DNS messages sent to or from the port in the capture, other packets are skipped
+    pub fn dns_packets(&self, port: u16) -> DnsPackets {
+        let mut decoded = DnsPackets {
+            packets: Vec::new(),
+            undecodable: 0,
+        };
+        for frame in &self.frames {
+            match decode(self.linktype, frame, port) {
+                None => (),
+                Some(Err(_)) => decoded.undecodable += 1,
+                Some(Ok(packet)) => decoded.packets.push(packet),
+            };
+        }
+        return decoded;
+    }
+}
+
+/// Captures DNS messages sent to or from the port on a network interface until an error occurs
+///
+/// Each message is passed to the callback as soon as it is captured, or the reason it could not be
+/// decoded. Capturing needs the same raw socket privileges as spoofing.
+pub fn live<F: FnMut(Result<DnsPacket, String>)>(
+    interface: &str,
+    port: u16,
+    mut callback: F,
+) -> Result<(), String> {
+    let interface = match datalink::interfaces()
+        .into_iter()
+        .find(|iface| iface.name == interface)
+    {
+        None => return Err(format!("No network interface named {}", interface)),
+        Some(i) => i,
+    };
+
+    let mut receiver = match datalink::channel(&interface, Default::default()) {
+        Err(e) => return Err(e.to_string()),
+        Ok(datalink::Channel::Ethernet(_, receiver)) => receiver,
+        Ok(_) => return Err(format!("Unsupported channel type for {}", interface.name)),
+    };
+
+    loop {
+        let data = match receiver.next() {
+            Err(e) => return Err(e.to_string()),
+            Ok(d) => d,
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::new(0, 0));
+        let frame = Frame {
+            timestamp,
+            data: data.to_vec(),
+        };
+        if let Some(decoded) = decode(LINKTYPE_ETHERNET, &frame, port) {
+            callback(decoded);
+        }
+    }
+}
+
+/// Decodes a DNS message carried over UDP and IPv4 in the frame
+///
+/// Returns None for anything else, including IPv6, TCP, and IP fragments, and an error for a UDP
+/// payload on the port that is not a well formed DNS message.
+fn decode(linktype: u32, frame: &Frame, port: u16) -> Option<Result<DnsPacket, String>> {
+    let ip_bytes = match linktype {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = read_u16(&frame.data, offset)?;
+            // skip over any 802.1Q tags
+            while ethertype == ETHERTYPE_VLAN {
+                offset += 4;
+                ethertype = read_u16(&frame.data, offset)?;
+            }
+            if ethertype != ETHERTYPE_IPV4 {
+                return None;
+            }
+            &frame.data[offset + 2..]
+        }
+        LINKTYPE_LINUX_SLL => {
+            if read_u16(&frame.data, 14)? != ETHERTYPE_IPV4 {
+                return None;
+            }
+            &frame.data[16..]
+        }
+        LINKTYPE_NULL => frame.data.get(4..)?,
+        LINKTYPE_RAW => &frame.data[..],
+        _ => return None,
+    };
+
+    let ip = Ipv4Packet::new(ip_bytes)?;
+    const MORE_FRAGMENTS: u8 = 1;
+    if ip.get_version() != 4
+        || ip.get_next_level_protocol() != IpNextHeaderProtocols::Udp
+        || ip.get_fragment_offset() != 0
+        || ip.get_flags() & MORE_FRAGMENTS != 0
+    {
+        return None;
+    }
+    // the capture may hold padding past the end of the IP packet
+    let total_length = (ip.get_total_length() as usize).min(ip_bytes.len());
+    let header_length = ip.get_header_length() as usize * 4;
+    if header_length > total_length {
+        return None;
+    }
+    let udp = UdpPacket::new(&ip_bytes[header_length..total_length])?;
+    if udp.get_source() != port && udp.get_destination() != port {
+        return None;
+    }
+
+    let src = SocketAddrV4::new(ip.get_source(), udp.get_source());
+    let dst = SocketAddrV4::new(ip.get_destination(), udp.get_destination());
+    let message = match Message::parse(udp.payload()) {
+        Err(e) => {
+            return Some(Err(format!(
+                "Could not decode the DNS message from {} to {}: {}",
+                src, dst, e
+            )))
+        }
+        Ok(m) => m,
+    };
+    return Some(Ok(DnsPacket {
+        timestamp: frame.timestamp,
+        src,
+        dst,
+        message,
+    }));
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    return Some(u16::from_be_bytes([bytes[0], bytes[1]]));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::{Frame, Pcap, LINKTYPE_ETHERNET, LINKTYPE_RAW};
+    use crate::dns;
+    use crate::rng;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::time::Duration;
+
+    /// IPv4 and UDP headers followed by the payload, with checksums left empty
+    fn udp_ipv4(src: SocketAddrV4, dst: SocketAddrV4, payload: &[u8]) -> Vec<u8> {
+        let total_length = (20 + 8 + payload.len()) as u16;
+        let mut bytes = vec![0x45, 0];
+        bytes.extend(&total_length.to_be_bytes());
+        bytes.extend(&[0, 0, 0x40, 0, 64, 17, 0, 0]); // don't fragment, TTL 64, UDP
+        bytes.extend(&src.ip().octets());
+        bytes.extend(&dst.ip().octets());
+        bytes.extend(&src.port().to_be_bytes());
+        bytes.extend(&dst.port().to_be_bytes());
+        bytes.extend(&((8 + payload.len()) as u16).to_be_bytes());
+        bytes.extend(&[0, 0]);
+        bytes.extend(payload);
+        return bytes;
+    }
+
+    fn pcap_bytes(linktype: u32, frames: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        // little endian microsecond header
+        let mut bytes = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        bytes.extend(&[0; 8]);
+        bytes.extend(&65535u32.to_le_bytes());
+        bytes.extend(&linktype.to_le_bytes());
+        for (seconds, micros, data) in frames {
+            bytes.extend(&seconds.to_le_bytes());
+            bytes.extend(&micros.to_le_bytes());
+            bytes.extend(&(data.len() as u32).to_le_bytes());
+            bytes.extend(&(data.len() as u32).to_le_bytes());
+            bytes.extend(data);
+        }
+        return bytes;
+    }
+
+    #[test]
+    fn parse_pcap_with_dns_packets() {
+        let query = dns::Query::new(vec!["www.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        let client = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 7), 33333);
+        let server = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 53), 53);
+
+        let mut ethernet = vec![0; 12];
+        ethernet.extend(&[0x08, 0x00]);
+        ethernet.extend(udp_ipv4(client, server, &query.to_bytes()));
+        let mut arp = vec![0; 12];
+        arp.extend(&[0x08, 0x06, 1, 2, 3]);
+
+        let bytes = pcap_bytes(LINKTYPE_ETHERNET, &[(100, 250, ethernet), (101, 0, arp)]);
+        let pcap = Pcap::parse(&bytes).unwrap();
+        assert_eq!(LINKTYPE_ETHERNET, pcap.linktype);
+        assert_eq!(2, pcap.frames.len());
+
+        let packets = pcap.dns_packets(53).packets;
+        assert_eq!(1, packets.len());
+        assert_eq!(Duration::new(100, 250_000), packets[0].timestamp);
+        assert_eq!(client, packets[0].src);
+        assert_eq!(server, packets[0].dst);
+        assert_eq!(query, packets[0].message);
+
+        // packets on other ports are not DNS as far as the detector is concerned
+        assert!(pcap.dns_packets(5353).packets.is_empty());
+        // truncated records are rejected
+        assert!(Pcap::parse(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn parse_raw_ip_pcap() {
+        let query = dns::Query::new(vec!["www.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        let client = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 7), 33333);
+        let server = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 53), 53);
+        let frame = Frame {
+            timestamp: Duration::new(0, 0),
+            data: udp_ipv4(server, client, &query.to_bytes()),
+        };
+
+        let pcap = Pcap {
+            linktype: LINKTYPE_RAW,
+            frames: vec![frame],
+        };
+        assert_eq!(1, pcap.dns_packets(53).packets.len());
+    }
+
+    #[test]
+    fn truncated_dns_payloads_are_counted() {
+        let query = dns::Query::new(vec!["www.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap()
+            .to_bytes();
+        let client = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 7), 33333);
+        let server = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 53), 53);
+        let frame = |payload: &[u8]| Frame {
+            timestamp: Duration::new(0, 0),
+            data: udp_ipv4(client, server, payload),
+        };
+
+        let pcap = Pcap {
+            linktype: LINKTYPE_RAW,
+            frames: vec![
+                frame(&query[..3]),
+                frame(&query[..query.len() - 1]),
+                frame(&query),
+            ],
+        };
+        let decoded = pcap.dns_packets(53);
+        assert_eq!(1, decoded.packets.len());
+        assert_eq!(2, decoded.undecodable);
+    }
+
+    #[test]
+    fn reject_non_pcap() {
+        let mut pcapng = vec![0x0a, 0x0d, 0x0d, 0x0a];
+        pcapng.extend(&[0; 20]);
+        assert!(Pcap::parse(&pcapng).is_err());
+        assert!(Pcap::parse(&[0; 24]).is_err());
+    }
+}
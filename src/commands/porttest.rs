@@ -0,0 +1,121 @@
+use crate::commands::{positive_seconds, seconds, SubdomainArgs};
+use crate::dns::cookie::CookieSecret;
+use crate::kaminsky::DEFAULT_TARGET_PORT;
+use crate::porttest::{run, Pattern, PorttestOptions};
+use crate::server::Server;
+use crate::zone::Zone;
+use rand::Rng;
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, UdpSocket};
+use structopt::StructOpt;
+
+/// Tests how randomly the target server picks the source ports and TXIDs of its upstream queries
+#[derive(Debug, StructOpt)]
+pub struct PorttestArgs {
+    /// IP address of the DNS server to test
+    #[structopt(long)]
+    target_addr: Ipv4Addr,
+
+    /// Zone file in the master file format to serve, the target server must resolve names in the
+    /// zone by asking this machine
+    #[structopt(long)]
+    zone: String,
+
+    /// Origin of the zone, required unless the zone file sets $ORIGIN
+    #[structopt(long)]
+    origin: Option<String>,
+
+    /// Address to serve the zone on
+    #[structopt(long, default_value = "0.0.0.0")]
+    listen: String,
+
+    /// UDP port to serve the zone on
+    #[structopt(long, default_value = "53")]
+    port: u16,
+
+    /// Number of fresh names in the zone to query through the target server
+    #[structopt(long, default_value = "50")]
+    queries: u32,
+
+    /// How long to wait for each answer in seconds before counting the query as failed
+    #[structopt(long, default_value = "2")]
+    timeout: f32,
+
+    /// How long to wait between queries in seconds
+    #[structopt(long, default_value = "0.05")]
+    interval: f32,
+
+    #[structopt(flatten)]
+    subdomains: SubdomainArgs,
+
+    /// Seed for the random subdomains, so that a run can be replayed exactly
+    #[structopt(long)]
+    seed: Option<u64>,
+}
+
+impl PorttestArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let zone = Zone::load(&self.zone, self.origin.as_deref())?;
+        let mut options = PorttestOptions::new(self.target_addr, zone.origin.clone());
+        options.queries = self.queries;
+        options.timeout = positive_seconds("--timeout", self.timeout)?;
+        options.interval = seconds("--interval", self.interval)?;
+        options.subdomains = self.subdomains.options()?;
+        options.seed = self.seed;
+
+        let server = Server::new(zone, CookieSecret::new(rand::thread_rng().gen()));
+        let address = format!("{}:{}", self.listen, self.port);
+        let socket = match UdpSocket::bind(&address) {
+            Err(e) => return Err(format!("Could not listen on {}: {}", address, e)),
+            Ok(sock) => sock,
+        };
+
+        let report = run(&options, server, socket)?;
+        let sources: HashSet<_> = report.received.iter().map(|q| q.source.ip()).collect();
+        println!(
+            "{} of {} queries were answered, and {} were passed on to {}",
+            report.asked - report.failed,
+            report.asked,
+            report.forwarded,
+            address
+        );
+        println!(
+            "Received {} upstream queries from {} source addresses",
+            report.received.len(),
+            sources.len()
+        );
+
+        let (ports, txids) = match (report.ports(), report.txids()) {
+            (Some(ports), Some(txids)) => (ports, txids),
+            _ => {
+                return Err(format!(
+                    "No upstream queries arrived, check that {} delegates {} to {}",
+                    self.target_addr, options.zone, address
+                ))
+            }
+        };
+        println!("Source ports: {}", ports);
+        println!("TXIDs: {}", txids);
+
+        match ports.pattern {
+            Pattern::Fixed(port) if port == DEFAULT_TARGET_PORT => println!(
+                "The target server queries from the fixed port {}, which spoofed responses are sent to by default",
+                port
+            ),
+            Pattern::Fixed(port) => println!(
+                "The target server queries from the fixed port {}, pass --target-port {} to the attack subcommand",
+                port, port
+            ),
+            _ => println!(
+                "The target server does not query from a fixed port, so spoofed responses sent to a single port rarely match"
+            ),
+        };
+        // fully random ports span all 65536 values, one more than calc accepts
+        println!(
+            "Odds of an attack: calc --txid-bits {:.0} --ports {}",
+            txids.entropy_bits(),
+            2f64.powf(ports.entropy_bits()).round().min(u16::MAX as f64)
+        );
+        return Ok(());
+    }
+}
@@ -0,0 +1,421 @@
+use crate::dns;
+use crate::rng;
+use crate::trace;
+use crate::validator::{RRset, Validator};
+use rand::RngCore;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+/// Runs DNS queries against a server, or traces their resolution from the root
+#[derive(Debug, StructOpt)]
+pub struct QueryArgs {
+    /// Hostname to query, e.g. www.example.com, can be left out if questions are given instead
+    #[structopt(long, required_unless("question"))]
+    hostname: Option<String>,
+
+    /// IP or hostname of DNS server to query, required unless tracing
+    #[structopt(long, required_unless("trace"))]
+    dns_server: Option<String>,
+
+    /// Resolve iteratively starting from the root servers in the root hints rather than asking a
+    /// recursive DNS server
+    ///
+    /// Each server asked is printed along with the referral or answer it gave, showing the
+    /// delegation path from the root down to the authoritative servers.
+    #[structopt(long, requires("root-hints"))]
+    trace: bool,
+
+    /// Root hints file in the named.root format that tracing starts from
+    #[structopt(long)]
+    root_hints: Option<String>,
+
+    /// Record type to query the hostname for, e.g. MX
+    #[structopt(long, default_value = "A")]
+    qtype: dns::Type,
+
+    /// Class to query the hostname in, e.g. CH
+    #[structopt(long, default_value = "IN")]
+    qclass: dns::Class,
+
+    /// Additional questions in the form name[/type[/class]], e.g. example.com/MX
+    ///
+    /// Each question is sent in its own query, since most servers only answer the first question
+    /// of a message, and the results are printed per question.
+    #[structopt(long, parse(try_from_str = parse_question))]
+    question: Vec<(String, dns::Type, dns::Class)>,
+
+    /// Opcode of the query, one of query, iquery, status, notify, or update
+    #[structopt(long, default_value = "query")]
+    opcode: dns::Opcode,
+
+    /// Send queries with RD=0 so that the server does not recurse
+    #[structopt(long)]
+    no_recursion: bool,
+
+    /// Set the CD bit so that the server does not validate DNSSEC signatures
+    #[structopt(long)]
+    checking_disabled: bool,
+
+    /// Randomize the case of the queried names (0x20 encoding) and check that responses echo it
+    #[structopt(long = "0x20")]
+    case_randomization: bool,
+
+    /// Send DNS cookies (RFC 7873) with queries and check that responses echo the client cookie
+    ///
+    /// The server cookie from each response is sent with the following queries, and a BADCOOKIE
+    /// response is retried once with the fresh server cookie it carries.
+    #[structopt(long)]
+    cookies: bool,
+
+    /// Add an EDNS OPT record to queries
+    ///
+    /// This is implied by setting the DO bit, the EDNS payload size, or any EDNS options.
+    #[structopt(long)]
+    edns: bool,
+
+    /// Set the DO bit in the EDNS OPT record to ask for DNSSEC records
+    #[structopt(long)]
+    dnssec_ok: bool,
+
+    /// UDP payload size advertised in the EDNS OPT record, defaults to 1232
+    #[structopt(long)]
+    edns_payload_size: Option<u16>,
+
+    /// EDNS options to send in the form code:hex-data, e.g. 10:0123456789abcdef for a client cookie
+    #[structopt(long, parse(try_from_str = parse_edns_option))]
+    edns_option: Vec<dns::EdnsOption>,
+
+    /// File of DS or DNSKEY records in the zone file format to validate DNSSEC signatures against
+    ///
+    /// Implies the DO bit. Each RRset in the answer and authority sections is reported as secure,
+    /// bogus, or unsigned, with the DNSKEY and DS records needed for the chain of trust fetched from
+    /// the same server.
+    #[structopt(long)]
+    trust_anchor: Option<String>,
+}
+
+impl QueryArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let mut questions = self.question.clone();
+        if let Some(hostname) = &self.hostname {
+            questions.insert(0, (hostname.clone(), self.qtype, self.qclass));
+        }
+
+        let mut rng = rng::new(None);
+        let results: Vec<Result<(), String>> = match (&self.root_hints, &self.dns_server) {
+            (Some(root_hints), _) if self.trace => {
+                let hints = dns::RootHints::load(root_hints)?;
+                questions
+                    .into_iter()
+                    .map(|(hostname, qtype, _)| trace(&hints, &hostname, qtype, &mut rng))
+                    .collect()
+            }
+            (_, Some(dns_server)) => {
+                let mut client = dns::Client::new(dns_server.clone());
+                if self.cookies {
+                    client.enable_cookies(&mut rng);
+                }
+                let mut validator = match &self.trust_anchor {
+                    None => None,
+                    Some(path) => Some(Validator::load(path)?),
+                };
+                questions
+                    .into_iter()
+                    .map(|(hostname, qtype, qclass)| {
+                        self.query(
+                            &client,
+                            validator.as_mut(),
+                            hostname,
+                            qtype,
+                            qclass,
+                            &mut rng,
+                        )
+                    })
+                    .collect()
+            }
+            _ => return Err("Either --dns-server or --trace is needed".to_string()),
+        };
+
+        let failures = results.iter().filter(|result| result.is_err()).count();
+        if failures > 0 {
+            return Err(format!(
+                "{} of {} questions failed",
+                failures,
+                results.len()
+            ));
+        }
+        return Ok(());
+    }
+
+    fn query(
+        &self,
+        client: &dns::Client,
+        validator: Option<&mut Validator>,
+        hostname: String,
+        qtype: dns::Type,
+        qclass: dns::Class,
+        rng: &mut dyn RngCore,
+    ) -> Result<(), String> {
+        println!(";; QUESTION: {} {:?} {}", hostname, qclass, qtype);
+
+        let mut request = dns::Query::new(vec![hostname]);
+        request.qtype = qtype;
+        request.qclass = qclass;
+        request.opcode = self.opcode;
+        request.recursion_desired = !self.no_recursion;
+        request.checking_disabled = self.checking_disabled;
+        request.edns = self.edns();
+        request.case_randomization = self.case_randomization;
+
+        let result = client.query(request, rng);
+        match &result {
+            Err(e) => eprintln!("{}", e),
+            Ok(m) => print_message(m),
+        }
+        if let (Ok(m), Some(validator)) = (&result, validator) {
+            self.print_validation(client, validator, m, rng)?;
+        }
+        println!();
+        return result.map(|_| ());
+    }
+
+    /// Prints whether each RRset of the answer and authority sections validates
+    fn print_validation(
+        &self,
+        client: &dns::Client,
+        validator: &mut Validator,
+        message: &dns::message::Message,
+        rng: &mut dyn RngCore,
+    ) -> Result<(), String> {
+        let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Err(e) => return Err(e.to_string()),
+            Ok(d) => d.as_secs() as u32,
+        };
+        // the server may not validate itself, so CD makes sure it returns the records regardless
+        let mut fetch = |name: &str, rtype: dns::Type| {
+            let mut request = dns::Query::new(vec![name.to_string()]);
+            request.qtype = rtype;
+            request.recursion_desired = !self.no_recursion;
+            request.checking_disabled = true;
+            request.edns = self.edns();
+            return client.query(request, rng);
+        };
+
+        println!();
+        let mut rrsets = RRset::group(&message.answers);
+        rrsets.extend(RRset::group(&message.authorities));
+        for rrset in rrsets {
+            let status = if rrset.rrsigs.is_empty() {
+                "unsigned".to_string()
+            } else {
+                match validator.validate(&rrset, now, &mut fetch) {
+                    Ok(()) => "secure".to_string(),
+                    Err(e) => format!("bogus ({})", e),
+                }
+            };
+            println!(";; DNSSEC: {} {} {}", rrset.name, rrset.rtype, status);
+        }
+        return Ok(());
+    }
+
+    fn edns(&self) -> Option<dns::Edns> {
+        let dnssec_ok = self.dnssec_ok || self.trust_anchor.is_some();
+        if !self.edns
+            && !dnssec_ok
+            && self.edns_payload_size.is_none()
+            && self.edns_option.is_empty()
+        {
+            return None;
+        }
+
+        let default = dns::Edns::new();
+        return Some(dns::Edns {
+            udp_payload_size: self.edns_payload_size.unwrap_or(default.udp_payload_size),
+            dnssec_ok,
+            options: self.edns_option.clone(),
+            ..default
+        });
+    }
+}
+
+/// Parses a question given as name[/type[/class]]
+fn parse_question(s: &str) -> Result<(String, dns::Type, dns::Class), String> {
+    let mut parts = s.split('/');
+    let name = String::from(parts.next().unwrap_or(""));
+    let qtype = match parts.next() {
+        None => dns::Type::A,
+        Some(t) => t.parse()?,
+    };
+    let qclass = match parts.next() {
+        None => dns::Class::IN,
+        Some(c) => c.parse()?,
+    };
+    if parts.next().is_some() {
+        return Err(format!(
+            "Invalid question {}, please enter it as name[/type[/class]]",
+            s
+        ));
+    }
+    return Ok((name, qtype, qclass));
+}
+
+/// Parses an EDNS option given as code:hex-data
+fn parse_edns_option(s: &str) -> Result<dns::EdnsOption, String> {
+    let mut parts = s.splitn(2, ':');
+    let code = match parts.next().unwrap_or("").parse::<u16>() {
+        Err(_) => return Err(format!("Invalid EDNS option code in {}", s)),
+        Ok(c) => c,
+    };
+    let hex = parts.next().unwrap_or("");
+    // slicing by bytes is only safe once every character is known to be a single byte hex digit
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid EDNS option data in {}", s));
+    }
+    if hex.len() % 2 != 0 {
+        return Err(format!(
+            "EDNS option data in {} has an odd number of hex digits",
+            s
+        ));
+    }
+    let data: Result<Vec<u8>, _> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect();
+    return match data {
+        Err(_) => Err(format!("Invalid EDNS option data in {}", s)),
+        Ok(data) => Ok(dns::EdnsOption { code, data }),
+    };
+}
+
+/// Prints a response in a format similar to dig's
+fn print_message(message: &dns::message::Message) {
+    let header = &message.header;
+    let flags: Vec<&str> = vec![
+        ("qr", header.qr),
+        ("aa", header.aa),
+        ("tc", header.tc),
+        ("rd", header.rd),
+        ("ra", header.ra),
+        ("ad", header.ad),
+        ("cd", header.cd),
+    ]
+    .into_iter()
+    .filter(|(_, set)| *set)
+    .map(|(name, _)| name)
+    .collect();
+
+    // EDNS extends the 4 bit rcode in the header with 8 more bits
+    let extended_rcode = match &message.edns {
+        Some(edns) => (edns.extended_rcode as u16) << 4,
+        None => 0,
+    };
+    let rcode = extended_rcode | header.rcode as u16;
+    let status = match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        16 => "BADVERS".to_string(),
+        23 => "BADCOOKIE".to_string(),
+        _ => format!("RCODE{}", rcode),
+    };
+    println!(
+        ";; opcode: {:?}, status: {}, id: {}, flags: {}",
+        header.opcode,
+        status,
+        header.id,
+        flags.join(" ")
+    );
+
+    if let Some(edns) = &message.edns {
+        println!(
+            ";; EDNS: version {}, udp {}, flags: {}",
+            edns.version,
+            edns.udp_payload_size,
+            if edns.dnssec_ok { "do" } else { "" }
+        );
+        for option in &edns.options {
+            let hex: Vec<String> = option.data.iter().map(|b| format!("{:02x}", b)).collect();
+            println!(";; EDNS option {}: {}", option.code, hex.join(""));
+        }
+    }
+
+    let sections = vec![
+        ("ANSWER", &message.answers),
+        ("AUTHORITY", &message.authorities),
+        ("ADDITIONAL", &message.additionals),
+    ];
+    for (name, records) in sections {
+        if records.is_empty() {
+            continue;
+        }
+        println!(";; {} SECTION:", name);
+        for record in records {
+            println!("{}", record);
+        }
+    }
+}
+
+fn trace(
+    hints: &dns::RootHints,
+    hostname: &str,
+    qtype: dns::Type,
+    rng: &mut dyn RngCore,
+) -> Result<(), String> {
+    let result = trace::trace(hints, hostname, qtype, rng);
+    match &result {
+        Err(e) => eprintln!("{}", e),
+        Ok(records) => {
+            println!("\nResolved {}:", hostname);
+            for record in records {
+                println!("{}", record);
+            }
+        }
+    }
+    return result.map(|_| ());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::query::{parse_edns_option, parse_question};
+    use crate::dns;
+
+    #[test]
+    fn parse_questions() {
+        assert_eq!(
+            ("example.com".to_string(), dns::Type::A, dns::Class::IN),
+            parse_question("example.com").unwrap()
+        );
+        assert_eq!(
+            ("version.bind".to_string(), dns::Type::TXT, dns::Class::CH),
+            parse_question("version.bind/txt/CH").unwrap()
+        );
+        assert!(parse_question("example.com/XYZ").is_err());
+        assert!(parse_question("example.com/A/IN/extra").is_err());
+    }
+
+    #[test]
+    fn parse_edns_options() {
+        assert_eq!(
+            dns::EdnsOption {
+                code: 10,
+                data: vec![0x01, 0x23, 0xab, 0xcd],
+            },
+            parse_edns_option("10:0123abcd").unwrap()
+        );
+        assert_eq!(
+            dns::EdnsOption {
+                code: 3,
+                data: vec![],
+            },
+            parse_edns_option("3").unwrap()
+        );
+        assert!(parse_edns_option("10:123").is_err());
+        assert!(parse_edns_option("cookie:0123").is_err());
+        // a multibyte character must not be sliced through
+        assert!(parse_edns_option("10:aéa").is_err());
+        assert!(parse_edns_option("10:+1").is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use crate::commands::{enforce_scope, run_attack, spoofed_sources};
+use crate::rng;
+use crate::scenario::Scenario;
+use structopt::StructOpt;
+
+/// Runs the attack described by a scenario file
+#[derive(Debug, StructOpt)]
+pub struct RunArgs {
+    /// TOML file describing the attack, see the README for its format
+    scenario: String,
+}
+
+impl RunArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let scenario = Scenario::load(&self.scenario)?;
+        scenario.validate()?;
+        let scope = scenario.scope()?;
+
+        let spoofed_addrs = spoofed_sources(
+            &scenario.sources.addrs,
+            &scenario.target.domain,
+            &scenario.discovery_server(),
+            &scenario.root_hints(),
+            &mut rng::new(scenario.seed),
+        )?;
+
+        let mut addrs = spoofed_addrs.clone();
+        addrs.push(scenario.target.resolver);
+        enforce_scope(&scope, &addrs, &scenario.target.domain)?;
+
+        let stats = run_attack(&scenario.attack_options(spoofed_addrs)?)?;
+        if scenario.output.stdout.unwrap_or(true) {
+            println!("{}", stats.to_json());
+        }
+        if let Some(path) = scenario.json_output() {
+            if let Err(e) = std::fs::write(&path, stats.to_json()) {
+                return Err(format!(
+                    "Could not write attack statistics to {}: {}",
+                    path, e
+                ));
+            }
+            println!("Attack statistics written to {}", path);
+        }
+        return Ok(());
+    }
+}
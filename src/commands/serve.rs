@@ -0,0 +1,97 @@
+use crate::dns::cookie::CookieSecret;
+use crate::server::Server;
+use crate::zone::Zone;
+use rand::Rng;
+use std::net::UdpSocket;
+use structopt::StructOpt;
+
+/// Serves a zone authoritatively over UDP
+#[derive(Debug, StructOpt)]
+pub struct ServeArgs {
+    /// Zone file in the master file format to serve
+    #[structopt(long)]
+    zone: String,
+
+    /// Origin of the zone, required unless the zone file sets $ORIGIN
+    #[structopt(long)]
+    origin: Option<String>,
+
+    /// Address to listen on
+    #[structopt(long, default_value = "0.0.0.0")]
+    listen: String,
+
+    /// UDP port to listen on
+    #[structopt(long, default_value = "53")]
+    port: u16,
+
+    /// Secret to mint server cookies with as 32 hex digits, random unless given
+    ///
+    /// Servers behind one address should share the secret so that each accepts the server cookies
+    /// the others mint.
+    #[structopt(long, parse(try_from_str = parse_cookie_secret))]
+    cookie_secret: Option<[u8; 16]>,
+
+    /// Only answer queries that return a valid server cookie
+    ///
+    /// Queries without a cookie are refused, and queries without a valid server cookie get a
+    /// BADCOOKIE response carrying a fresh one to retry with.
+    #[structopt(long)]
+    require_cookies: bool,
+}
+
+impl ServeArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let zone = Zone::load(&self.zone, self.origin.as_deref())?;
+        println!(
+            "Loaded {} records for {} from {}",
+            zone.records.len(),
+            zone.origin,
+            self.zone
+        );
+
+        let key = match self.cookie_secret {
+            None => rand::thread_rng().gen(),
+            Some(key) => key,
+        };
+        let mut server = Server::new(zone, CookieSecret::new(key));
+        server.require_cookies = self.require_cookies;
+
+        let address = format!("{}:{}", self.listen, self.port);
+        let socket = match UdpSocket::bind(&address) {
+            Err(e) => return Err(format!("Could not listen on {}: {}", address, e)),
+            Ok(sock) => sock,
+        };
+        println!("Listening on {}", address);
+        return server.serve(&socket);
+    }
+}
+
+fn parse_cookie_secret(s: &str) -> Result<[u8; 16], String> {
+    let mut key = [0; 16];
+    match data_encoding::HEXLOWER_PERMISSIVE.decode(s.as_bytes()) {
+        Ok(bytes) if bytes.len() == key.len() => key.copy_from_slice(&bytes),
+        _ => return Err(format!("Cookie secret {} is not 32 hex digits", s)),
+    }
+    return Ok(key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cookie_secret;
+
+    #[test]
+    fn cookie_secrets() {
+        let key = parse_cookie_secret("000102030405060708090A0b0c0d0e0f").unwrap();
+        assert_eq!(key, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+        for s in &[
+            "",
+            "000102030405060708090a0b0c0d0e",
+            "000102030405060708090a0b0c0d0e0f10",
+            "+f0102030405060708090a0b0c0d0e0f",
+            "é02030405060708090a0b0c0d0e0f10",
+            "0g0102030405060708090a0b0c0d0e0f",
+        ] {
+            assert!(parse_cookie_secret(s).is_err(), "{} should be rejected", s);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::dns;
+use crate::dns::dnssec;
+use crate::signer::{self, NSEC3Options, SignOptions, SigningKey};
+use crate::zone::Zone;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+/// Signs a zone with DNSSEC, adding DNSKEY, RRSIG, and NSEC or NSEC3 records
+#[derive(Debug, StructOpt)]
+pub struct SignArgs {
+    /// Zone file in the master file format to sign
+    #[structopt(long)]
+    zone: String,
+
+    /// Origin of the zone, required unless the zone file sets $ORIGIN
+    #[structopt(long)]
+    origin: Option<String>,
+
+    /// Key pairs to sign with in the format of BIND's dnssec-keygen, e.g. Kexample.com.+015+12345
+    ///
+    /// Keys with the SEP flag set (flags 257) only sign the DNSKEY RRset when keys without it are
+    /// given as well.
+    #[structopt(long, required_unless("generate"))]
+    key: Vec<String>,
+
+    /// Generate a key with the algorithm, either ECDSAP256SHA256 or ED25519, and sign with it too
+    ///
+    /// The key pair is written next to the signed zone in the format of BIND's dnssec-keygen, and
+    /// its DS record is printed for the parent zone or a trust anchor file.
+    #[structopt(long)]
+    generate: Option<dnssec::Algorithm>,
+
+    /// File to write the signed zone to, defaults to the zone file with .signed appended
+    #[structopt(long)]
+    output: Option<String>,
+
+    /// Time the signatures become valid as YYYYMMDDHHmmSS in UTC or seconds since the epoch,
+    /// defaults to an hour ago
+    #[structopt(long, parse(try_from_str = dnssec::parse_time))]
+    inception: Option<u32>,
+
+    /// Days the signatures stay valid for after their inception
+    #[structopt(long, default_value = "30")]
+    validity: u32,
+
+    /// TTL of the DNSKEY and NSEC3PARAM records added to the zone
+    ///
+    /// The NSEC or NSEC3 records take the negative caching TTL from the zone's SOA record instead,
+    /// and only fall back to this TTL when the zone has none.
+    #[structopt(long, default_value = "3600")]
+    ttl: u32,
+
+    /// Deny the existence of names with an NSEC3 chain instead of NSEC records
+    #[structopt(long)]
+    nsec3: bool,
+
+    /// Extra rounds of hashing for the NSEC3 chain
+    #[structopt(long, default_value = "0")]
+    nsec3_iterations: u16,
+
+    /// Salt for the NSEC3 chain in hex, or - for none
+    #[structopt(long, default_value = "-")]
+    nsec3_salt: String,
+}
+
+impl SignArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let zone = Zone::load(&self.zone, self.origin.as_deref())?;
+        let output = match &self.output {
+            None => format!("{}.signed", self.zone),
+            Some(output) => output.clone(),
+        };
+
+        let mut keys = Vec::new();
+        for path in &self.key {
+            keys.push(SigningKey::load(path)?);
+        }
+        if let Some(algorithm) = self.generate {
+            let key = SigningKey::generate(&zone.origin, algorithm, dnssec::SECURE_ENTRY_POINT)?;
+            let directory = match std::path::Path::new(&output).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy(),
+                _ => ".".into(),
+            };
+            let path = key.save(&directory)?;
+            println!("Generated key {} in {}", key.dnskey.key_tag(), path);
+            println!("DS record for the parent zone:");
+            println!("{}", dns::Record::DS(key.dnskey.ds()?));
+            keys.push(key);
+        }
+
+        let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Err(e) => return Err(e.to_string()),
+            Ok(d) => d.as_secs() as u32,
+        };
+        // an hour of leeway keeps the signatures valid for resolvers with clocks running behind
+        let inception = self.inception.unwrap_or_else(|| now.wrapping_sub(3600));
+        // signature times compare as serial numbers, so validity must stay under 2^31 seconds
+        let validity = match self.validity.checked_mul(86400) {
+            Some(seconds) if seconds <= i32::MAX as u32 => seconds,
+            _ => return Err(format!("A validity of {} days is too long", self.validity)),
+        };
+        let mut options = SignOptions::new(inception, inception.wrapping_add(validity));
+        options.ttl = self.ttl;
+        if self.nsec3 {
+            let salt = match self.nsec3_salt.as_str() {
+                "-" => Vec::new(),
+                salt => match data_encoding::HEXLOWER_PERMISSIVE.decode(salt.as_bytes()) {
+                    Err(_) => return Err(format!("NSEC3 salt {} is not hex", salt)),
+                    Ok(salt) => salt,
+                },
+            };
+            options.nsec3 = Some(NSEC3Options {
+                iterations: self.nsec3_iterations,
+                salt,
+            });
+        }
+
+        let signed = signer::sign_zone(&zone, &keys, &options)?;
+        if let Err(e) = std::fs::write(&output, signed.to_string()) {
+            return Err(format!(
+                "Could not write the signed zone to {}: {}",
+                output, e
+            ));
+        }
+        let tags: Vec<u16> = keys.iter().map(|key| key.dnskey.key_tag()).collect();
+        println!(
+            "Signed {} with keys {:?}, wrote {} records to {}",
+            dnssec::presentation_name(&signed.origin),
+            tags,
+            signed.records.len(),
+            output
+        );
+        println!(
+            "Signatures are valid from {} to {}",
+            dnssec::format_time(options.inception),
+            dnssec::format_time(options.expiration)
+        );
+        return Ok(());
+    }
+}
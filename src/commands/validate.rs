@@ -0,0 +1,30 @@
+use crate::scenario::Scenario;
+use structopt::StructOpt;
+
+/// Checks a scenario file without sending any packets
+#[derive(Debug, StructOpt)]
+pub struct ValidateArgs {
+    /// TOML file describing the attack, see the README for its format
+    scenario: String,
+}
+
+impl ValidateArgs {
+    pub fn run(&self) -> Result<(), String> {
+        let scenario = Scenario::load(&self.scenario)?;
+        if let Err(e) = scenario.validate() {
+            return Err(format!("{} is not valid: {}", self.scenario, e));
+        }
+
+        println!(
+            "{} is valid: poisoning {} for {} with {} as its nameserver",
+            self.scenario,
+            scenario.target.resolver,
+            scenario.target.domain,
+            scenario.target.attacker_ns
+        );
+        if scenario.sources.discover {
+            println!("The nameservers to spoof responses from will be discovered when the scenario is run");
+        }
+        return Ok(());
+    }
+}
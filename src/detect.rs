@@ -0,0 +1,494 @@
+use crate::capture::DnsPacket;
+use crate::dns;
+use crate::dns::dnssec::civil_from_days;
+use crate::dns::hostname::{in_zone, normalize_name};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::SocketAddrV4;
+use std::time::Duration;
+
+/// Kind of Kaminsky attack signature an alert was raised for
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum AlertKind {
+    /// many responses for one query carried the wrong TXID
+    TxidMismatch,
+    /// many different random names were queried under one domain
+    RandomSubdomains,
+    /// a response advertised a nameserver that was out of bailiwick or not seen for the zone before
+    UnexpectedNs,
+}
+
+impl std::fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AlertKind::TxidMismatch => "txid-mismatch",
+            AlertKind::RandomSubdomains => "random-subdomains",
+            AlertKind::UnexpectedNs => "unexpected-ns",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+/// Possible poisoning attempt seen in the traffic
+#[derive(PartialEq, Debug)]
+pub struct Alert {
+    /// Capture time of the packet that raised the alert, as time since the Unix epoch
+    pub timestamp: Duration,
+    pub kind: AlertKind,
+    pub description: String,
+}
+
+impl std::fmt::Display for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "{} {}: {}",
+            format_timestamp(self.timestamp),
+            self.kind,
+            self.description
+        );
+    }
+}
+
+/// Thresholds for the signatures the detector looks for
+#[derive(PartialEq, Debug, Clone)]
+pub struct DetectorOptions {
+    /// Sliding window the thresholds are counted over
+    pub window: Duration,
+    /// Number of responses with the wrong TXID for one query that raise an alert
+    pub txid_threshold: usize,
+    /// Number of different names queried directly under one domain that raise an alert
+    pub subdomain_threshold: usize,
+}
+
+impl DetectorOptions {
+    pub fn new() -> DetectorOptions {
+        return DetectorOptions {
+            window: Duration::new(1, 0),
+            txid_threshold: 10,
+            subdomain_threshold: 20,
+        };
+    }
+}
+
+/// Responses seen for the name a client asked about
+struct Exchange {
+    /// TXID of the client's most recent query, if the query was captured
+    expected_id: Option<u16>,
+    /// Capture times of responses that did not carry the expected TXID
+    mismatches: VecDeque<Duration>,
+    alerted: bool,
+    last_seen: Duration,
+}
+
+/// Names recently queried directly under a domain
+struct Subdomains {
+    queries: VecDeque<(Duration, String)>,
+    /// Alerts for the domain are held back until this time so that a flood raises a single alert
+    quiet_until: Duration,
+}
+
+/// Looks for the signatures of a Kaminsky attack in a stream of DNS messages
+///
+/// Three signatures are checked:
+/// - a burst of responses to a client for the name it queried, but with TXIDs other than the one
+///   it used, which is how spoofed responses guess the TXID
+/// - many different names queried directly under one domain, which is how each round of the attack
+///   gets a fresh name that the resolver has not cached
+/// - NS records in the authority section that are out of bailiwick for the query, or that differ
+///   from the nameservers first seen for the zone
+///
+/// Packets must be observed in capture order.
+pub struct Detector {
+    options: DetectorOptions,
+    exchanges: HashMap<(SocketAddrV4, String), Exchange>,
+    subdomains: HashMap<String, Subdomains>,
+    /// Nameservers first seen for each zone, used as the baseline for later responses
+    nameservers: HashMap<String, BTreeSet<String>>,
+    /// Zone and nameserver pairs that have already raised an alert
+    reported_ns: HashSet<(String, String)>,
+    last_pruned: Duration,
+}
+
+impl Detector {
+    pub fn new(options: DetectorOptions) -> Detector {
+        return Detector {
+            options,
+            exchanges: HashMap::new(),
+            subdomains: HashMap::new(),
+            nameservers: HashMap::new(),
+            reported_ns: HashSet::new(),
+            last_pruned: Duration::new(0, 0),
+        };
+    }
+
+    /// Checks the packet against the traffic seen so far, returning any alerts it raises
+    pub fn observe(&mut self, packet: &DnsPacket) -> Vec<Alert> {
+        self.prune(packet.timestamp);
+
+        let mut alerts = Vec::new();
+        let message = &packet.message;
+        for question in &message.questions {
+            let qname = question.qname.to_string().to_ascii_lowercase();
+            if message.header.qr {
+                alerts.extend(self.observe_response(packet, &qname));
+            } else {
+                self.observe_query(packet, &qname);
+                alerts.extend(self.observe_subdomain(packet.timestamp, &qname));
+            }
+        }
+        if message.header.qr {
+            alerts.extend(self.observe_authorities(packet));
+        }
+        return alerts;
+    }
+
+    fn observe_query(&mut self, packet: &DnsPacket, qname: &str) {
+        let exchange = self
+            .exchanges
+            .entry((packet.src, qname.to_string()))
+            .or_insert(Exchange {
+                expected_id: None,
+                mismatches: VecDeque::new(),
+                alerted: false,
+                last_seen: packet.timestamp,
+            });
+        exchange.expected_id = Some(packet.message.header.id);
+        exchange.last_seen = packet.timestamp;
+    }
+
+    fn observe_response(&mut self, packet: &DnsPacket, qname: &str) -> Option<Alert> {
+        let window = self.options.window;
+        let exchange = self
+            .exchanges
+            .entry((packet.dst, qname.to_string()))
+            .or_insert(Exchange {
+                expected_id: None,
+                mismatches: VecDeque::new(),
+                alerted: false,
+                last_seen: packet.timestamp,
+            });
+        exchange.last_seen = packet.timestamp;
+
+        // responses are only expected to carry the TXID of the query they answer
+        if exchange.expected_id == Some(packet.message.header.id) {
+            return None;
+        }
+        exchange.mismatches.push_back(packet.timestamp);
+        while let Some(first) = exchange.mismatches.front() {
+            if packet.timestamp.checked_sub(*first).unwrap_or_default() <= window {
+                break;
+            }
+            exchange.mismatches.pop_front();
+        }
+
+        if exchange.alerted || exchange.mismatches.len() < self.options.txid_threshold {
+            return None;
+        }
+        exchange.alerted = true;
+        let expected = match exchange.expected_id {
+            Some(id) => format!("the query's TXID {}", id),
+            None => "no captured query".to_string(),
+        };
+        return Some(Alert {
+            timestamp: packet.timestamp,
+            kind: AlertKind::TxidMismatch,
+            description: format!(
+                "{} responses for {} sent to {} within {:?} did not match {}, last from {}",
+                exchange.mismatches.len(),
+                qname,
+                packet.dst,
+                window,
+                expected,
+                packet.src
+            ),
+        });
+    }
+
+    fn observe_subdomain(&mut self, timestamp: Duration, qname: &str) -> Option<Alert> {
+        let mut labels = qname.trim_end_matches('.').splitn(2, '.');
+        let label = labels.next()?;
+        let domain = labels.next()?;
+        // every name under a top level domain is a different domain, not a random subdomain
+        if !domain.contains('.') {
+            return None;
+        }
+
+        let window = self.options.window;
+        let subdomains = self
+            .subdomains
+            .entry(domain.to_string())
+            .or_insert(Subdomains {
+                queries: VecDeque::new(),
+                quiet_until: Duration::new(0, 0),
+            });
+        subdomains.queries.push_back((timestamp, label.to_string()));
+        while let Some((first, _)) = subdomains.queries.front() {
+            if timestamp.checked_sub(*first).unwrap_or_default() <= window {
+                break;
+            }
+            subdomains.queries.pop_front();
+        }
+
+        let distinct: HashSet<&String> = subdomains.queries.iter().map(|(_, l)| l).collect();
+        if distinct.len() < self.options.subdomain_threshold || timestamp < subdomains.quiet_until {
+            return None;
+        }
+        subdomains.quiet_until = timestamp.saturating_add(window);
+        return Some(Alert {
+            timestamp,
+            kind: AlertKind::RandomSubdomains,
+            description: format!(
+                "{} different names were queried under {} within {:?}, latest {}",
+                distinct.len(),
+                domain,
+                window,
+                qname
+            ),
+        });
+    }
+
+    fn observe_authorities(&mut self, packet: &DnsPacket) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let qnames: Vec<String> = packet
+            .message
+            .questions
+            .iter()
+            .map(|q| q.qname.to_string())
+            .collect();
+
+        let mut zones: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for rr in &packet.message.authorities {
+            if let Ok(dns::Record::NS(ns)) = dns::Record::from_rr(rr) {
+                zones
+                    .entry(normalize_name(&ns.name))
+                    .or_insert_with(BTreeSet::new)
+                    .insert(normalize_name(&ns.ns));
+            }
+        }
+
+        for (zone, nameservers) in zones {
+            // a server may only delegate names at or above the queried name
+            if !qnames.iter().any(|qname| in_zone(qname, &zone)) {
+                for ns in &nameservers {
+                    if !self.reported_ns.insert((zone.clone(), ns.clone())) {
+                        continue;
+                    }
+                    alerts.push(Alert {
+                        timestamp: packet.timestamp,
+                        kind: AlertKind::UnexpectedNs,
+                        description: format!(
+                            "{} sent {} as a nameserver for {}, which is out of bailiwick for a query for {}",
+                            packet.src,
+                            ns,
+                            zone,
+                            qnames.join(", ")
+                        ),
+                    });
+                }
+                continue;
+            }
+
+            let known = match self.nameservers.get(&zone) {
+                None => {
+                    self.nameservers.insert(zone, nameservers);
+                    continue;
+                }
+                Some(known) => known.clone(),
+            };
+            for ns in nameservers.difference(&known) {
+                if !self.reported_ns.insert((zone.clone(), ns.clone())) {
+                    continue;
+                }
+                alerts.push(Alert {
+                    timestamp: packet.timestamp,
+                    kind: AlertKind::UnexpectedNs,
+                    description: format!(
+                        "{} sent {} as a nameserver for {}, but the nameservers seen for it so far are {}",
+                        packet.src,
+                        ns,
+                        zone,
+                        known.iter().cloned().collect::<Vec<String>>().join(", ")
+                    ),
+                });
+            }
+        }
+        return alerts;
+    }
+
+    /// Forgets exchanges that have been idle for a while so that memory use stays bounded
+    fn prune(&mut self, now: Duration) {
+        const IDLE_WINDOWS: u32 = 10;
+
+        let idle = self.options.window.saturating_mul(IDLE_WINDOWS);
+        if now.checked_sub(self.last_pruned).unwrap_or_default() < idle {
+            return;
+        }
+        self.last_pruned = now;
+        self.exchanges
+            .retain(|_, exchange| now.checked_sub(exchange.last_seen).unwrap_or_default() < idle);
+        self.subdomains
+            .retain(|_, subdomains| match subdomains.queries.back() {
+                None => false,
+                Some((last, _)) => now.checked_sub(*last).unwrap_or_default() < idle,
+            });
+    }
+}
+
+/// Formats time since the Unix epoch as an RFC 3339 timestamp in UTC with microseconds
+fn format_timestamp(timestamp: Duration) -> String {
+    const SECONDS_PER_DAY: u64 = 86400;
+
+    let seconds = timestamp.as_secs();
+    let (year, month, day) = civil_from_days((seconds / SECONDS_PER_DAY) as i64);
+    let time = seconds % SECONDS_PER_DAY;
+
+    return format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        timestamp.subsec_micros()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::DnsPacket;
+    use crate::detect::{format_timestamp, AlertKind, Detector, DetectorOptions};
+    use crate::dns;
+    use crate::rng;
+    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::time::Duration;
+
+    fn resolver() -> SocketAddrV4 {
+        return SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 7), 33333);
+    }
+
+    fn nameserver() -> SocketAddrV4 {
+        return SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 53), 53);
+    }
+
+    fn query(millis: u64, qname: &str, id: u16) -> DnsPacket {
+        let mut message = dns::Query::new(vec![qname.to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        message.header.id = id;
+        return DnsPacket {
+            timestamp: Duration::from_millis(millis),
+            src: resolver(),
+            dst: nameserver(),
+            message,
+        };
+    }
+
+    fn response(millis: u64, qname: &str, id: u16, ns: &str) -> DnsPacket {
+        let mut query = query(millis, qname, id);
+        let mut response = dns::Response::new(query.message);
+        response
+            .add_authority(dns::Record::NS(dns::NSRecord {
+                name: "example.com".to_string(),
+                ttl: 300,
+                ns: ns.to_string(),
+            }))
+            .unwrap();
+        query.message = response.to_message().unwrap();
+        return DnsPacket {
+            src: nameserver(),
+            dst: resolver(),
+            ..query
+        };
+    }
+
+    #[test]
+    fn detect_txid_mismatch_burst() {
+        let mut detector = Detector::new(DetectorOptions::new());
+        assert!(detector.observe(&query(0, "abc.example.com", 7)).is_empty());
+
+        let mut alerts = Vec::new();
+        for id in 100..120 {
+            alerts.extend(detector.observe(&response(10, "abc.example.com", id, "ns.example.com")));
+        }
+        // the real answer carries the right TXID and raises nothing
+        alerts.extend(detector.observe(&response(20, "abc.example.com", 7, "ns.example.com")));
+
+        assert_eq!(1, alerts.len());
+        assert_eq!(AlertKind::TxidMismatch, alerts[0].kind);
+        assert_eq!(Duration::from_millis(10), alerts[0].timestamp);
+    }
+
+    #[test]
+    fn mismatches_outside_the_window_are_not_counted() {
+        let mut detector = Detector::new(DetectorOptions::new());
+        detector.observe(&query(0, "abc.example.com", 7));
+        for i in 0..20 {
+            let alerts =
+                detector.observe(&response(i * 500, "abc.example.com", 100, "ns.example.com"));
+            assert!(alerts.is_empty());
+        }
+    }
+
+    #[test]
+    fn huge_windows_do_not_overflow() {
+        let mut options = DetectorOptions::new();
+        options.window = Duration::MAX;
+        let mut detector = Detector::new(options);
+        let mut alerts = Vec::new();
+        for i in 0..40 {
+            alerts.extend(detector.observe(&query(i * 10, &format!("r{}.example.com", i), 1)));
+        }
+        assert_eq!(1, alerts.len());
+    }
+
+    #[test]
+    fn detect_random_subdomains() {
+        let mut detector = Detector::new(DetectorOptions::new());
+        let mut alerts = Vec::new();
+        for i in 0..40 {
+            alerts.extend(detector.observe(&query(i * 10, &format!("r{}.example.com", i), 1)));
+        }
+        assert_eq!(1, alerts.len());
+        assert_eq!(AlertKind::RandomSubdomains, alerts[0].kind);
+
+        // repeated queries for the same name are not random subdomains
+        let mut detector = Detector::new(DetectorOptions::new());
+        for i in 0..40 {
+            assert!(detector.observe(&query(i, "www.example.com", 1)).is_empty());
+        }
+    }
+
+    #[test]
+    fn detect_unexpected_ns() {
+        let mut detector = Detector::new(DetectorOptions::new());
+        assert!(detector
+            .observe(&response(0, "www.example.com", 1, "ns.example.com"))
+            .is_empty());
+
+        let alerts = detector.observe(&response(10, "www.example.com", 2, "ns.attacker.lab"));
+        assert_eq!(1, alerts.len());
+        assert_eq!(AlertKind::UnexpectedNs, alerts[0].kind);
+        // the same nameserver is only reported once
+        assert!(detector
+            .observe(&response(20, "www.example.com", 3, "ns.attacker.lab"))
+            .is_empty());
+
+        // NS records for a zone the query does not fall under are out of bailiwick
+        let alerts = detector.observe(&response(30, "www.other.lab", 4, "ns.example.com"));
+        assert_eq!(1, alerts.len());
+        assert_eq!(AlertKind::UnexpectedNs, alerts[0].kind);
+    }
+
+    #[test]
+    fn timestamps_in_utc() {
+        assert_eq!(
+            "1970-01-01T00:00:00.000000Z",
+            format_timestamp(Duration::new(0, 0))
+        );
+        assert_eq!(
+            "2021-03-04T05:06:07.000250Z",
+            format_timestamp(Duration::new(1614834367, 250_000))
+        );
+    }
+}
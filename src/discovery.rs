@@ -0,0 +1,204 @@
+use crate::dns;
+use crate::dns::hostname::same_name;
+use rand::RngCore;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Nameserver found for a domain along with its addresses
+#[derive(PartialEq, Debug, Clone)]
+pub struct Nameserver {
+    pub name: String,
+    pub ipv4: Vec<Ipv4Addr>,
+    pub ipv6: Vec<Ipv6Addr>,
+}
+
+/// Nameservers that a domain is delegated to
+#[derive(PartialEq, Debug, Clone)]
+pub struct Delegation {
+    pub domain: String,
+    pub nameservers: Vec<Nameserver>,
+}
+
+impl Delegation {
+    pub fn ipv4_addrs(&self) -> Vec<Ipv4Addr> {
+        return self
+            .nameservers
+            .iter()
+            .flat_map(|ns| ns.ipv4.iter().cloned())
+            .collect();
+    }
+}
+
+impl fmt::Display for Delegation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.domain)?;
+        for ns in &self.nameservers {
+            let addrs: Vec<String> = ns
+                .ipv4
+                .iter()
+                .map(|ip| ip.to_string())
+                .chain(ns.ipv6.iter().map(|ip| ip.to_string()))
+                .collect();
+            writeln!(f, "  NS {} -> {}", ns.name, addrs.join(", "))?;
+        }
+        return Ok(());
+    }
+}
+
+/// Discovers the authoritative nameservers of every zone from the top level domain down to the
+/// given domain by asking the given server
+///
+/// Suffixes of the domain that are not zones of their own, e.g. co.uk or the www of
+/// www.example.com, are skipped, so the last delegation in the returned chain is the one for the
+/// zone closest to the domain.
+pub fn discover_chain(
+    server: &str,
+    domain: &str,
+    rng: &mut dyn RngCore,
+) -> Result<Vec<Delegation>, String> {
+    let labels: Vec<&str> = domain.trim_end_matches('.').split('.').collect();
+
+    let mut chain = Vec::new();
+    for i in (0..labels.len()).rev() {
+        let zone = labels[i..].join(".");
+        if let Some(delegation) = discover(server, &zone, rng)? {
+            chain.push(delegation);
+        }
+    }
+    if chain.is_empty() {
+        return Err(format!("No NS records found for {} or its parents", domain));
+    }
+    return Ok(chain);
+}
+
+/// Discovers the authoritative nameservers of the domain along with their A and AAAA addresses,
+/// returning None if the domain is not a zone of its own
+pub fn discover(
+    server: &str,
+    domain: &str,
+    rng: &mut dyn RngCore,
+) -> Result<Option<Delegation>, String> {
+    let client = dns::Client::new(String::from(server));
+
+    let ns_response = client.query(query(domain, dns::Type::NS), rng)?;
+    let ns_names = nameserver_names(&ns_response.answers, domain);
+    if ns_names.is_empty() {
+        return Ok(None);
+    }
+
+    let mut nameservers = Vec::new();
+    for name in ns_names {
+        // the answers may include a CNAME chain, only the final addresses matter
+        let ipv4 = client
+            .query(query(&name, dns::Type::A), rng)?
+            .answers
+            .iter()
+            .filter_map(|rr| match dns::Record::from_rr(rr) {
+                Ok(dns::Record::A(a)) => Some(Ipv4Addr::from(a.ip)),
+                _ => None,
+            })
+            .collect();
+        // only IPv4 addresses are spoofed, so a nameserver without IPv6 is still of use
+        let ipv6 = match client.query(query(&name, dns::Type::AAAA), rng) {
+            Err(e) => {
+                eprintln!("AAAA lookup for {} failed: {}", name, e);
+                Vec::new()
+            }
+            Ok(response) => response
+                .answers
+                .iter()
+                .filter_map(|rr| match dns::Record::from_rr(rr) {
+                    Ok(dns::Record::AAAA(aaaa)) => Some(Ipv6Addr::from(aaaa.ip)),
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        nameservers.push(Nameserver { name, ipv4, ipv6 });
+    }
+
+    return Ok(Some(Delegation {
+        domain: String::from(domain),
+        nameservers,
+    }));
+}
+
+/// Names of the nameservers in NS records owned by the domain itself
+///
+/// Asking for the NS records of a name that is not a zone of its own can be answered with those
+/// of the zone it falls in, owned by that zone rather than by the name that was asked about.
+fn nameserver_names(answers: &[dns::ResourceRecord], domain: &str) -> Vec<String> {
+    return answers
+        .iter()
+        .filter_map(|rr| match dns::Record::from_rr(rr) {
+            Ok(dns::Record::NS(ns)) if same_name(&ns.name, domain) => Some(ns.ns),
+            _ => None,
+        })
+        .collect();
+}
+
+fn query(name: &str, qtype: dns::Type) -> dns::Query {
+    let mut request = dns::Query::new(vec![String::from(name)]);
+    request.qtype = qtype;
+    return request;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discovery::{nameserver_names, Delegation, Nameserver};
+    use crate::dns;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::str::FromStr;
+
+    #[test]
+    fn delegation_addrs_and_display() {
+        let delegation = Delegation {
+            domain: "example.com".to_string(),
+            nameservers: vec![
+                Nameserver {
+                    name: "a.iana-servers.net".to_string(),
+                    ipv4: vec![Ipv4Addr::new(10, 0, 0, 1)],
+                    ipv6: vec![Ipv6Addr::from_str("2001:db8::1").unwrap()],
+                },
+                Nameserver {
+                    name: "b.iana-servers.net".to_string(),
+                    ipv4: vec![Ipv4Addr::new(10, 0, 0, 2)],
+                    ipv6: vec![],
+                },
+            ],
+        };
+
+        assert_eq!(
+            vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)],
+            delegation.ipv4_addrs()
+        );
+        assert_eq!(
+            "example.com\n  NS a.iana-servers.net -> 10.0.0.1, 2001:db8::1\n  NS b.iana-servers.net -> 10.0.0.2\n",
+            delegation.to_string()
+        );
+    }
+
+    #[test]
+    fn nameservers_of_other_owners_are_ignored() {
+        let ns = |name: &str, ns: &str| {
+            dns::Record::NS(dns::NSRecord {
+                name: name.to_string(),
+                ttl: 300,
+                ns: ns.to_string(),
+            })
+            .to_rr()
+            .unwrap()
+        };
+        let answers = vec![
+            ns("Example.com", "a.iana-servers.net"),
+            ns("com", "a.gtld-servers.net"),
+        ];
+
+        assert_eq!(
+            vec!["a.iana-servers.net".to_string()],
+            nameserver_names(&answers, "example.com.")
+        );
+        // the parent's NS records do not make a zone of www.example.com
+        assert!(nameserver_names(&answers, "www.example.com").is_empty());
+    }
+}
@@ -3,18 +3,33 @@ mod answer;
 mod authority;
 mod classes;
 mod client;
+pub mod cookie;
+pub mod dnssec;
+mod edns;
 mod header;
-mod hostname;
+pub mod hostname;
 pub mod message;
 mod query;
 mod question;
 mod resource_record;
 mod response;
+mod root_hints;
 mod types;
 
+pub type Class = classes::Class;
 pub type Client = client::Client;
+pub type Edns = edns::Edns;
+pub type EdnsOption = edns::EdnsOption;
+pub type Hostname = hostname::Hostname;
+pub type Opcode = header::Opcode;
 pub type Query = query::Query;
 pub type Response = response::Response;
+pub type RootHints = root_hints::RootHints;
 pub type Record = response::Record;
+pub type ResourceRecord = resource_record::ResourceRecord;
 pub type ARecord = response::ARecord;
+pub type AAAARecord = response::AAAARecord;
 pub type NSRecord = response::NSRecord;
+pub type CNAMERecord = response::CNAMERecord;
+pub type SOARecord = response::SOARecord;
+pub type Type = types::Type;
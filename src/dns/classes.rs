@@ -1,8 +1,26 @@
 use num_derive::FromPrimitive;
+use std::str::FromStr;
 
 #[repr(u16)]
 #[derive(FromPrimitive, PartialEq, Debug, Copy, Clone)]
 /// Subset of CLASS values specified in IETF RFC 1035
 pub enum Class {
-    IN = 1, // the Internet
+    IN = 1,    // the Internet
+    CH = 3,    // the CHAOS class
+    HS = 4,    // Hesiod
+    ANY = 255, // QCLASS only
+}
+
+impl FromStr for Class {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_uppercase().as_str() {
+            "IN" => Ok(Class::IN),
+            "CH" => Ok(Class::CH),
+            "HS" => Ok(Class::HS),
+            "ANY" => Ok(Class::ANY),
+            _ => Err(format!("Unsupported class {}", s)),
+        };
+    }
 }
@@ -1,5 +1,9 @@
+use crate::dns::cookie::{Cookie, BADCOOKIE};
+use crate::dns::edns::Edns;
 use crate::dns::message::Message;
 use crate::dns::query::Query;
+use rand::{Rng, RngCore};
+use std::cell::RefCell;
 use std::net::UdpSocket;
 use std::time::Duration;
 
@@ -12,6 +16,9 @@ pub struct Client {
     server: String,
     port: u16,
     timeout: Duration,
+    /// DNS cookie sent with every query once cookies are enabled, holding the server cookie
+    /// learned from the last response
+    cookie: Option<RefCell<Cookie>>,
 }
 
 impl Client {
@@ -22,16 +29,64 @@ impl Client {
             server,
             port: 53,
             timeout: Duration::new(10, 0),
+            cookie: None,
         };
     }
 
+    /// Sends a DNS cookie as specified in IETF RFC 7873 with every query, using a client cookie
+    /// drawn from the given random number generator, and rejects responses that do not echo it
+    pub fn enable_cookies(&mut self, rng: &mut dyn RngCore) {
+        let mut client = [0; 8];
+        rng.fill(&mut client);
+        self.cookie = Some(RefCell::new(Cookie {
+            client,
+            server: Vec::new(),
+        }));
+    }
+
+    /// Sets how long to wait when sending a message or waiting for a response
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sends the query and waits for the response, drawing the ID of the query and any case
+    /// randomization from the given random number generator
     // TODO: make sure to use an error type that encompasses the IO errors
-    pub fn query(&self, request: Query) -> Result<Message, String> {
-        return self.send_message(&request.to_message()?);
+    pub fn query(&self, mut request: Query, rng: &mut dyn RngCore) -> Result<Message, String> {
+        let mut retried = false;
+        loop {
+            if let Some(cookie) = &self.cookie {
+                let edns = request.edns.get_or_insert_with(Edns::new);
+                cookie.borrow().attach(edns);
+            }
+
+            let message = request.to_message(rng)?;
+            let response = self.send_message(&message)?;
+            // a response that does not echo the 0x20 encoded question may be spoofed
+            if request.case_randomization {
+                message.check_echo(&response)?;
+            }
+
+            if let Some(cookie) = &self.cookie {
+                learn_cookie(&mut cookie.borrow_mut(), &response)?;
+                // a server that requires cookies sends a fresh server cookie along with BADCOOKIE,
+                // so the query is retried once with it
+                if response.rcode() == BADCOOKIE && !retried {
+                    retried = true;
+                    continue;
+                }
+            }
+            return Ok(response);
+        }
     }
 
     pub fn send_message(&self, message: &Message) -> Result<Message, String> {
-        let mut buffer = [0; DNS_MAX_UDP_SIZE];
+        // EDNS lets the server send responses as large as the payload size we advertise
+        let buffer_size = match &message.edns {
+            Some(edns) => DNS_MAX_UDP_SIZE.max(edns.udp_payload_size as usize),
+            None => DNS_MAX_UDP_SIZE,
+        };
+        let mut buffer = vec![0; buffer_size];
         let socket = self.connect()?;
 
         let message_payload = message.to_bytes();
@@ -70,6 +125,10 @@ impl Client {
             Err(e) => return Err(e.to_string()),
             _ => (),
         };
+        match socket.set_read_timeout(Some(self.timeout)) {
+            Err(e) => return Err(e.to_string()),
+            _ => (),
+        };
 
         eprintln!("Bound to local address {}", socket.local_addr().unwrap());
 
@@ -87,3 +146,25 @@ impl Client {
         return Ok(socket);
     }
 }
+
+/// Checks the COOKIE option of a response as specified in section 5.3 of IETF RFC 7873 and
+/// remembers the server cookie it carries
+fn learn_cookie(cookie: &mut Cookie, response: &Message) -> Result<(), String> {
+    let received = match Cookie::find(&response.edns) {
+        // servers that do not support cookies leave the option out
+        None if cookie.server.is_empty() => return Ok(()),
+        None => {
+            return Err("Response is missing the COOKIE option the server sent before".to_string())
+        }
+        Some(Err(e)) => return Err(e),
+        Some(Ok(received)) => received,
+    };
+    if received.client != cookie.client {
+        return Err("Response does not echo the client cookie".to_string());
+    }
+    if received.server.is_empty() {
+        return Err("Response COOKIE option has no server cookie".to_string());
+    }
+    cookie.server = received.server;
+    return Ok(());
+}
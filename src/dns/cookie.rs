@@ -0,0 +1,244 @@
+use crate::dns::edns::{Edns, EdnsOption};
+use std::net::IpAddr;
+
+/// EDNS option code of the COOKIE option
+pub const COOKIE_OPTION: u16 = 10;
+const CLIENT_COOKIE_LENGTH: usize = 8;
+const MIN_SERVER_COOKIE_LENGTH: usize = 8;
+const MAX_SERVER_COOKIE_LENGTH: usize = 32;
+
+/// Extended rcode a server sends when a query has no valid server cookie and one is required
+pub const BADCOOKIE: u16 = 23;
+
+#[derive(PartialEq, Clone, Debug)]
+/// DNS cookie as specified in IETF RFC 7873
+///
+/// The client cookie is chosen by the client and echoed by the server, so an off-path attacker has
+/// to guess it to spoof a response. The server cookie is minted by the server and returned by the
+/// client in later queries to show that it can receive responses at its address.
+pub struct Cookie {
+    pub client: [u8; CLIENT_COOKIE_LENGTH],
+    /// Server cookie, empty if the client does not know one yet
+    pub server: Vec<u8>,
+}
+
+impl Cookie {
+    pub fn to_option(&self) -> EdnsOption {
+        let mut data = self.client.to_vec();
+        data.extend(&self.server);
+        return EdnsOption {
+            code: COOKIE_OPTION,
+            data,
+        };
+    }
+
+    pub fn from_option(option: &EdnsOption) -> Result<Cookie, String> {
+        let length = option.data.len();
+        let valid_server_length = length == CLIENT_COOKIE_LENGTH
+            || (length >= CLIENT_COOKIE_LENGTH + MIN_SERVER_COOKIE_LENGTH
+                && length <= CLIENT_COOKIE_LENGTH + MAX_SERVER_COOKIE_LENGTH);
+        if option.code != COOKIE_OPTION || !valid_server_length {
+            return Err(format!("Malformed COOKIE option of length {}", length));
+        }
+        let mut client = [0; CLIENT_COOKIE_LENGTH];
+        client.copy_from_slice(&option.data[..CLIENT_COOKIE_LENGTH]);
+        return Ok(Cookie {
+            client,
+            server: option.data[CLIENT_COOKIE_LENGTH..].to_vec(),
+        });
+    }
+
+    /// Finds the COOKIE option in a message's OPT record, if there is one
+    pub fn find(edns: &Option<Edns>) -> Option<Result<Cookie, String>> {
+        let option = edns
+            .as_ref()?
+            .options
+            .iter()
+            .find(|option| option.code == COOKIE_OPTION)?;
+        return Some(Cookie::from_option(option));
+    }
+
+    /// Replaces any COOKIE option in the OPT record with this cookie
+    pub fn attach(&self, edns: &mut Edns) {
+        edns.options.retain(|option| option.code != COOKIE_OPTION);
+        edns.options.push(self.to_option());
+    }
+}
+
+/// Secret a server mints its server cookies with, following the interoperable format of IETF RFC 9018
+///
+/// A server cookie is a version, three reserved bytes, a timestamp, and a SipHash-2-4 of the client
+/// cookie, those fields and the client address keyed with the secret. It can be checked without
+/// keeping any state per client.
+pub struct CookieSecret {
+    key: [u8; 16],
+}
+
+impl CookieSecret {
+    /// Server cookies older than this are no longer accepted, in seconds
+    const LIFETIME: u32 = 3600;
+    /// Server cookies from this far in the future are still accepted to allow for clock skew
+    const FUTURE_SKEW: u32 = 300;
+    const VERSION: u8 = 1;
+
+    pub fn new(key: [u8; 16]) -> CookieSecret {
+        return CookieSecret { key };
+    }
+
+    /// Mints the server cookie for the client cookie and address at the time, given in seconds since
+    /// the Unix epoch
+    pub fn mint(
+        &self,
+        client: &[u8; CLIENT_COOKIE_LENGTH],
+        addr: &IpAddr,
+        timestamp: u32,
+    ) -> Vec<u8> {
+        let mut cookie = vec![CookieSecret::VERSION, 0, 0, 0];
+        cookie.extend(&timestamp.to_be_bytes());
+
+        let mut input = client.to_vec();
+        input.extend(&cookie);
+        match addr {
+            IpAddr::V4(ip) => input.extend(&ip.octets()),
+            IpAddr::V6(ip) => input.extend(&ip.octets()),
+        };
+        cookie.extend(&siphash24(&self.key, &input).to_le_bytes());
+        return cookie;
+    }
+
+    /// Whether the cookie carries a server cookie this secret minted for the client recently enough
+    pub fn verify(&self, cookie: &Cookie, addr: &IpAddr, now: u32) -> bool {
+        if cookie.server.len() != 16 || cookie.server[0] != CookieSecret::VERSION {
+            return false;
+        }
+        let timestamp = u32::from_be_bytes([
+            cookie.server[4],
+            cookie.server[5],
+            cookie.server[6],
+            cookie.server[7],
+        ]);
+        // serial number arithmetic so that timestamps keep working when the counter wraps
+        let age = now.wrapping_sub(timestamp) as i32;
+        if age > CookieSecret::LIFETIME as i32 || age < -(CookieSecret::FUTURE_SKEW as i32) {
+            return false;
+        }
+        return self.mint(&cookie.client, addr, timestamp) == cookie.server;
+    }
+}
+
+/// SipHash-2-4 as specified by Aumasson and Bernstein
+fn siphash24(key: &[u8; 16], data: &[u8]) -> u64 {
+    let k0 = u64::from_le_bytes([
+        key[0], key[1], key[2], key[3], key[4], key[5], key[6], key[7],
+    ]);
+    let k1 = u64::from_le_bytes([
+        key[8], key[9], key[10], key[11], key[12], key[13], key[14], key[15],
+    ]);
+    let mut v = [
+        k0 ^ 0x736f6d6570736575,
+        k1 ^ 0x646f72616e646f6d,
+        k0 ^ 0x6c7967656e657261,
+        k1 ^ 0x7465646279746573,
+    ];
+
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    fn compress(m: u64, v: &mut [u64; 4]) {
+        v[3] ^= m;
+        round(v);
+        round(v);
+        v[0] ^= m;
+    }
+
+    let chunks = data.chunks_exact(8);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        compress(u64::from_le_bytes(word), &mut v);
+    }
+    let mut last = [0; 8];
+    last[..remainder.len()].copy_from_slice(remainder);
+    last[7] = data.len() as u8;
+    compress(u64::from_le_bytes(last), &mut v);
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    return v[0] ^ v[1] ^ v[2] ^ v[3];
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::cookie::{siphash24, Cookie, CookieSecret, COOKIE_OPTION};
+    use crate::dns::edns::{Edns, EdnsOption};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn siphash_test_vector() {
+        let mut key = [0; 16];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let data: Vec<u8> = (0..15).collect();
+
+        // from appendix A of the SipHash paper
+        assert_eq!(0xa129ca6149be45e5, siphash24(&key, &data));
+    }
+
+    #[test]
+    fn cookie_option_round_trip() {
+        let cookie = Cookie {
+            client: [1, 2, 3, 4, 5, 6, 7, 8],
+            server: vec![9; 16],
+        };
+        let option = cookie.to_option();
+        assert_eq!(COOKIE_OPTION, option.code);
+        assert_eq!(24, option.data.len());
+        assert_eq!(cookie, Cookie::from_option(&option).unwrap());
+
+        let mut edns = Edns::new();
+        assert_eq!(None, Cookie::find(&Some(edns.clone())));
+        cookie.attach(&mut edns);
+        cookie.attach(&mut edns);
+        assert_eq!(1, edns.options.len());
+        assert_eq!(Some(Ok(cookie)), Cookie::find(&Some(edns)));
+
+        // server cookies must be between 8 and 32 bytes long
+        let short = EdnsOption {
+            code: COOKIE_OPTION,
+            data: vec![0; 12],
+        };
+        assert!(Cookie::from_option(&short).is_err());
+    }
+
+    #[test]
+    fn mint_and_verify_server_cookies() {
+        let secret = CookieSecret::new([7; 16]);
+        let client = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7));
+        let mut cookie = Cookie {
+            client: [1; 8],
+            server: Vec::new(),
+        };
+        cookie.server = secret.mint(&cookie.client, &client, 1_000_000);
+
+        assert!(secret.verify(&cookie, &client, 1_000_000));
+        assert!(secret.verify(&cookie, &client, 1_000_000 + 3600));
+        // expired, minted for another client, or minted with another secret
+        assert!(!secret.verify(&cookie, &client, 1_000_000 + 3601));
+        assert!(!secret.verify(&cookie, &IpAddr::V4(Ipv4Addr::new(10, 0, 0, 8)), 1_000_000));
+        assert!(!CookieSecret::new([8; 16]).verify(&cookie, &client, 1_000_000));
+    }
+}
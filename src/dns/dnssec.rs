@@ -0,0 +1,1028 @@
+use crate::dns::classes::Class;
+use crate::dns::hostname::{same_name, Hostname};
+use crate::dns::resource_record::ResourceRecord;
+use crate::dns::types::Type;
+use ring::{digest, signature};
+use std::cmp::Ordering;
+
+/// Flag of a DNSKEY record that marks it as a zone key, which is required to sign RRsets
+pub const ZONE_KEY: u16 = 0x0100;
+/// Flag of a DNSKEY record that marks it as a key signing key, which only signs the DNSKEY RRset
+pub const SECURE_ENTRY_POINT: u16 = 0x0001;
+/// Protocol field of a DNSKEY record, which must always be 3
+const DNSKEY_PROTOCOL: u8 = 3;
+/// Digest type of a DS record holding a SHA-256 digest, specified in IETF RFC 4509
+pub const DIGEST_SHA256: u8 = 2;
+/// Hash algorithm of NSEC3 records, SHA-1 being the only one specified in IETF RFC 5155
+pub const NSEC3_SHA1: u8 = 1;
+
+/// DNSSEC signing algorithm from the IANA registry that signatures can be checked for
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Algorithm {
+    /// RSA/SHA-256 as specified in IETF RFC 5702
+    RSASHA256 = 8,
+    /// ECDSA P-256 with SHA-256 as specified in IETF RFC 6605
+    ECDSAP256SHA256 = 13,
+    /// Ed25519 as specified in IETF RFC 8080
+    ED25519 = 15,
+}
+
+impl Algorithm {
+    pub fn from_number(number: u8) -> Option<Algorithm> {
+        return match number {
+            8 => Some(Algorithm::RSASHA256),
+            13 => Some(Algorithm::ECDSAP256SHA256),
+            15 => Some(Algorithm::ED25519),
+            _ => None,
+        };
+    }
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_uppercase().as_str() {
+            "RSASHA256" | "8" => Ok(Algorithm::RSASHA256),
+            "ECDSAP256SHA256" | "13" => Ok(Algorithm::ECDSAP256SHA256),
+            "ED25519" | "15" => Ok(Algorithm::ED25519),
+            _ => Err(format!(
+                "Unknown algorithm {}, please enter either RSASHA256, ECDSAP256SHA256, or ED25519",
+                s
+            )),
+        };
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// DNSKEY record as specified in IETF RFC 4034
+pub struct DNSKEYRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub flags: u16,
+    pub protocol: u8,
+    pub algorithm: u8,
+    pub public_key: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// DS record as specified in IETF RFC 4034, which a parent zone holds for each key signing key of a
+/// child zone
+pub struct DSRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub key_tag: u16,
+    pub algorithm: u8,
+    pub digest_type: u8,
+    pub digest: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// RRSIG record as specified in IETF RFC 4034
+pub struct RRSIGRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub type_covered: Type,
+    pub algorithm: u8,
+    /// Number of labels in the owner name of the signed RRset, fewer for wildcard expansions
+    pub labels: u8,
+    pub original_ttl: u32,
+    /// Time the signature expires, in seconds since the Unix epoch
+    pub expiration: u32,
+    /// Time the signature becomes valid, in seconds since the Unix epoch
+    pub inception: u32,
+    pub key_tag: u16,
+    pub signer_name: String,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// NSEC record as specified in IETF RFC 4034
+pub struct NSECRecord {
+    pub name: String,
+    pub ttl: u32,
+    /// Next owner name in the canonical order of the zone
+    pub next_name: String,
+    pub types: Vec<Type>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// NSEC3 record as specified in IETF RFC 5155
+pub struct NSEC3Record {
+    pub name: String,
+    pub ttl: u32,
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+    /// Hash of the next owner name in the hash order of the zone
+    pub next_hashed_owner: Vec<u8>,
+    pub types: Vec<Type>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+/// NSEC3PARAM record as specified in IETF RFC 5155, which tells authoritative servers the
+/// parameters of the NSEC3 chain of the zone
+pub struct NSEC3PARAMRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub hash_algorithm: u8,
+    pub flags: u8,
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+}
+
+impl DNSKEYRecord {
+    pub(crate) fn rdata(&self) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        rdata.extend(&self.flags.to_be_bytes());
+        rdata.push(self.protocol);
+        rdata.push(self.algorithm);
+        rdata.extend(&self.public_key);
+        return rdata;
+    }
+
+    pub(crate) fn from_rdata(name: String, ttl: u32, rdata: &[u8]) -> Result<DNSKEYRecord, String> {
+        if rdata.len() < 4 {
+            return Err(format!(
+                "Invalid DNSKEY record rdata length {}",
+                rdata.len()
+            ));
+        }
+        return Ok(DNSKEYRecord {
+            name,
+            ttl,
+            flags: u16::from_be_bytes([rdata[0], rdata[1]]),
+            protocol: rdata[2],
+            algorithm: rdata[3],
+            public_key: rdata[4..].to_vec(),
+        });
+    }
+
+    pub(crate) fn rdata_to_string(&self) -> String {
+        return format!(
+            "{} {} {} {}",
+            self.flags,
+            self.protocol,
+            self.algorithm,
+            data_encoding::BASE64.encode(&self.public_key)
+        );
+    }
+
+    /// Key tag as calculated in appendix B of IETF RFC 4034, which RRSIG and DS records use to
+    /// refer to the key
+    pub fn key_tag(&self) -> u16 {
+        let mut sum: u32 = 0;
+        for (i, byte) in self.rdata().iter().enumerate() {
+            sum += if i % 2 == 0 {
+                (*byte as u32) << 8
+            } else {
+                *byte as u32
+            };
+        }
+        sum += (sum >> 16) & 0xffff;
+        return (sum & 0xffff) as u16;
+    }
+
+    /// DS record for this key with a SHA-256 digest, to be published in the parent zone
+    pub fn ds(&self) -> Result<DSRecord, String> {
+        let mut data = canonical_name(&self.name)?;
+        data.extend(self.rdata());
+        return Ok(DSRecord {
+            name: self.name.clone(),
+            ttl: self.ttl,
+            key_tag: self.key_tag(),
+            algorithm: self.algorithm,
+            digest_type: DIGEST_SHA256,
+            digest: digest::digest(&digest::SHA256, &data).as_ref().to_vec(),
+        });
+    }
+
+    /// Whether the DS record refers to this key
+    pub fn matches(&self, ds: &DSRecord) -> bool {
+        if ds.digest_type != DIGEST_SHA256 || !same_name(&ds.name, &self.name) {
+            return false;
+        }
+        return match self.ds() {
+            Err(_) => false,
+            Ok(own) => own.key_tag == ds.key_tag && own.digest == ds.digest,
+        };
+    }
+
+    /// Checks the signature over the data with this key
+    pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), String> {
+        if self.protocol != DNSKEY_PROTOCOL {
+            return Err(format!("DNSKEY has invalid protocol {}", self.protocol));
+        }
+        let result = match Algorithm::from_number(self.algorithm) {
+            None => return Err(format!("Unsupported DNSSEC algorithm {}", self.algorithm)),
+            Some(Algorithm::RSASHA256) => {
+                // the public key is the exponent length, the exponent and then the modulus, as
+                // specified in IETF RFC 3110
+                let key = &self.public_key;
+                let (length, offset) = match key.first() {
+                    Some(0) if key.len() > 3 => (u16::from_be_bytes([key[1], key[2]]) as usize, 3),
+                    Some(length) => (*length as usize, 1),
+                    None => return Err("DNSKEY has an empty RSA public key".to_string()),
+                };
+                if key.len() <= offset + length {
+                    return Err("DNSKEY has a truncated RSA public key".to_string());
+                }
+                let components = signature::RsaPublicKeyComponents {
+                    n: &key[offset + length..],
+                    e: &key[offset..offset + length],
+                };
+                components.verify(
+                    &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                    data,
+                    signature,
+                )
+            }
+            Some(Algorithm::ECDSAP256SHA256) => {
+                // DNSKEY records hold the bare point, without the uncompressed point marker
+                let mut point = vec![0x04];
+                point.extend(&self.public_key);
+                signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                    .verify(data, signature)
+            }
+            Some(Algorithm::ED25519) => {
+                signature::UnparsedPublicKey::new(&signature::ED25519, &self.public_key)
+                    .verify(data, signature)
+            }
+        };
+        return match result {
+            Err(_) => Err(format!(
+                "Signature does not verify with key {}",
+                self.key_tag()
+            )),
+            Ok(()) => Ok(()),
+        };
+    }
+}
+
+impl DSRecord {
+    pub(crate) fn rdata(&self) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        rdata.extend(&self.key_tag.to_be_bytes());
+        rdata.push(self.algorithm);
+        rdata.push(self.digest_type);
+        rdata.extend(&self.digest);
+        return rdata;
+    }
+
+    pub(crate) fn from_rdata(name: String, ttl: u32, rdata: &[u8]) -> Result<DSRecord, String> {
+        if rdata.len() < 4 {
+            return Err(format!("Invalid DS record rdata length {}", rdata.len()));
+        }
+        return Ok(DSRecord {
+            name,
+            ttl,
+            key_tag: u16::from_be_bytes([rdata[0], rdata[1]]),
+            algorithm: rdata[2],
+            digest_type: rdata[3],
+            digest: rdata[4..].to_vec(),
+        });
+    }
+
+    pub(crate) fn rdata_to_string(&self) -> String {
+        return format!(
+            "{} {} {} {}",
+            self.key_tag,
+            self.algorithm,
+            self.digest_type,
+            data_encoding::HEXUPPER.encode(&self.digest)
+        );
+    }
+}
+
+impl RRSIGRecord {
+    /// Rdata up to the signature, which is the start of the data the signature is made over
+    fn rdata_without_signature(&self) -> Result<Vec<u8>, String> {
+        let mut rdata = Vec::new();
+        rdata.extend(&self.type_covered.to_u16().to_be_bytes());
+        rdata.push(self.algorithm);
+        rdata.push(self.labels);
+        rdata.extend(&self.original_ttl.to_be_bytes());
+        rdata.extend(&self.expiration.to_be_bytes());
+        rdata.extend(&self.inception.to_be_bytes());
+        rdata.extend(&self.key_tag.to_be_bytes());
+        rdata.extend(canonical_name(&self.signer_name)?);
+        return Ok(rdata);
+    }
+
+    pub(crate) fn rdata(&self) -> Result<Vec<u8>, String> {
+        let mut rdata = self.rdata_without_signature()?;
+        rdata.extend(&self.signature);
+        return Ok(rdata);
+    }
+
+    pub(crate) fn from_rdata(name: String, ttl: u32, rdata: &[u8]) -> Result<RRSIGRecord, String> {
+        if rdata.len() < 19 {
+            return Err(format!("Invalid RRSIG record rdata length {}", rdata.len()));
+        }
+        let u32_at =
+            |i: usize| u32::from_be_bytes([rdata[i], rdata[i + 1], rdata[i + 2], rdata[i + 3]]);
+        let signer = Hostname::parse(&rdata[18..])?;
+        return Ok(RRSIGRecord {
+            name,
+            ttl,
+            type_covered: Type::from_u16(u16::from_be_bytes([rdata[0], rdata[1]])),
+            algorithm: rdata[2],
+            labels: rdata[3],
+            original_ttl: u32_at(4),
+            expiration: u32_at(8),
+            inception: u32_at(12),
+            key_tag: u16::from_be_bytes([rdata[16], rdata[17]]),
+            signer_name: signer.hostname.to_string(),
+            signature: rdata[18 + signer.parsed_bytes as usize..].to_vec(),
+        });
+    }
+
+    pub(crate) fn rdata_to_string(&self) -> String {
+        return format!(
+            "{} {} {} {} {} {} {} {} {}",
+            self.type_covered,
+            self.algorithm,
+            self.labels,
+            self.original_ttl,
+            format_time(self.expiration),
+            format_time(self.inception),
+            self.key_tag,
+            presentation_name(&self.signer_name),
+            data_encoding::BASE64.encode(&self.signature)
+        );
+    }
+
+    /// Data the signature is made over for the RRset, as specified in section 3.1.8.1 of IETF
+    /// RFC 4034
+    ///
+    /// The records are put in canonical form with the original TTL of the signature, and the owner
+    /// name of a wildcard expansion is replaced by the wildcard it was expanded from.
+    pub fn signed_data(&self, rrset: &[ResourceRecord]) -> Result<Vec<u8>, String> {
+        let mut data = self.rdata_without_signature()?;
+        for rr in canonical_rrset(rrset)? {
+            let mut name = rr.name.to_string();
+            let labels = rr.name.label_count();
+            if labels < self.labels {
+                return Err(format!(
+                    "RRSIG has {} labels but {} only has {}",
+                    self.labels, name, labels
+                ));
+            }
+            if labels > self.labels {
+                let suffix: Vec<&str> = name
+                    .split('.')
+                    .skip((labels - self.labels) as usize)
+                    .collect();
+                name = if suffix.is_empty() {
+                    "*".to_string()
+                } else {
+                    format!("*.{}", suffix.join("."))
+                };
+            }
+            data.extend(canonical_name(&name)?);
+            data.extend(&rr.rtype.to_u16().to_be_bytes());
+            data.extend(&(rr.class as u16).to_be_bytes());
+            data.extend(&self.original_ttl.to_be_bytes());
+            data.extend(&rr.rdlength.to_be_bytes());
+            data.extend(&rr.rdata);
+        }
+        return Ok(data);
+    }
+}
+
+impl NSECRecord {
+    pub(crate) fn rdata(&self) -> Result<Vec<u8>, String> {
+        // the next name keeps its case, as clarified in section 5.1 of IETF RFC 6840
+        let mut rdata = Hostname::from_string(&self.next_name)?.to_bytes();
+        rdata.extend(type_bitmap(&self.types));
+        return Ok(rdata);
+    }
+
+    pub(crate) fn from_rdata(name: String, ttl: u32, rdata: &[u8]) -> Result<NSECRecord, String> {
+        let next = Hostname::parse(rdata)?;
+        return Ok(NSECRecord {
+            name,
+            ttl,
+            next_name: next.hostname.to_string(),
+            types: parse_type_bitmap(&rdata[next.parsed_bytes as usize..])?,
+        });
+    }
+
+    pub(crate) fn rdata_to_string(&self) -> String {
+        return format!(
+            "{} {}",
+            presentation_name(&self.next_name),
+            format_types(&self.types)
+        );
+    }
+
+    /// Whether the name falls between the owner and the next name, which proves that it does not
+    /// exist
+    pub fn covers(&self, name: &str) -> bool {
+        let after_owner = compare_names(&self.name, name) == Ordering::Less;
+        let before_next = compare_names(name, &self.next_name) == Ordering::Less;
+        // the last NSEC record of the zone points back to the apex
+        if compare_names(&self.name, &self.next_name) != Ordering::Less {
+            return after_owner || before_next;
+        }
+        return after_owner && before_next;
+    }
+}
+
+impl NSEC3Record {
+    pub(crate) fn rdata(&self) -> Vec<u8> {
+        let mut rdata = vec![self.hash_algorithm, self.flags];
+        rdata.extend(&self.iterations.to_be_bytes());
+        rdata.push(self.salt.len() as u8);
+        rdata.extend(&self.salt);
+        rdata.push(self.next_hashed_owner.len() as u8);
+        rdata.extend(&self.next_hashed_owner);
+        rdata.extend(type_bitmap(&self.types));
+        return rdata;
+    }
+
+    pub(crate) fn from_rdata(name: String, ttl: u32, rdata: &[u8]) -> Result<NSEC3Record, String> {
+        let invalid = || format!("Invalid NSEC3 record rdata length {}", rdata.len());
+        let salt_length = *rdata.get(4).ok_or_else(invalid)? as usize;
+        let salt = rdata.get(5..5 + salt_length).ok_or_else(invalid)?;
+        let hash_length = *rdata.get(5 + salt_length).ok_or_else(invalid)? as usize;
+        let hash_start = 6 + salt_length;
+        let hash = rdata
+            .get(hash_start..hash_start + hash_length)
+            .ok_or_else(invalid)?;
+        return Ok(NSEC3Record {
+            name,
+            ttl,
+            hash_algorithm: rdata[0],
+            flags: rdata[1],
+            iterations: u16::from_be_bytes([rdata[2], rdata[3]]),
+            salt: salt.to_vec(),
+            next_hashed_owner: hash.to_vec(),
+            types: parse_type_bitmap(&rdata[hash_start + hash_length..])?,
+        });
+    }
+
+    pub(crate) fn rdata_to_string(&self) -> String {
+        return format!(
+            "{} {} {} {} {} {}",
+            self.hash_algorithm,
+            self.flags,
+            self.iterations,
+            format_salt(&self.salt),
+            data_encoding::BASE32HEX_NOPAD.encode(&self.next_hashed_owner),
+            format_types(&self.types)
+        );
+    }
+
+    /// Hash held in the first label of the owner name
+    pub fn owner_hash(&self) -> Result<Vec<u8>, String> {
+        let label = self.name.split('.').next().unwrap_or("");
+        return match data_encoding::BASE32HEX_NOPAD.decode(label.to_ascii_uppercase().as_bytes()) {
+            Err(_) => Err(format!(
+                "NSEC3 record has an invalid owner name {}",
+                self.name
+            )),
+            Ok(hash) => Ok(hash),
+        };
+    }
+
+    /// Whether the hash falls between the hashed owner and the next hashed owner, which proves
+    /// that no name with that hash exists
+    pub fn covers(&self, hash: &[u8]) -> Result<bool, String> {
+        let owner = self.owner_hash()?;
+        let next = self.next_hashed_owner.as_slice();
+        // the last NSEC3 record of the zone points back to the first
+        if owner.as_slice() >= next {
+            return Ok(owner.as_slice() < hash || hash < next);
+        }
+        return Ok(owner.as_slice() < hash && hash < next);
+    }
+}
+
+impl NSEC3PARAMRecord {
+    pub(crate) fn rdata(&self) -> Vec<u8> {
+        let mut rdata = vec![self.hash_algorithm, self.flags];
+        rdata.extend(&self.iterations.to_be_bytes());
+        rdata.push(self.salt.len() as u8);
+        rdata.extend(&self.salt);
+        return rdata;
+    }
+
+    pub(crate) fn from_rdata(
+        name: String,
+        ttl: u32,
+        rdata: &[u8],
+    ) -> Result<NSEC3PARAMRecord, String> {
+        let salt_length = *rdata.get(4).unwrap_or(&0) as usize;
+        if rdata.len() != 5 + salt_length {
+            return Err(format!(
+                "Invalid NSEC3PARAM record rdata length {}",
+                rdata.len()
+            ));
+        }
+        return Ok(NSEC3PARAMRecord {
+            name,
+            ttl,
+            hash_algorithm: rdata[0],
+            flags: rdata[1],
+            iterations: u16::from_be_bytes([rdata[2], rdata[3]]),
+            salt: rdata[5..].to_vec(),
+        });
+    }
+
+    pub(crate) fn rdata_to_string(&self) -> String {
+        return format!(
+            "{} {} {} {}",
+            self.hash_algorithm,
+            self.flags,
+            self.iterations,
+            format_salt(&self.salt)
+        );
+    }
+}
+
+/// Sorts the records of an RRset into the canonical order of section 6.3 of IETF RFC 4034, with
+/// duplicates removed and owner names lowercased
+///
+/// Names in the rdata of NS and CNAME records are lowercased as well, following section 6.2.
+pub fn canonical_rrset(rrset: &[ResourceRecord]) -> Result<Vec<ResourceRecord>, String> {
+    let mut canonical = Vec::new();
+    for rr in rrset {
+        let mut rr = rr.clone();
+        rr.name = Hostname::from_string(&rr.name.to_string().to_ascii_lowercase())?;
+        if rr.rtype == Type::NS || rr.rtype == Type::CNAME {
+            let name = Hostname::parse(&rr.rdata)?.hostname.to_string();
+            rr.rdata = canonical_name(&name)?;
+            rr.rdlength = rr.rdata.len() as u16;
+        }
+        canonical.push(rr);
+    }
+    canonical.sort_by(|a, b| a.rdata.cmp(&b.rdata));
+    canonical.dedup_by(|a, b| a.rdata == b.rdata);
+    return Ok(canonical);
+}
+
+/// Compares names in the canonical order of section 6.1 of IETF RFC 4034, which sorts by the
+/// labels from the root down, ignoring case
+pub fn compare_names(a: &str, b: &str) -> Ordering {
+    let labels = |name: &str| -> Vec<Vec<u8>> {
+        return name
+            .trim_end_matches('.')
+            .split('.')
+            .filter(|label| !label.is_empty())
+            .rev()
+            .map(|label| label.to_ascii_lowercase().into_bytes())
+            .collect();
+    };
+    return labels(a).cmp(&labels(b));
+}
+
+/// Hashes the name as specified in section 5 of IETF RFC 5155, for the owner names of NSEC3
+/// records
+pub fn nsec3_hash(name: &str, salt: &[u8], iterations: u16) -> Result<Vec<u8>, String> {
+    let mut hash = canonical_name(name)?;
+    for _ in 0..=iterations {
+        let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+        context.update(&hash);
+        context.update(salt);
+        hash = context.finish().as_ref().to_vec();
+    }
+    return Ok(hash);
+}
+
+/// Owner name of the NSEC3 record for the hash in the zone
+pub fn nsec3_owner(hash: &[u8], zone: &str) -> String {
+    let label = data_encoding::BASE32HEX_NOPAD
+        .encode(hash)
+        .to_ascii_lowercase();
+    if zone.is_empty() {
+        return label;
+    }
+    return format!("{}.{}", label, zone);
+}
+
+/// Name as written in zone files and query output, where the root is a single dot
+pub(crate) fn presentation_name(name: &str) -> &str {
+    if name.is_empty() {
+        return ".";
+    }
+    return name;
+}
+
+/// Lowercase uncompressed wire format of the name
+pub(crate) fn canonical_name(name: &str) -> Result<Vec<u8>, String> {
+    return Ok(Hostname::from_string(&name.to_ascii_lowercase())?.to_bytes());
+}
+
+/// Type bitmap of NSEC and NSEC3 records as specified in section 4.1.2 of IETF RFC 4034
+fn type_bitmap(types: &[Type]) -> Vec<u8> {
+    let mut numbers: Vec<u16> = types.iter().map(|t| t.to_u16()).collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let mut bitmap = Vec::new();
+    let mut window: Vec<u8> = Vec::new();
+    let mut current = None;
+    for number in numbers {
+        let block = (number >> 8) as u8;
+        if current != Some(block) {
+            if let Some(previous) = current {
+                bitmap.push(previous);
+                bitmap.push(window.len() as u8);
+                bitmap.extend(&window);
+            }
+            current = Some(block);
+            window.clear();
+        }
+        let bit = (number & 0xff) as usize;
+        if window.len() <= bit / 8 {
+            window.resize(bit / 8 + 1, 0);
+        }
+        window[bit / 8] |= 0x80 >> (bit % 8);
+    }
+    if let Some(block) = current {
+        bitmap.push(block);
+        bitmap.push(window.len() as u8);
+        bitmap.extend(&window);
+    }
+    return bitmap;
+}
+
+fn parse_type_bitmap(bitmap: &[u8]) -> Result<Vec<Type>, String> {
+    let mut types = Vec::new();
+    let mut offset = 0;
+    while offset < bitmap.len() {
+        let (block, length) = match bitmap.get(offset..offset + 2) {
+            Some([block, length]) if *length >= 1 && *length <= 32 => {
+                (*block as u16, *length as usize)
+            }
+            _ => return Err("Malformed type bitmap".to_string()),
+        };
+        let window = match bitmap.get(offset + 2..offset + 2 + length) {
+            None => return Err("Type bitmap runs past the end of the rdata".to_string()),
+            Some(w) => w,
+        };
+        for (i, byte) in window.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    types.push(Type::from_u16(block << 8 | (i * 8 + bit) as u16));
+                }
+            }
+        }
+        offset += 2 + length;
+    }
+    return Ok(types);
+}
+
+fn format_salt(salt: &[u8]) -> String {
+    if salt.is_empty() {
+        return "-".to_string();
+    }
+    return data_encoding::HEXUPPER.encode(salt);
+}
+
+fn format_types(types: &[Type]) -> String {
+    let names: Vec<String> = types.iter().map(|t| format!("{}", t)).collect();
+    return names.join(" ");
+}
+
+/// Formats a signature time as YYYYMMDDHHmmSS in UTC, the presentation format of RRSIG records
+pub(crate) fn format_time(time: u32) -> String {
+    let days = time / 86400;
+    let seconds = time % 86400;
+    let (year, month, day) = civil_from_days(days as i64);
+    return format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+}
+
+/// Parses a signature time given either as YYYYMMDDHHmmSS in UTC or as seconds since the epoch
+pub(crate) fn parse_time(s: &str) -> Result<u32, String> {
+    // slicing the fields by bytes is only safe once every character is known to be a digit
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("Invalid signature time {}", s));
+    }
+    if s.len() != 14 {
+        return match s.parse::<u32>() {
+            Err(_) => Err(format!("Invalid signature time {}", s)),
+            Ok(time) => Ok(time),
+        };
+    }
+    let field = |range: std::ops::Range<usize>| s[range].parse::<i64>();
+    let parsed = (
+        field(0..4),
+        field(4..6),
+        field(6..8),
+        field(8..10),
+        field(10..12),
+        field(12..14),
+    );
+    let (year, month, day, hour, minute, second) = match parsed {
+        (Ok(y), Ok(mo), Ok(d), Ok(h), Ok(mi), Ok(s)) => (y, mo, d, h, mi, s),
+        _ => return Err(format!("Invalid signature time {}", s)),
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(format!("Invalid signature time {}", s));
+    }
+    let time = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    // times wrap around every 136 years, as specified in section 3.1.5 of IETF RFC 4034
+    return Ok(time as u32);
+}
+
+/// Converts days since the Unix epoch to a proleptic Gregorian date, following Howard Hinnant's
+/// civil_from_days algorithm
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    return (year, month, day);
+}
+
+/// Inverse of civil_from_days
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    return era * 146097 + doe - 719468;
+}
+
+/// Resource record for the rdata of one of the DNSSEC types
+pub(crate) fn resource_record(
+    name: &str,
+    rtype: Type,
+    ttl: u32,
+    rdata: Vec<u8>,
+) -> Result<ResourceRecord, String> {
+    return Ok(ResourceRecord {
+        name: Hostname::from_string(name)?,
+        rtype,
+        class: Class::IN,
+        ttl,
+        rdlength: rdata.len() as u16,
+        rdata,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::dnssec::{
+        canonical_rrset, compare_names, nsec3_hash, nsec3_owner, parse_time, parse_type_bitmap,
+        type_bitmap, DNSKEYRecord, DSRecord, NSEC3PARAMRecord, NSEC3Record, NSECRecord,
+        RRSIGRecord,
+    };
+    use crate::dns::response::{ARecord, Record};
+    use crate::dns::types::Type;
+
+    fn ksk() -> DNSKEYRecord {
+        // KSK-2017, the key signing key of the root zone
+        let public_key = data_encoding::BASE64
+            .decode(
+                b"AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=",
+            )
+            .unwrap();
+        return DNSKEYRecord {
+            name: ".".to_string(),
+            ttl: 172800,
+            flags: 257,
+            protocol: 3,
+            algorithm: 8,
+            public_key,
+        };
+    }
+
+    #[test]
+    fn key_tag_and_ds_of_root_ksk() {
+        let ksk = ksk();
+        assert_eq!(20326, ksk.key_tag());
+
+        // the DS record of KSK-2017 published by IANA
+        let expected = DSRecord {
+            name: ".".to_string(),
+            ttl: 172800,
+            key_tag: 20326,
+            algorithm: 8,
+            digest_type: 2,
+            digest: data_encoding::HEXUPPER
+                .decode(b"E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D")
+                .unwrap(),
+        };
+        assert_eq!(expected, ksk.ds().unwrap());
+        assert!(ksk.matches(&expected));
+    }
+
+    #[test]
+    fn dnssec_rdata_round_trip() {
+        let ksk = ksk();
+        assert_eq!(
+            ksk,
+            DNSKEYRecord::from_rdata(".".to_string(), 172800, &ksk.rdata()).unwrap()
+        );
+
+        let rrsig = RRSIGRecord {
+            name: "example.com".to_string(),
+            ttl: 3600,
+            type_covered: Type::NS,
+            algorithm: 15,
+            labels: 2,
+            original_ttl: 3600,
+            expiration: 1_700_000_000,
+            inception: 1_600_000_000,
+            key_tag: 12345,
+            signer_name: "example.com".to_string(),
+            signature: vec![1, 2, 3],
+        };
+        let rdata = rrsig.rdata().unwrap();
+        assert_eq!(
+            rrsig,
+            RRSIGRecord::from_rdata("example.com".to_string(), 3600, &rdata).unwrap()
+        );
+
+        let nsec = NSECRecord {
+            name: "example.com".to_string(),
+            ttl: 3600,
+            next_name: "www.example.com".to_string(),
+            types: vec![Type::NS, Type::SOA, Type::RRSIG, Type::NSEC, Type::DNSKEY],
+        };
+        assert_eq!(
+            nsec,
+            NSECRecord::from_rdata("example.com".to_string(), 3600, &nsec.rdata().unwrap())
+                .unwrap()
+        );
+
+        let nsec3 = NSEC3Record {
+            name: "example.com".to_string(),
+            ttl: 3600,
+            hash_algorithm: 1,
+            flags: 0,
+            iterations: 0,
+            salt: vec![0xab],
+            next_hashed_owner: vec![7; 20],
+            types: vec![Type::A, Type::RRSIG],
+        };
+        assert_eq!(
+            nsec3,
+            NSEC3Record::from_rdata("example.com".to_string(), 3600, &nsec3.rdata()).unwrap()
+        );
+        assert_eq!(
+            "1 0 0 AB 0S3GE1O70S3GE1O70S3GE1O70S3GE1O7 A RRSIG",
+            nsec3.rdata_to_string()
+        );
+
+        let nsec3param = NSEC3PARAMRecord {
+            name: "example.com".to_string(),
+            ttl: 0,
+            hash_algorithm: 1,
+            flags: 0,
+            iterations: 12,
+            salt: vec![0xaa, 0xbb, 0xcc, 0xdd],
+        };
+        assert_eq!(
+            nsec3param,
+            NSEC3PARAMRecord::from_rdata("example.com".to_string(), 0, &nsec3param.rdata())
+                .unwrap()
+        );
+        assert_eq!("1 0 12 AABBCCDD", nsec3param.rdata_to_string());
+    }
+
+    #[test]
+    fn type_bitmaps() {
+        // example from section 4.3 of IETF RFC 4034
+        let types = vec![Type::A, Type::MX, Type::RRSIG, Type::NSEC];
+        let bitmap = type_bitmap(&types);
+        assert_eq!(vec![0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03], bitmap);
+        assert_eq!(types, parse_type_bitmap(&bitmap).unwrap());
+        assert!(parse_type_bitmap(&[0x00, 0x06, 0x40]).is_err());
+    }
+
+    #[test]
+    fn canonical_order() {
+        let a = |name: &str, ip: [u8; 4]| {
+            return Record::A(ARecord {
+                name: name.to_string(),
+                ttl: 300,
+                ip,
+            })
+            .to_rr()
+            .unwrap();
+        };
+        let rrset = vec![
+            a("WWW.Example.com", [10, 0, 0, 2]),
+            a("www.example.com", [10, 0, 0, 1]),
+            a("www.example.com", [10, 0, 0, 2]),
+        ];
+        let canonical = canonical_rrset(&rrset).unwrap();
+        assert_eq!(2, canonical.len());
+        assert_eq!(vec![10, 0, 0, 1], canonical[0].rdata);
+        assert_eq!("www.example.com", canonical[1].name.to_string());
+    }
+
+    #[test]
+    fn canonical_name_order() {
+        // names from the example in section 6.1 of IETF RFC 4034
+        let ordered = [
+            "example",
+            "a.example",
+            "yljkjljk.a.example",
+            "Z.a.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            "*.z.example",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(std::cmp::Ordering::Less, compare_names(pair[0], pair[1]));
+        }
+        assert_eq!(std::cmp::Ordering::Less, compare_names("", "example"));
+
+        let nsec = NSECRecord {
+            name: "a.example".to_string(),
+            ttl: 3600,
+            next_name: "z.example".to_string(),
+            types: vec![Type::A],
+        };
+        assert!(nsec.covers("b.a.example"));
+        assert!(nsec.covers("m.example"));
+        assert!(!nsec.covers("a.example"));
+        assert!(!nsec.covers("www.z.example"));
+        let last = NSECRecord {
+            name: "z.example".to_string(),
+            next_name: "example".to_string(),
+            ..nsec
+        };
+        assert!(last.covers("zz.example"));
+        assert!(!last.covers("b.example"));
+    }
+
+    #[test]
+    fn nsec3_hashes() {
+        // the example zone from appendix A of IETF RFC 5155
+        let salt = [0xaa, 0xbb, 0xcc, 0xdd];
+        let hash = nsec3_hash("example", &salt, 12).unwrap();
+        assert_eq!(
+            "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example",
+            nsec3_owner(&hash, "example")
+        );
+        let hash = nsec3_hash("a.example", &salt, 12).unwrap();
+        assert_eq!(
+            "35mthgpgcu1qg68fab165klnsnk3dpvl.example",
+            nsec3_owner(&hash, "example")
+        );
+
+        let nsec3 = NSEC3Record {
+            name: "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example".to_string(),
+            ttl: 3600,
+            hash_algorithm: 1,
+            flags: 0,
+            iterations: 12,
+            salt: salt.to_vec(),
+            next_hashed_owner: hash,
+            types: vec![Type::A],
+        };
+        let hash = |label: &str| {
+            return data_encoding::BASE32HEX_NOPAD
+                .decode(label.to_ascii_uppercase().as_bytes())
+                .unwrap();
+        };
+        assert!(nsec3
+            .covers(&hash("2t7b4g4vsa5smi47k61mv5bv1a22bojr"))
+            .unwrap());
+        assert!(!nsec3
+            .covers(&hash("4g6p9u5gvfshp30pqecj98b3maqbn1ck"))
+            .unwrap());
+        assert!(!nsec3.covers(&nsec3.owner_hash().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn signature_times() {
+        assert_eq!(1_700_000_000, parse_time("20231114221320").unwrap());
+        assert_eq!(
+            "20231114221320",
+            crate::dns::dnssec::format_time(1_700_000_000)
+        );
+        assert_eq!(1_700_000_000, parse_time("1700000000").unwrap());
+        assert!(parse_time("20231314221320").is_err());
+        // fourteen bytes, but a multibyte character would be sliced through
+        assert!(parse_time("202é123456789").is_err());
+        assert!(parse_time("+1700000000").is_err());
+    }
+}
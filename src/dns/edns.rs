@@ -0,0 +1,201 @@
+use crate::dns::types::Type;
+
+/// Payload size recommended by the DNS flag day 2020 to avoid IP fragmentation
+const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+/// Bit in the flags half of the OPT TTL field that signals DNSSEC records are wanted
+const DNSSEC_OK: u32 = 1 << 15;
+
+#[derive(PartialEq, Clone, Debug)]
+/// Option carried in the rdata of an OPT record
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+/// EDNS(0) OPT pseudo-record as specified in IETF RFC 6891
+///
+/// The OPT record reuses the CLASS field for the UDP payload size and the TTL field for the
+/// extended rcode, version and flags, so it is kept apart from the regular resource records of a
+/// message.
+pub struct Edns {
+    pub udp_payload_size: u16,
+    /// Upper 8 bits of the 12 bit extended rcode
+    pub extended_rcode: u8,
+    pub version: u8,
+    /// DO bit, specified in IETF RFC 3225
+    pub dnssec_ok: bool,
+    pub options: Vec<EdnsOption>,
+}
+
+pub(crate) struct ParsedEdns {
+    /// Number of buffer bytes parsed to construct the OPT record
+    pub(crate) parsed_bytes: usize,
+    pub(crate) edns: Edns,
+}
+
+impl Edns {
+    pub fn new() -> Edns {
+        return Edns {
+            udp_payload_size: DEFAULT_UDP_PAYLOAD_SIZE,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        };
+    }
+
+    /// Whether the buffer starts with an OPT record, which always has the root as its owner
+    pub(crate) fn is_opt(buffer: &[u8]) -> bool {
+        return buffer.len() >= 3
+            && buffer[0] == 0
+            && u16::from_be_bytes([buffer[1], buffer[2]]) == Type::OPT.to_u16();
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let rdata: Vec<u8> = self
+            .options
+            .iter()
+            .flat_map(|option| {
+                let mut bytes = Vec::new();
+                bytes.extend(&option.code.to_be_bytes());
+                bytes.extend(&(option.data.len() as u16).to_be_bytes());
+                bytes.extend(&option.data);
+                return bytes;
+            })
+            .collect();
+
+        let mut ttl = (self.extended_rcode as u32) << 24 | (self.version as u32) << 16;
+        if self.dnssec_ok {
+            ttl |= DNSSEC_OK;
+        }
+
+        let mut bytes = vec![0]; // root owner name
+        bytes.extend(&Type::OPT.to_u16().to_be_bytes());
+        bytes.extend(&self.udp_payload_size.to_be_bytes());
+        bytes.extend(&ttl.to_be_bytes());
+        bytes.extend(&(rdata.len() as u16).to_be_bytes());
+        bytes.extend(rdata);
+        return bytes;
+    }
+
+    pub(crate) fn parse(buffer: &[u8]) -> Result<ParsedEdns, String> {
+        const FIXED_LENGTH: usize = 11;
+
+        if !Edns::is_opt(buffer) {
+            return Err("Expected an OPT record".to_string());
+        }
+        if buffer.len() < FIXED_LENGTH {
+            return Err("OPT record is truncated".to_string());
+        }
+
+        let udp_payload_size = u16::from_be_bytes([buffer[3], buffer[4]]);
+        let ttl = u32::from_be_bytes([buffer[5], buffer[6], buffer[7], buffer[8]]);
+        let rdlength = u16::from_be_bytes([buffer[9], buffer[10]]) as usize;
+        if buffer.len() < FIXED_LENGTH + rdlength {
+            return Err("OPT record rdata is truncated".to_string());
+        }
+        let rdata = &buffer[FIXED_LENGTH..FIXED_LENGTH + rdlength];
+
+        let mut options = Vec::new();
+        let mut offset = 0;
+        while offset < rdata.len() {
+            if offset + 4 > rdata.len() {
+                return Err("EDNS option header is truncated".to_string());
+            }
+            let code = u16::from_be_bytes([rdata[offset], rdata[offset + 1]]);
+            let length = u16::from_be_bytes([rdata[offset + 2], rdata[offset + 3]]) as usize;
+            offset += 4;
+            if offset + length > rdata.len() {
+                return Err(format!("EDNS option {} is truncated", code));
+            }
+            options.push(EdnsOption {
+                code,
+                data: rdata[offset..offset + length].to_vec(),
+            });
+            offset += length;
+        }
+
+        return Ok(ParsedEdns {
+            parsed_bytes: FIXED_LENGTH + rdlength,
+            edns: Edns {
+                udp_payload_size,
+                extended_rcode: (ttl >> 24) as u8,
+                version: (ttl >> 16) as u8,
+                dnssec_ok: ttl & DNSSEC_OK != 0,
+                options,
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::edns::{Edns, EdnsOption};
+
+    #[test]
+    fn edns_to_bytes() {
+        let edns = Edns {
+            dnssec_ok: true,
+            options: vec![EdnsOption {
+                code: 10,
+                data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            }],
+            ..Edns::new()
+        };
+
+        let mut expected: Vec<u8> = vec![
+            0, // root
+            0, 41, // OPT
+            0x04, 0xd0, // 1232 byte payload
+            0, 0, 0x80, 0, // DO bit set
+            0, 12, // rdlength
+            0, 10, 0, 8, // cookie option header
+        ];
+        expected.extend(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        assert_eq!(expected, edns.to_bytes());
+    }
+
+    #[test]
+    fn parse_edns() {
+        let edns = Edns {
+            udp_payload_size: 4096,
+            extended_rcode: 1,
+            options: vec![
+                EdnsOption {
+                    code: 3,
+                    data: vec![],
+                },
+                EdnsOption {
+                    code: 10,
+                    data: vec![0xab; 8],
+                },
+            ],
+            ..Edns::new()
+        };
+        let mut bytes = edns.to_bytes();
+        let length = bytes.len();
+        bytes.extend(&[0xff, 0xff]);
+
+        let parsed = Edns::parse(bytes.as_slice()).unwrap();
+
+        assert_eq!(length, parsed.parsed_bytes);
+        assert_eq!(edns, parsed.edns);
+    }
+
+    #[test]
+    fn parse_truncated_edns() {
+        let edns = Edns {
+            options: vec![EdnsOption {
+                code: 10,
+                data: vec![0xab; 8],
+            }],
+            ..Edns::new()
+        };
+        let bytes = edns.to_bytes();
+
+        assert!(Edns::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Edns::parse(&bytes[1..]).is_err());
+    }
+}
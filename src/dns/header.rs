@@ -14,7 +14,11 @@ pub struct Header {
     pub tc: bool,
     pub rd: bool,
     pub ra: bool,
-    pub z: u8,     // ideally u3
+    pub z: u8, // ideally u1
+    /// authentic data, specified in IETF RFC 4035
+    pub ad: bool,
+    /// checking disabled, specified in IETF RFC 4035
+    pub cd: bool,
     pub rcode: u8, // ideally u4
     pub qdcount: u16,
     pub ancount: u16,
@@ -83,8 +87,17 @@ const FLAG_RA: Flag = Flag {
     width: 1,
 };
 const FLAG_Z: Flag = Flag {
+    offset: 6,
+    width: 1,
+};
+// The AD and CD flags take over the lower two bits of what RFC 1035 originally reserved for z
+const FLAG_AD: Flag = Flag {
+    offset: 5,
+    width: 1,
+};
+const FLAG_CD: Flag = Flag {
     offset: 4,
-    width: 3,
+    width: 1,
 };
 const FLAG_RCODE: Flag = Flag {
     offset: 0,
@@ -100,6 +113,26 @@ pub enum Opcode {
     QUERY = 0,
     IQUERY = 1,
     STATUS = 2,
+    NOTIFY = 4, // specified in IETF RFC 1996
+    UPDATE = 5, // specified in IETF RFC 2136
+}
+
+impl std::str::FromStr for Opcode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.to_ascii_uppercase().as_str() {
+            "QUERY" => Ok(Opcode::QUERY),
+            "IQUERY" => Ok(Opcode::IQUERY),
+            "STATUS" => Ok(Opcode::STATUS),
+            "NOTIFY" => Ok(Opcode::NOTIFY),
+            "UPDATE" => Ok(Opcode::UPDATE),
+            _ => Err(format!(
+                "Unknown opcode {}, please enter either query, iquery, status, notify, or update",
+                s
+            )),
+        };
+    }
 }
 
 impl Header {
@@ -112,6 +145,8 @@ impl Header {
             ^ ((self.rd as u16) << FLAG_RD.offset)
             ^ ((self.ra as u16) << FLAG_RA.offset)
             ^ (0)   // z bits are only set to 0 in RFC 1035
+            ^ ((self.ad as u16) << FLAG_AD.offset)
+            ^ ((self.cd as u16) << FLAG_CD.offset)
             ^ ((self.rcode as u16 & BITMASKS[FLAG_RCODE.width]) << FLAG_RCODE.offset);
         return PackedHeader {
             data: [
@@ -135,6 +170,13 @@ impl Header {
     }
 
     fn from_bytes(buffer: &[u8]) -> Result<Header, String> {
+        if buffer.len() < FIELD_ARCOUNT.offset + 2 {
+            return Err(format!(
+                "Header is truncated, only {} of 12 bytes were received",
+                buffer.len()
+            ));
+        }
+
         let packed_flags =
             u16::from_be_bytes([buffer[FIELD_FLAGS.offset], buffer[FIELD_FLAGS.offset + 1]]);
         let opcode_int = (packed_flags >> FLAG_OPCODE.offset) & BITMASKS[FLAG_OPCODE.width];
@@ -169,6 +211,8 @@ impl Header {
             rd: (packed_flags >> FLAG_RD.offset) & BITMASKS[FLAG_RD.width] != 0,
             ra: (packed_flags >> FLAG_RA.offset) & BITMASKS[FLAG_RA.width] != 0,
             z: ((packed_flags >> FLAG_Z.offset) & BITMASKS[FLAG_Z.width]) as u8,
+            ad: (packed_flags >> FLAG_AD.offset) & BITMASKS[FLAG_AD.width] != 0,
+            cd: (packed_flags >> FLAG_CD.offset) & BITMASKS[FLAG_CD.width] != 0,
             rcode: ((packed_flags >> FLAG_RCODE.offset) & BITMASKS[FLAG_RCODE.width]) as u8,
         });
     }
@@ -201,6 +245,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 3,
             qdcount: 1,
             ancount: 2,
@@ -225,6 +271,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 3,
             qdcount: 1,
             ancount: 2,
@@ -247,6 +295,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 2,
@@ -269,6 +319,8 @@ mod tests {
             rd: false,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 2,
@@ -298,6 +350,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 3,
             qdcount: 1,
             ancount: 2,
@@ -331,6 +385,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0, // z should always be 0 as per RFC 1035
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -364,6 +420,8 @@ mod tests {
             rd: false,
             ra: false,
             z: 0, // z should always be 0 as per RFC 1035
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -397,6 +455,8 @@ mod tests {
             rd: false,
             ra: false,
             z: 0, // z should always be 0 as per RFC 1035
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -420,6 +480,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0, // z should be set to 0 as specified in RFC 1035
+            ad: false,
+            cd: false,
             rcode: 3,
             qdcount: 1,
             ancount: 2,
@@ -441,4 +503,30 @@ mod tests {
             Header::from_bytes(&bytes).unwrap().to_bytes().as_slice()
         );
     }
+
+    #[test]
+    fn ad_and_cd_flags() {
+        let header = Header {
+            id: 0xdb42,
+            qr: true,
+            opcode: Opcode::QUERY,
+            aa: false,
+            tc: false,
+            rd: true,
+            ra: true,
+            z: 0,
+            ad: true,
+            cd: true,
+            rcode: 0,
+            qdcount: 1,
+            ancount: 0,
+            nscount: 0,
+            arcount: 1,
+        };
+
+        let bytes = header.to_bytes();
+
+        assert_eq!(vec![0b10000001, 0b10110000], bytes[2..4].to_vec());
+        assert_eq!(header, Header::from_bytes(bytes.as_slice()).unwrap());
+    }
 }
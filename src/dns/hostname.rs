@@ -59,7 +59,13 @@ impl CompressedHostnameLabel {
 impl Hostname {
     // TODO: use From trait instead of a separate function
     pub(crate) fn from_string(hostname: &str) -> Result<Hostname, String> {
-        if !valid_hostname(hostname) {
+        // fully qualified names with a trailing dot are accepted too, and "." is the root
+        let hostname = hostname.strip_suffix('.').unwrap_or(hostname);
+        if hostname.is_empty() {
+            return Ok(Hostname(Vec::new()));
+        }
+        // a wildcard label may only come first, as specified in IETF RFC 4592
+        if !valid_hostname(hostname.strip_prefix("*.").unwrap_or(hostname)) {
             return Err("Invalid hostname".to_string());
         }
 
@@ -77,6 +83,16 @@ impl Hostname {
         ));
     }
 
+    /// Number of labels in the hostname, not counting the root or a leading wildcard, which is how
+    /// the labels field of an RRSIG record counts them
+    pub(crate) fn label_count(&self) -> u8 {
+        let wildcard = match self.0.first() {
+            Some(Label::NORMAL(label)) => label.label == "*",
+            _ => false,
+        };
+        return (self.0.len() - wildcard as usize) as u8;
+    }
+
     pub(crate) fn to_bytes(&self) -> Vec<u8> {
         let mut bytes: Vec<u8> = self.0.iter().flat_map(|label| label.to_bytes()).collect();
         // each hostname is terminated by the zero-length octet (e.g. null byte)
@@ -88,21 +104,36 @@ impl Hostname {
         let mut labels: Vec<Label> = Vec::new();
         let mut i: usize = 0;
 
-        // TODO: add bounds check for a more friendly error than rust's panic
         loop {
-            let next_bytes = u16::from_be_bytes([buffer[i], buffer[i + 1]]);
+            let label_size = match buffer.get(i) {
+                None => return Err("Hostname runs past the end of the buffer".to_string()),
+                Some(size) => *size,
+            };
 
-            if next_bytes & COMPRESSED_MASK == COMPRESSED_INDICATOR {
-                let pointer = next_bytes;
+            // the root hostname is just the zero-length octet
+            if label_size == 0 {
+                i += 1;
+                break;
+            }
+
+            if label_size as u16 & (COMPRESSED_MASK >> 8) == COMPRESSED_INDICATOR >> 8 {
+                let pointer = match buffer.get(i + 1) {
+                    None => return Err("Hostname runs past the end of the buffer".to_string()),
+                    Some(low) => u16::from_be_bytes([label_size, *low]),
+                };
                 labels.push(Label::COMPRESSED(CompressedHostnameLabel { pointer }));
                 i += 2;
                 break; // as per RFC 1035, a NAME ends in either a pointer or a zero octet
             } else {
-                let label_size = buffer[i];
                 i += 1;
-                // TODO: should use errors instead of relying on panic here
-                let label =
-                    String::from_utf8(buffer[i..i + (label_size as usize)].to_vec()).unwrap();
+                let label_bytes = match buffer.get(i..i + (label_size as usize)) {
+                    None => return Err("Hostname runs past the end of the buffer".to_string()),
+                    Some(bytes) => bytes,
+                };
+                let label = match String::from_utf8(label_bytes.to_vec()) {
+                    Err(e) => return Err(e.to_string()),
+                    Ok(l) => l,
+                };
 
                 labels.push(Label::NORMAL(HostnameLabel {
                     length: label_size,
@@ -110,7 +141,7 @@ impl Hostname {
                 }));
                 i += label_size as usize;
 
-                if buffer[i] == 0 {
+                if buffer.get(i) == Some(&0) {
                     i += 1;
                     break; // as per RFC 1035, a NAME ends in either a pointer or a zero octet
                 }
@@ -128,6 +159,102 @@ impl Hostname {
             hostname: Hostname(labels),
         });
     }
+
+    /// Whether this hostname is the same as or falls under the given domain, ignoring case
+    ///
+    /// Both hostnames must already have been decompressed.
+    pub(crate) fn is_subdomain_of(&self, domain: &Hostname) -> bool {
+        let compressed = |hostname: &Hostname| {
+            hostname
+                .0
+                .iter()
+                .any(|label| matches!(label, Label::COMPRESSED(_)))
+        };
+        if compressed(self) || compressed(domain) {
+            return false;
+        }
+        return in_zone(&self.to_string(), &domain.to_string());
+    }
+
+    /// Follows any compression pointers using the message the hostname was parsed from
+    ///
+    /// The returned hostname only contains normal labels.
+    pub(crate) fn decompress(&self, message: &[u8]) -> Result<Hostname, String> {
+        let mut labels: Vec<Label> = Vec::new();
+        let mut current = self.clone();
+
+        // Each pointer jump moves to a different offset in the message, so a well formed name can
+        // never need more jumps than there are bytes in the message
+        for _ in 0..=message.len() {
+            let mut pointer = None;
+            for label in current.0 {
+                match label {
+                    Label::NORMAL(label) => labels.push(Label::NORMAL(label)),
+                    Label::COMPRESSED(label) => pointer = Some(label.pointer & !COMPRESSED_MASK),
+                }
+            }
+
+            let offset = match pointer {
+                None => return Ok(Hostname(labels)),
+                Some(p) => p as usize,
+            };
+            if offset >= message.len() {
+                return Err(format!(
+                    "Compression pointer {} is outside the message",
+                    offset
+                ));
+            }
+            current = Hostname::parse(&message[offset..])?.hostname;
+        }
+
+        return Err("Compression pointers in hostname form a loop".to_string());
+    }
+}
+
+impl std::fmt::Display for Hostname {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, ".");
+        }
+
+        let labels: Vec<String> = self
+            .0
+            .iter()
+            .map(|label| match label {
+                Label::NORMAL(label) => label.label.clone(),
+                Label::COMPRESSED(label) => format!("@{}", label.pointer & !COMPRESSED_MASK),
+            })
+            .collect();
+        return write!(f, "{}", labels.join("."));
+    }
+}
+
+/// Lowercases a name and drops any trailing dot so that names from different sources can be
+/// compared, which makes the root the empty name
+pub(crate) fn normalize_name(name: &str) -> String {
+    return name.trim_end_matches('.').to_ascii_lowercase();
+}
+
+/// Compares two names ignoring case and any trailing dot
+pub(crate) fn same_name(a: &str, b: &str) -> bool {
+    return a
+        .trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'));
+}
+
+/// Whether the name is the same as or falls under the zone, ignoring case and any trailing dot
+pub(crate) fn in_zone(name: &str, zone: &str) -> bool {
+    let name = name.trim_end_matches('.').as_bytes();
+    let zone = zone.trim_end_matches('.').as_bytes();
+    if zone.is_empty() {
+        return true;
+    }
+    if name.len() < zone.len() {
+        return false;
+    }
+    // the zone has to start at a label boundary, so that badexample.com is not in example.com
+    let start = name.len() - zone.len();
+    return name[start..].eq_ignore_ascii_case(zone) && (start == 0 || name[start - 1] == b'.');
 }
 
 /// Attempts to validate that a hostname is valid as per RFC 1123
@@ -146,7 +273,9 @@ fn valid_hostname(hostname: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::dns::hostname::{CompressedHostnameLabel, Hostname, HostnameLabel, Label};
+    use crate::dns::hostname::{
+        in_zone, normalize_name, same_name, CompressedHostnameLabel, Hostname, HostnameLabel, Label,
+    };
 
     #[test]
     fn test_hostname_from_string() {
@@ -165,6 +294,13 @@ mod tests {
             }),
         ]);
         assert_eq!(expected, Hostname::from_string("www.example.com").unwrap());
+        assert_eq!(expected, Hostname::from_string("www.example.com.").unwrap());
+
+        assert_eq!(Hostname(Vec::new()), Hostname::from_string(".").unwrap());
+        assert_eq!(0, Hostname::from_string(".").unwrap().label_count());
+        let wildcard = Hostname::from_string("*.example.com").unwrap();
+        assert_eq!(2, wildcard.label_count());
+        assert!(Hostname::from_string("www.*.example.com").is_err());
     }
 
     #[test]
@@ -286,4 +422,88 @@ mod tests {
         assert_eq!(expected, result.hostname);
         assert_eq!(hostname_length, result.parsed_bytes as usize);
     }
+
+    #[test]
+    fn parse_root_hostname() {
+        let bytes: Vec<u8> = vec![0, 0, 2, 0, 1];
+
+        let result = Hostname::parse(bytes.as_slice()).unwrap();
+
+        assert_eq!(Hostname(vec![]), result.hostname);
+        assert_eq!(1, result.parsed_bytes as usize);
+        assert_eq!(".", result.hostname.to_string());
+    }
+
+    #[test]
+    fn parse_truncated_hostname() {
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.push(7);
+        bytes.extend("exam".as_bytes());
+
+        assert!(Hostname::parse(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn decompress_hostname() {
+        // A message where www.example.com starts at offset 2 and a later name points at example.com
+        let mut message: Vec<u8> = vec![0xff, 0xff];
+        message.extend(Hostname::from_string("www.example.com").unwrap().to_bytes());
+        message.push(7);
+        message.extend("service".as_bytes());
+        message.extend(&(0xc006 as u16).to_be_bytes());
+
+        let compressed = Hostname::parse(&message[19..]).unwrap().hostname;
+
+        assert_eq!(
+            Hostname::from_string("service.example.com").unwrap(),
+            compressed.decompress(message.as_slice()).unwrap()
+        );
+    }
+
+    #[test]
+    fn decompress_hostname_loop() {
+        let message: Vec<u8> = vec![0xc0, 0x00];
+
+        let compressed = Hostname::parse(message.as_slice()).unwrap().hostname;
+
+        assert!(compressed.decompress(message.as_slice()).is_err());
+    }
+
+    #[test]
+    fn hostname_is_subdomain_of() {
+        let domain = Hostname::from_string("example.com").unwrap();
+
+        assert!(Hostname::from_string("ns.Example.COM")
+            .unwrap()
+            .is_subdomain_of(&domain));
+        assert!(Hostname::from_string("example.com")
+            .unwrap()
+            .is_subdomain_of(&domain));
+        assert!(Hostname::from_string("ns.example.com")
+            .unwrap()
+            .is_subdomain_of(&Hostname(vec![])));
+        assert!(!Hostname::from_string("ns.badexample.com")
+            .unwrap()
+            .is_subdomain_of(&domain));
+        assert!(!Hostname::from_string("com")
+            .unwrap()
+            .is_subdomain_of(&domain));
+    }
+
+    #[test]
+    fn compare_names() {
+        assert!(same_name("ns.Example.com.", "ns.example.COM"));
+        assert!(!same_name("ns.example.com", "ns.example.net"));
+        assert_eq!("example.com", normalize_name("Example.COM."));
+        assert_eq!("", normalize_name("."));
+
+        assert!(in_zone("www.example.com", "."));
+        assert!(in_zone("www.Example.com.", "example.com"));
+        assert!(in_zone("example.com", "example.com."));
+        assert!(!in_zone("www.badexample.com", "example.com"));
+        assert!(!in_zone("com", "example.com"));
+        // names that are not ASCII are compared byte for byte without splitting a character
+        assert!(!in_zone("xé.com", "é.com"));
+        assert!(in_zone("www.é.com", "é.com"));
+    }
 }
@@ -1,8 +1,10 @@
 use crate::dns::additional::Additional;
 use crate::dns::answer::Answer;
 use crate::dns::authority::Authority;
+use crate::dns::edns::Edns;
 use crate::dns::header::Header;
 use crate::dns::question::Question;
+use crate::dns::response::Record;
 
 #[derive(PartialEq, Clone, Debug)]
 /// DNS message format as specified in IETF RFC 1035
@@ -12,6 +14,8 @@ pub struct Message {
     pub answers: Vec<Answer>,
     pub authorities: Vec<Authority>,
     pub additionals: Vec<Additional>,
+    /// OPT pseudo-record from the additional section, counted in the header's arcount
+    pub edns: Option<Edns>,
 }
 
 impl Message {
@@ -22,6 +26,7 @@ impl Message {
             answers: Vec::new(),
             authorities: Vec::new(),
             additionals: Vec::new(),
+            edns: None,
         };
     }
 
@@ -32,6 +37,9 @@ impl Message {
         bytes.extend(self.answers.iter().flat_map(|an| an.to_bytes()));
         bytes.extend(self.authorities.iter().flat_map(|au| au.to_bytes()));
         bytes.extend(self.additionals.iter().flat_map(|ad| ad.to_bytes()));
+        if let Some(edns) = &self.edns {
+            bytes.extend(edns.to_bytes());
+        }
         return bytes;
     }
 
@@ -45,28 +53,38 @@ impl Message {
         let mut questions: Vec<Question> = Vec::new();
         for _ in 0..header.qdcount {
             let parsed_question = Question::parse(&buffer[parsed_bytes..])?;
-            questions.push(parsed_question.question);
+            let mut question = parsed_question.question;
+            question.qname = question.qname.decompress(buffer)?;
+            questions.push(question);
             parsed_bytes += parsed_question.parsed_bytes as usize;
         }
 
         let mut answers: Vec<Answer> = Vec::new();
         for _ in 0..header.ancount {
             let parsed_answer = Answer::parse(&buffer[parsed_bytes..])?;
-            answers.push(parsed_answer.record);
+            answers.push(parsed_answer.record.decompress(buffer)?);
             parsed_bytes += parsed_answer.parsed_bytes as usize;
         }
 
         let mut authorities: Vec<Answer> = Vec::new();
         for _ in 0..header.nscount {
             let parsed_authority = Authority::parse(&buffer[parsed_bytes..])?;
-            authorities.push(parsed_authority.record);
+            authorities.push(parsed_authority.record.decompress(buffer)?);
             parsed_bytes += parsed_authority.parsed_bytes as usize;
         }
 
         let mut additionals: Vec<Answer> = Vec::new();
+        let mut edns = None;
         for _ in 0..header.arcount {
+            // the OPT record's class and TTL fields do not hold a class or TTL so it is parsed apart
+            if Edns::is_opt(&buffer[parsed_bytes..]) {
+                let parsed_edns = Edns::parse(&buffer[parsed_bytes..])?;
+                edns = Some(parsed_edns.edns);
+                parsed_bytes += parsed_edns.parsed_bytes;
+                continue;
+            }
             let parsed_additional = Additional::parse(&buffer[parsed_bytes..])?;
-            additionals.push(parsed_additional.record);
+            additionals.push(parsed_additional.record.decompress(buffer)?);
             parsed_bytes += parsed_additional.parsed_bytes as usize;
         }
 
@@ -80,8 +98,67 @@ impl Message {
             answers,
             authorities,
             additionals,
+            edns,
         });
     }
+
+    /// Checks that the response echoes the questions of this query exactly
+    ///
+    /// Names are compared including their case, which is what makes 0x20 encoding work: a spoofed
+    /// response has to guess the case of every letter in the query.
+    pub(crate) fn check_echo(&self, response: &Message) -> Result<(), String> {
+        if self.questions.len() != response.questions.len() {
+            return Err(format!(
+                "Response has {} questions but the query had {}",
+                response.questions.len(),
+                self.questions.len()
+            ));
+        }
+        for (question, echoed) in self.questions.iter().zip(response.questions.iter()) {
+            if question.qname != echoed.qname {
+                return Err(format!(
+                    "Response question {} does not match the query question {}",
+                    echoed.qname, question.qname
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Full 12 bit rcode, combining the header rcode with the upper bits from the OPT record
+    pub fn rcode(&self) -> u16 {
+        let extended_rcode = match &self.edns {
+            None => 0,
+            Some(edns) => edns.extended_rcode as u16,
+        };
+        return extended_rcode << 4 | self.header.rcode as u16;
+    }
+
+    /// How long the response may be negatively cached for, if it is an NXDOMAIN or NODATA response
+    /// carrying the SOA record of the zone in the authority section as per IETF RFC 2308
+    ///
+    /// Negative responses without an SOA record must not be cached, so None is returned for them.
+    pub fn negative_ttl(&self) -> Option<u32> {
+        const NXDOMAIN: u16 = 3;
+
+        let qtype = self.questions.first()?.qtype;
+        let negative = match self.rcode() {
+            NXDOMAIN => true,
+            // a NODATA response may still hold the CNAMEs leading to the name without the type
+            0 => !self.answers.iter().any(|rr| rr.rtype == qtype),
+            _ => false,
+        };
+        if !negative {
+            return None;
+        }
+        return self
+            .authorities
+            .iter()
+            .find_map(|rr| match Record::from_rr(rr) {
+                Ok(Record::SOA(soa)) => Some(soa.negative_ttl()),
+                _ => None,
+            });
+    }
 }
 
 #[cfg(test)]
@@ -90,6 +167,7 @@ mod tests {
     use crate::dns::answer::Answer;
     use crate::dns::authority::Authority;
     use crate::dns::classes::Class;
+    use crate::dns::edns::Edns;
     use crate::dns::header::{Header, Opcode};
     use crate::dns::hostname::Hostname;
     use crate::dns::message::Message;
@@ -107,6 +185,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -137,7 +217,7 @@ mod tests {
         expected.push(3);
         expected.extend("com".as_bytes());
         expected.push(0);
-        expected.extend(&(Type::A as u16).to_be_bytes());
+        expected.extend(&Type::A.to_u16().to_be_bytes());
         expected.extend(&(Class::IN as u16).to_be_bytes());
 
         assert_eq!(expected, message.to_bytes());
@@ -154,6 +234,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 2,
             ancount: 0,
@@ -191,7 +273,7 @@ mod tests {
         expected.push(3);
         expected.extend("com".as_bytes());
         expected.push(0);
-        expected.extend(&(Type::A as u16).to_be_bytes());
+        expected.extend(&Type::A.to_u16().to_be_bytes());
         expected.extend(&(Class::IN as u16).to_be_bytes());
 
         // Question for www.google.com
@@ -202,7 +284,7 @@ mod tests {
         expected.push(3);
         expected.extend("com".as_bytes());
         expected.push(0);
-        expected.extend(&(Type::A as u16).to_be_bytes());
+        expected.extend(&Type::A.to_u16().to_be_bytes());
         expected.extend(&(Class::IN as u16).to_be_bytes());
 
         assert_eq!(expected, message.to_bytes());
@@ -224,7 +306,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&extra_bytes);
 
@@ -237,6 +319,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0, // z should always be 0 as per RFC 1035
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -274,7 +358,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
 
         // Question for www.google.com
@@ -285,7 +369,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&extra_bytes);
 
@@ -298,6 +382,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0, // z should always be 0 as per RFC 1035
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 2,
             ancount: 0,
@@ -337,6 +423,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 0,
             ancount: 1,
@@ -370,7 +458,7 @@ mod tests {
         expected.push(3);
         expected.extend("com".as_bytes());
         expected.push(0);
-        expected.extend(&(Type::A as u16).to_be_bytes());
+        expected.extend(&Type::A.to_u16().to_be_bytes());
         expected.extend(&(Class::IN as u16).to_be_bytes());
         expected.extend(&(0x258 as u32).to_be_bytes());
         expected.extend(&(4 as u16).to_be_bytes());
@@ -390,6 +478,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 7, // z should be ignored since RFC 1035 specifies it set to 0
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 0,
             ancount: 1,
@@ -443,7 +533,7 @@ mod tests {
         expected.push(3);
         expected.extend("com".as_bytes());
         expected.push(0);
-        expected.extend(&(Type::A as u16).to_be_bytes());
+        expected.extend(&Type::A.to_u16().to_be_bytes());
         expected.extend(&(Class::IN as u16).to_be_bytes());
         expected.extend(&(0x258 as u32).to_be_bytes());
         expected.extend(&(4 as u16).to_be_bytes());
@@ -455,7 +545,7 @@ mod tests {
         expected.push(3);
         expected.extend("com".as_bytes());
         expected.push(0);
-        expected.extend(&(Type::NS as u16).to_be_bytes());
+        expected.extend(&Type::NS.to_u16().to_be_bytes());
         expected.extend(&(Class::IN as u16).to_be_bytes());
         expected.extend(&(0x258 as u32).to_be_bytes());
         expected.extend(&(4 as u16).to_be_bytes());
@@ -469,7 +559,7 @@ mod tests {
         expected.push(3);
         expected.extend("com".as_bytes());
         expected.push(0);
-        expected.extend(&(Type::A as u16).to_be_bytes());
+        expected.extend(&Type::A.to_u16().to_be_bytes());
         expected.extend(&(Class::IN as u16).to_be_bytes());
         expected.extend(&(0x258 as u32).to_be_bytes());
         expected.extend(&(4 as u16).to_be_bytes());
@@ -494,7 +584,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&(0x258 as u32).to_be_bytes());
         bytes.extend(&(4 as u16).to_be_bytes());
@@ -510,6 +600,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0, // z should always be 0 as per RFC 1035
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 0,
             ancount: 1,
@@ -550,7 +642,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&(0x258 as u32).to_be_bytes());
         bytes.extend(&(4 as u16).to_be_bytes());
@@ -562,7 +654,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::NS as u16).to_be_bytes());
+        bytes.extend(&Type::NS.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&(0x258 as u32).to_be_bytes());
         bytes.extend(&(4 as u16).to_be_bytes());
@@ -576,7 +668,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&(0x258 as u32).to_be_bytes());
         bytes.extend(&(4 as u16).to_be_bytes());
@@ -593,6 +685,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0, // z should always be 0 as per RFC 1035
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 0,
             ancount: 1,
@@ -636,4 +730,153 @@ mod tests {
 
         assert_eq!(expected_message, Message::parse(bytes.as_slice()).unwrap());
     }
+
+    #[test]
+    fn parse_compressed_response() {
+        let mut bytes: Vec<u8> = vec![
+            // Header
+            0xdb, 0x42, 0b10000001, 0b00000000, 0, 1, 0, 0, 0, 1, 0, 0,
+        ];
+        // Question, example.com starts at offset 12
+        bytes.push(7);
+        bytes.extend("example".as_bytes());
+        bytes.push(3);
+        bytes.extend("com".as_bytes());
+        bytes.push(0);
+        bytes.extend(&Type::NS.to_u16().to_be_bytes());
+        bytes.extend(&(Class::IN as u16).to_be_bytes());
+
+        // Authority, both the owner name and the nameserver point back to the question
+        bytes.extend(&(0xc00c as u16).to_be_bytes());
+        bytes.extend(&Type::NS.to_u16().to_be_bytes());
+        bytes.extend(&(Class::IN as u16).to_be_bytes());
+        bytes.extend(&(0x258 as u32).to_be_bytes());
+        bytes.extend(&(5 as u16).to_be_bytes());
+        bytes.push(2);
+        bytes.extend("ns".as_bytes());
+        bytes.extend(&(0xc00c as u16).to_be_bytes());
+
+        let ns_bytes = Hostname::from_string("ns.example.com").unwrap().to_bytes();
+        let expected_authority = Authority {
+            name: Hostname::from_string("example.com").unwrap(),
+            rtype: Type::NS,
+            class: Class::IN,
+            ttl: 0x258,
+            rdlength: ns_bytes.len() as u16,
+            rdata: ns_bytes,
+        };
+
+        let message = Message::parse(bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            Hostname::from_string("example.com").unwrap(),
+            message.questions[0].qname
+        );
+        assert_eq!(vec![expected_authority], message.authorities);
+    }
+
+    #[test]
+    fn message_with_edns_round_trip() {
+        let header = Header {
+            id: 0xdb42,
+            qr: false,
+            opcode: Opcode::QUERY,
+            aa: false,
+            tc: false,
+            rd: true,
+            ra: false,
+            z: 0,
+            ad: false,
+            cd: true,
+            rcode: 0,
+            qdcount: 1,
+            ancount: 0,
+            nscount: 0,
+            arcount: 1,
+        };
+
+        let message = Message {
+            questions: vec![Question {
+                qname: Hostname::from_string("example.com").unwrap(),
+                qtype: Type::MX,
+                qclass: Class::IN,
+            }],
+            edns: Some(Edns {
+                dnssec_ok: true,
+                ..Edns::new()
+            }),
+            ..Message::new(header)
+        };
+
+        let parsed = Message::parse(message.to_bytes().as_slice()).unwrap();
+
+        assert_eq!(message, parsed);
+        assert!(parsed.additionals.is_empty());
+    }
+
+    #[test]
+    fn check_echoed_question_case() {
+        let question = |name: &str| Question {
+            qname: Hostname::from_string(name).unwrap(),
+            qtype: Type::A,
+            qclass: Class::IN,
+        };
+        let header = Header {
+            id: 1,
+            qr: false,
+            opcode: Opcode::QUERY,
+            aa: false,
+            tc: false,
+            rd: true,
+            ra: false,
+            z: 0,
+            ad: false,
+            cd: false,
+            rcode: 0,
+            qdcount: 1,
+            ancount: 0,
+            nscount: 0,
+            arcount: 0,
+        };
+        let query = Message {
+            questions: vec![question("wWw.ExamPle.com")],
+            ..Message::new(header)
+        };
+        let echo = |name: &str| Message {
+            questions: vec![question(name)],
+            ..Message::new(header)
+        };
+
+        assert!(query.check_echo(&echo("wWw.ExamPle.com")).is_ok());
+        assert!(query.check_echo(&echo("www.example.com")).is_err());
+        assert!(query.check_echo(&Message::new(header)).is_err());
+    }
+
+    #[test]
+    fn truncated_messages_are_errors() {
+        let mut bytes: Vec<u8> = vec![
+            // Header of a response with one question and one answer
+            0x12, 0x34, 0b10000001, 0b10000000, 0, 1, 0, 1, 0, 0, 0, 0,
+        ];
+        bytes.extend(b"\x03www\x07example\x03com\x00");
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
+        bytes.extend(&(Class::IN as u16).to_be_bytes());
+        bytes.extend(&[0xc0, 12]); // pointer to the question's name
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
+        bytes.extend(&(Class::IN as u16).to_be_bytes());
+        bytes.extend(&300u32.to_be_bytes());
+        bytes.extend(&4u16.to_be_bytes());
+        bytes.extend(&[10, 0, 0, 1]);
+        assert!(Message::parse(&bytes).is_ok());
+
+        // cutting the message short anywhere, down to a lone byte of the header, is an error
+        for length in 0..bytes.len() {
+            assert!(
+                Message::parse(&bytes[..length]).is_err(),
+                "{} bytes",
+                length
+            );
+        }
+        assert!(Message::parse(b"\x12\x34\x01").is_err());
+    }
 }
@@ -1,16 +1,27 @@
 use crate::dns::classes::Class;
+use crate::dns::edns::Edns;
 use crate::dns::header::{Header, Opcode};
 use crate::dns::hostname::Hostname;
 use crate::dns::message::Message;
 use crate::dns::question::Question;
 use crate::dns::types::Type;
-use rand;
+use rand::Rng;
 
 pub struct Query {
     pub hostnames: Vec<String>,
     pub qtype: Type,
+    pub qclass: Class,
     pub opcode: Opcode,
     pub recursion_desired: bool,
+    /// asks the server not to validate DNSSEC signatures, specified in IETF RFC 4035
+    pub checking_disabled: bool,
+    /// OPT record to send in the additional section, required for the DO bit and EDNS options
+    pub edns: Option<Edns>,
+    /// randomizes the case of each letter in the hostnames, known as 0x20 encoding
+    ///
+    /// Servers echo the question back exactly, so a response whose question does not match the case
+    /// of the query is likely spoofed. Each letter adds a bit an attacker has to guess.
+    pub case_randomization: bool,
 }
 
 impl Query {
@@ -18,12 +29,17 @@ impl Query {
         return Query {
             hostnames,
             qtype: Type::A,
+            qclass: Class::IN,
             opcode: Opcode::QUERY,
             recursion_desired: true,
+            checking_disabled: false,
+            edns: None,
+            case_randomization: false,
         };
     }
 
-    pub(crate) fn to_message(&self) -> Result<Message, String> {
+    /// Builds the query message, drawing its ID from the given random number generator
+    pub(crate) fn to_message<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Message, String> {
         if self.hostnames.len() > u16::max_value() as usize {
             return Err(format!(
                 "Too many hostnames entered, cannot query for more than {} hostnames",
@@ -31,7 +47,7 @@ impl Query {
             ));
         }
 
-        let id = rand::random::<u16>();
+        let id = rng.gen::<u16>();
 
         let qdcount: u16 = self.hostnames.len() as u16;
 
@@ -44,33 +60,55 @@ impl Query {
             rd: self.recursion_desired,
             ra: false,
             z: 0,
+            ad: false,
+            cd: self.checking_disabled,
             rcode: 0,
             qdcount,
             ancount: 0,
             nscount: 0,
-            arcount: 0,
+            arcount: self.edns.is_some() as u16,
         };
 
         let questions: Result<Vec<Question>, String> = self
             .hostnames
             .iter()
             .map(|hostname| {
-                let qname = Hostname::from_string(hostname)?;
+                let hostname = if self.case_randomization {
+                    randomize_case(hostname, rng)
+                } else {
+                    hostname.clone()
+                };
+                let qname = Hostname::from_string(&hostname)?;
                 return Ok(Question {
                     qname,
                     qtype: self.qtype,
-                    qclass: Class::IN,
+                    qclass: self.qclass,
                 });
             })
             .collect();
 
         return Ok(Message {
             questions: questions?,
+            edns: self.edns.clone(),
             ..Message::new(header)
         });
     }
 }
 
+/// Flips the case of each letter in the hostname at random, as described in the 0x20 encoding draft
+fn randomize_case<R: Rng + ?Sized>(hostname: &str, rng: &mut R) -> String {
+    return hostname
+        .chars()
+        .map(|c| {
+            if rng.gen::<bool>() {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect();
+}
+
 #[cfg(test)]
 mod tests {
     use crate::dns::classes::Class;
@@ -80,11 +118,12 @@ mod tests {
     use crate::dns::query::Query;
     use crate::dns::question::Question;
     use crate::dns::types::Type;
+    use crate::rng;
 
     #[test]
     fn query_to_message() {
         let query = Query::new(vec!["www.example.com".to_string()]);
-        let message = query.to_message().unwrap();
+        let message = query.to_message(&mut rng::new(Some(1))).unwrap();
 
         let expected_header = Header {
             id: message.header.id,
@@ -95,6 +134,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -113,4 +154,25 @@ mod tests {
 
         assert_eq!(expected, message);
     }
+
+    #[test]
+    fn query_with_case_randomization() {
+        let mut query = Query::new(vec!["www.example.com".to_string()]);
+        query.case_randomization = true;
+
+        let mut rng = rng::new(Some(20));
+        let names: Vec<String> = (0..8)
+            .map(|_| {
+                query.to_message(&mut rng).unwrap().questions[0]
+                    .qname
+                    .to_string()
+            })
+            .collect();
+
+        assert!(names
+            .iter()
+            .all(|name| name.eq_ignore_ascii_case("www.example.com")));
+        // 13 letters leave only a tiny chance of every name having the same case
+        assert!(names.iter().any(|name| name != &names[0]));
+    }
 }
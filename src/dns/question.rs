@@ -26,7 +26,7 @@ impl Question {
     fn pack(&self) -> PackedQuestion {
         let mut packed = Vec::new();
         packed.extend(self.qname.to_bytes());
-        packed.extend(&(self.qtype as u16).to_be_bytes());
+        packed.extend(&self.qtype.to_u16().to_be_bytes());
         packed.extend(&(self.qclass as u16).to_be_bytes());
         return PackedQuestion { data: packed };
     }
@@ -41,11 +41,16 @@ impl Question {
         let parsed_hostname = Hostname::parse(buffer)?;
         parsed_bytes += parsed_hostname.parsed_bytes as usize;
 
+        // QTYPE and QCLASS follow the name
+        if buffer.len() < parsed_bytes + 4 {
+            return Err(format!(
+                "Question for {} is truncated",
+                parsed_hostname.hostname
+            ));
+        }
+
         let qtype_int = u16::from_be_bytes([buffer[parsed_bytes], buffer[parsed_bytes + 1]]);
-        let qtype = match Type::from_u16(qtype_int) {
-            None => return Err(format!("Unsupported QTYPE {}", qtype_int)),
-            Some(op) => op,
-        };
+        let qtype = Type::from_u16(qtype_int);
         parsed_bytes += 2;
 
         let qclass_int = u16::from_be_bytes([buffer[parsed_bytes], buffer[parsed_bytes + 1]]);
@@ -92,7 +97,7 @@ mod tests {
         expected_data.push(3);
         expected_data.extend("com".as_bytes());
         expected_data.push(0);
-        expected_data.extend(&(Type::A as u16).to_be_bytes());
+        expected_data.extend(&Type::A.to_u16().to_be_bytes());
         expected_data.extend(&(Class::IN as u16).to_be_bytes());
         let expected = PackedQuestion {
             data: expected_data,
@@ -112,7 +117,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&extra_bytes);
 
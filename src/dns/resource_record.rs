@@ -1,4 +1,7 @@
 use crate::dns::classes::Class;
+use crate::dns::dnssec::{
+    DNSKEYRecord, DSRecord, NSEC3PARAMRecord, NSEC3Record, NSECRecord, RRSIGRecord,
+};
 use crate::dns::hostname::Hostname;
 use crate::dns::types::Type;
 use num_traits::FromPrimitive;
@@ -21,7 +24,7 @@ struct PackedResourceRecord {
 
 pub(crate) struct ParsedResourceRecord {
     /// Number of buffer bytes parsed to construct a resource record
-    pub(crate) parsed_bytes: u8,
+    pub(crate) parsed_bytes: usize,
     pub(crate) record: ResourceRecord,
 }
 
@@ -29,7 +32,7 @@ impl ResourceRecord {
     fn pack(&self) -> PackedResourceRecord {
         let mut packed = Vec::new();
         packed.extend(self.name.to_bytes());
-        packed.extend(&(self.rtype as u16).to_be_bytes());
+        packed.extend(&self.rtype.to_u16().to_be_bytes());
         packed.extend(&(self.class as u16).to_be_bytes());
         packed.extend(&self.ttl.to_be_bytes());
         packed.extend(&self.rdlength.to_be_bytes());
@@ -41,17 +44,164 @@ impl ResourceRecord {
         return self.pack().data;
     }
 
+    /// Expands any compressed hostnames in the record using the message it was parsed from
+    ///
+    /// This covers the owner name as well as names held in the rdata of NS, CNAME, PTR, MX, SRV and
+    /// SOA records. Rdata that does not hold well formed names is left as is so that it can still be
+    /// inspected.
+    pub(crate) fn decompress(&self, message: &[u8]) -> Result<ResourceRecord, String> {
+        let name = self.name.decompress(message)?;
+        let rdata = self.decompress_rdata(message)?;
+
+        return Ok(ResourceRecord {
+            name,
+            rdlength: rdata.len() as u16,
+            rdata,
+            ..self.clone()
+        });
+    }
+
+    fn decompress_rdata(&self, message: &[u8]) -> Result<Vec<u8>, String> {
+        // number of fixed bytes before the names, number of names, and number of fixed bytes after
+        let (prefix, names, suffix) = match self.rtype {
+            Type::NS | Type::CNAME | Type::PTR => (0, 1, 0),
+            Type::MX => (2, 1, 0),
+            Type::SRV => (6, 1, 0),
+            Type::SOA => (0, 2, 20),
+            _ => return Ok(self.rdata.clone()),
+        };
+        if self.rdata.len() < prefix + suffix {
+            return Ok(self.rdata.clone());
+        }
+
+        let mut rdata = self.rdata[..prefix].to_vec();
+        let mut offset = prefix;
+        for _ in 0..names {
+            match Hostname::parse(&self.rdata[offset..]) {
+                Ok(parsed) => {
+                    rdata.extend(parsed.hostname.decompress(message)?.to_bytes());
+                    offset += parsed.parsed_bytes as usize;
+                }
+                Err(_) => return Ok(self.rdata.clone()),
+            }
+        }
+        if self.rdata.len() != offset + suffix {
+            return Ok(self.rdata.clone());
+        }
+        rdata.extend(&self.rdata[offset..]);
+
+        return Ok(rdata);
+    }
+
+    /// Rdata in zone file presentation format, falling back to the generic format from IETF RFC 3597
+    /// for types that are not understood or rdata that is malformed
+    fn rdata_to_string(&self) -> String {
+        let generic = || {
+            let hex: Vec<String> = self.rdata.iter().map(|b| format!("{:02x}", b)).collect();
+            return format!("\\# {} {}", self.rdata.len(), hex.join(""));
+        };
+        let name_at = |offset: usize| -> Option<(String, usize)> {
+            let parsed = Hostname::parse(self.rdata.get(offset..)?).ok()?;
+            return Some((
+                parsed.hostname.to_string(),
+                offset + parsed.parsed_bytes as usize,
+            ));
+        };
+        let u16_at = |offset: usize| -> Option<u16> {
+            let bytes = self.rdata.get(offset..offset + 2)?;
+            return Some(u16::from_be_bytes([bytes[0], bytes[1]]));
+        };
+
+        let formatted = match self.rtype {
+            Type::A if self.rdata.len() == 4 => Some(
+                std::net::Ipv4Addr::new(self.rdata[0], self.rdata[1], self.rdata[2], self.rdata[3])
+                    .to_string(),
+            ),
+            Type::AAAA if self.rdata.len() == 16 => {
+                let mut ip = [0u8; 16];
+                ip.copy_from_slice(self.rdata.as_slice());
+                Some(std::net::Ipv6Addr::from(ip).to_string())
+            }
+            Type::NS | Type::CNAME | Type::PTR => name_at(0).map(|(name, _)| name),
+            Type::MX => u16_at(0).and_then(|preference| {
+                name_at(2).map(|(exchange, _)| format!("{} {}", preference, exchange))
+            }),
+            Type::SRV => match (u16_at(0), u16_at(2), u16_at(4), name_at(6)) {
+                (Some(priority), Some(weight), Some(port), Some((target, _))) => {
+                    Some(format!("{} {} {} {}", priority, weight, port, target))
+                }
+                _ => None,
+            },
+            Type::SOA => name_at(0).and_then(|(mname, offset)| {
+                let (rname, offset) = name_at(offset)?;
+                let numbers = self.rdata.get(offset..offset + 20)?;
+                let values: Vec<String> = numbers
+                    .chunks(4)
+                    .map(|n| u32::from_be_bytes([n[0], n[1], n[2], n[3]]).to_string())
+                    .collect();
+                return Some(format!("{} {} {}", mname, rname, values.join(" ")));
+            }),
+            Type::TXT => {
+                // TXT rdata is a sequence of length prefixed character strings
+                let mut strings = Vec::new();
+                let mut offset = 0;
+                while offset < self.rdata.len() {
+                    let length = self.rdata[offset] as usize;
+                    match self.rdata.get(offset + 1..offset + 1 + length) {
+                        None => break,
+                        Some(text) => strings.push(format!(
+                            "\"{}\"",
+                            String::from_utf8_lossy(text).replace('"', "\\\"")
+                        )),
+                    }
+                    offset += 1 + length;
+                }
+                if offset == self.rdata.len() {
+                    Some(strings.join(" "))
+                } else {
+                    None
+                }
+            }
+            Type::DNSKEY => DNSKEYRecord::from_rdata(String::new(), self.ttl, &self.rdata)
+                .ok()
+                .map(|r| r.rdata_to_string()),
+            Type::DS => DSRecord::from_rdata(String::new(), self.ttl, &self.rdata)
+                .ok()
+                .map(|r| r.rdata_to_string()),
+            Type::RRSIG => RRSIGRecord::from_rdata(String::new(), self.ttl, &self.rdata)
+                .ok()
+                .map(|r| r.rdata_to_string()),
+            Type::NSEC => NSECRecord::from_rdata(String::new(), self.ttl, &self.rdata)
+                .ok()
+                .map(|r| r.rdata_to_string()),
+            Type::NSEC3 => NSEC3Record::from_rdata(String::new(), self.ttl, &self.rdata)
+                .ok()
+                .map(|r| r.rdata_to_string()),
+            Type::NSEC3PARAM => NSEC3PARAMRecord::from_rdata(String::new(), self.ttl, &self.rdata)
+                .ok()
+                .map(|r| r.rdata_to_string()),
+            _ => None,
+        };
+
+        return formatted.unwrap_or_else(generic);
+    }
+
     pub(crate) fn parse(buffer: &[u8]) -> Result<ParsedResourceRecord, String> {
         let mut parsed_bytes: usize = 0;
 
         let parsed_hostname = Hostname::parse(buffer)?;
         parsed_bytes += parsed_hostname.parsed_bytes as usize;
 
+        // TYPE, CLASS, TTL and RDLENGTH follow the name
+        if buffer.len() < parsed_bytes + 10 {
+            return Err(format!(
+                "Record for {} is truncated",
+                parsed_hostname.hostname
+            ));
+        }
+
         let rtype_int = u16::from_be_bytes([buffer[parsed_bytes], buffer[parsed_bytes + 1]]);
-        let rtype = match Type::from_u16(rtype_int) {
-            None => return Err(format!("Unsupported QTYPE {}", rtype_int)),
-            Some(op) => op,
-        };
+        let rtype = Type::from_u16(rtype_int);
         parsed_bytes += 2;
 
         let class_int = u16::from_be_bytes([buffer[parsed_bytes], buffer[parsed_bytes + 1]]);
@@ -72,15 +222,21 @@ impl ResourceRecord {
         let rdlength = u16::from_be_bytes([buffer[parsed_bytes], buffer[parsed_bytes + 1]]);
         parsed_bytes += 2;
 
-        let rdata: Vec<u8> = buffer[parsed_bytes..parsed_bytes + rdlength as usize].to_vec();
+        let rdata: Vec<u8> = match buffer.get(parsed_bytes..parsed_bytes + rdlength as usize) {
+            None => {
+                return Err(format!(
+                    "Record for {} has {} bytes of rdata but only {} were received",
+                    parsed_hostname.hostname,
+                    rdlength,
+                    buffer.len() - parsed_bytes
+                ))
+            }
+            Some(rdata) => rdata.to_vec(),
+        };
         parsed_bytes += rdlength as usize;
 
-        if parsed_bytes > u8::max_value() as usize {
-            return Err("Parsed more bytes than can fit into a u8".to_string());
-        }
-
         return Ok(ParsedResourceRecord {
-            parsed_bytes: parsed_bytes as u8,
+            parsed_bytes,
             record: ResourceRecord {
                 name: parsed_hostname.hostname,
                 rtype,
@@ -93,6 +249,21 @@ impl ResourceRecord {
     }
 }
 
+/// Formats the record the way it would appear in a zone file, e.g. "www.example.com 300 IN A 10.0.0.1"
+impl std::fmt::Display for ResourceRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "{} {} {:?} {} {}",
+            self.name,
+            self.ttl,
+            self.class,
+            self.rtype,
+            self.rdata_to_string()
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::dns::classes::Class;
@@ -119,7 +290,7 @@ mod tests {
         expected_data.push(3);
         expected_data.extend("com".as_bytes());
         expected_data.push(0);
-        expected_data.extend(&(Type::A as u16).to_be_bytes());
+        expected_data.extend(&Type::A.to_u16().to_be_bytes());
         expected_data.extend(&(Class::IN as u16).to_be_bytes());
         expected_data.extend(&(0x258 as u32).to_be_bytes());
         expected_data.extend(&(4 as u16).to_be_bytes());
@@ -142,7 +313,7 @@ mod tests {
         bytes.push(3);
         bytes.extend("com".as_bytes());
         bytes.push(0);
-        bytes.extend(&(Type::A as u16).to_be_bytes());
+        bytes.extend(&Type::A.to_u16().to_be_bytes());
         bytes.extend(&(Class::IN as u16).to_be_bytes());
         bytes.extend(&(0x258 as u32).to_be_bytes());
         bytes.extend(&(4 as u16).to_be_bytes());
@@ -165,4 +336,99 @@ mod tests {
         assert_eq!(expected, result.record);
         assert_eq!(record_length, result.parsed_bytes as usize);
     }
+
+    #[test]
+    fn resource_record_display() {
+        let record = |rtype: Type, rdata: Vec<u8>| ResourceRecord {
+            name: Hostname::from_string("example.com").unwrap(),
+            rtype,
+            class: Class::IN,
+            ttl: 300,
+            rdlength: rdata.len() as u16,
+            rdata,
+        };
+
+        assert_eq!(
+            "example.com 300 IN A 10.0.0.1",
+            record(Type::A, vec![10, 0, 0, 1]).to_string()
+        );
+
+        let mut mx = vec![0, 10];
+        mx.extend(
+            Hostname::from_string("mail.example.com")
+                .unwrap()
+                .to_bytes(),
+        );
+        assert_eq!(
+            "example.com 300 IN MX 10 mail.example.com",
+            record(Type::MX, mx).to_string()
+        );
+
+        let mut txt = vec![5];
+        txt.extend("hello".as_bytes());
+        txt.push(0);
+        assert_eq!(
+            "example.com 300 IN TXT \"hello\" \"\"",
+            record(Type::TXT, txt).to_string()
+        );
+
+        // malformed rdata falls back to the generic format
+        assert_eq!(
+            "example.com 300 IN A \\# 3 0a0000",
+            record(Type::A, vec![10, 0, 0]).to_string()
+        );
+    }
+
+    #[test]
+    fn decompress_mx_rdata() {
+        // message holding example.com at offset 0 followed by MX rdata that points back to it
+        let mut message = Hostname::from_string("example.com").unwrap().to_bytes();
+        let rdata = vec![0, 10, 4, b'm', b'a', b'i', b'l', 0xc0, 0x00];
+        message.extend(&rdata);
+
+        let record = ResourceRecord {
+            name: Hostname::from_string("example.com").unwrap(),
+            rtype: Type::MX,
+            class: Class::IN,
+            ttl: 300,
+            rdlength: rdata.len() as u16,
+            rdata,
+        };
+
+        let decompressed = record.decompress(message.as_slice()).unwrap();
+
+        let mut expected = vec![0, 10];
+        expected.extend(
+            Hostname::from_string("mail.example.com")
+                .unwrap()
+                .to_bytes(),
+        );
+        assert_eq!(expected, decompressed.rdata);
+        assert_eq!(expected.len() as u16, decompressed.rdlength);
+    }
+
+    #[test]
+    fn unknown_types_keep_opaque_rdata() {
+        // HTTPS record whose rdata happens to hold what looks like a compression pointer
+        let rdata = vec![0, 1, 0xc0, 0x00];
+        let mut bytes = Hostname::from_string("example.com").unwrap().to_bytes();
+        bytes.extend(&65u16.to_be_bytes());
+        bytes.extend(&(Class::IN as u16).to_be_bytes());
+        bytes.extend(&300u32.to_be_bytes());
+        bytes.extend(&(rdata.len() as u16).to_be_bytes());
+        bytes.extend(&rdata);
+
+        let parsed = ResourceRecord::parse(bytes.as_slice()).unwrap();
+        assert_eq!(Type::Unknown(65), parsed.record.rtype);
+        assert_eq!(rdata, parsed.record.rdata);
+        assert_eq!(
+            rdata,
+            parsed.record.decompress(bytes.as_slice()).unwrap().rdata
+        );
+        assert_eq!(
+            "example.com 300 IN TYPE65 \\# 4 0001c000",
+            parsed.record.to_string()
+        );
+        assert_eq!(bytes, parsed.record.pack().data);
+    }
 }
@@ -2,11 +2,17 @@ use crate::dns::additional::Additional;
 use crate::dns::answer::Answer;
 use crate::dns::authority::Authority;
 use crate::dns::classes::Class;
+use crate::dns::dnssec::{
+    self, DNSKEYRecord, DSRecord, NSEC3PARAMRecord, NSEC3Record, NSECRecord, RRSIGRecord,
+};
+use crate::dns::edns::Edns;
 use crate::dns::hostname::Hostname;
 use crate::dns::message::Message;
 use crate::dns::resource_record::ResourceRecord;
 use crate::dns::types::Type;
 
+const NXDOMAIN: u8 = 3;
+
 #[derive(PartialEq, Debug)]
 pub struct Response {
     pub query: Message,
@@ -16,35 +22,235 @@ pub struct Response {
     pub additionals: Vec<Additional>,
     pub authoritative_answer: bool,
     pub recursion_available: bool,
+    /// OPT record to send in the additional section
+    pub edns: Option<Edns>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum Record {
     A(ARecord),
     NS(NSRecord),
+    CNAME(CNAMERecord),
+    AAAA(AAAARecord),
+    DNSKEY(DNSKEYRecord),
+    DS(DSRecord),
+    RRSIG(RRSIGRecord),
+    NSEC(NSECRecord),
+    NSEC3(NSEC3Record),
+    NSEC3PARAM(NSEC3PARAMRecord),
+    SOA(SOARecord),
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ARecord {
     pub name: String,
     pub ttl: u32,
     pub ip: [u8; 4],
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
+pub struct AAAARecord {
+    pub name: String,
+    pub ttl: u32,
+    pub ip: [u8; 16],
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct NSRecord {
     pub name: String,
     pub ttl: u32,
     pub ns: String,
 }
 
+#[derive(Clone, PartialEq, Debug)]
+pub struct CNAMERecord {
+    pub name: String,
+    pub ttl: u32,
+    pub cname: String,
+}
+
+/// Start of authority record at the apex of a zone
+#[derive(Clone, PartialEq, Debug)]
+pub struct SOARecord {
+    pub name: String,
+    pub ttl: u32,
+    /// Primary nameserver of the zone
+    pub mname: String,
+    /// Mailbox of the person responsible for the zone, with the @ written as a dot
+    pub rname: String,
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    /// TTL of negative answers from the zone, as redefined by IETF RFC 2308
+    pub minimum: u32,
+}
+
 impl Record {
-    fn to_rr(&self) -> Result<ResourceRecord, String> {
+    pub(crate) fn to_rr(&self) -> Result<ResourceRecord, String> {
         match self {
             Record::A(record) => record.to_rr(),
             Record::NS(record) => record.to_rr(),
+            Record::CNAME(record) => record.to_rr(),
+            Record::AAAA(record) => record.to_rr(),
+            Record::DNSKEY(r) => dnssec::resource_record(&r.name, Type::DNSKEY, r.ttl, r.rdata()),
+            Record::DS(r) => dnssec::resource_record(&r.name, Type::DS, r.ttl, r.rdata()),
+            Record::RRSIG(r) => dnssec::resource_record(&r.name, Type::RRSIG, r.ttl, r.rdata()?),
+            Record::NSEC(r) => dnssec::resource_record(&r.name, Type::NSEC, r.ttl, r.rdata()?),
+            Record::NSEC3(r) => dnssec::resource_record(&r.name, Type::NSEC3, r.ttl, r.rdata()),
+            Record::NSEC3PARAM(r) => {
+                dnssec::resource_record(&r.name, Type::NSEC3PARAM, r.ttl, r.rdata())
+            }
+            Record::SOA(record) => record.to_rr(),
+        }
+    }
+
+    /// Converts a parsed resource record back into a record
+    ///
+    /// Any compressed names in the resource record must already have been expanded, which is done
+    /// when parsing a message.
+    pub(crate) fn from_rr(rr: &ResourceRecord) -> Result<Record, String> {
+        let name = rr.name.to_string();
+        match rr.rtype {
+            Type::A => {
+                if rr.rdata.len() != 4 {
+                    return Err(format!("Invalid A record rdata length {}", rr.rdata.len()));
+                }
+                return Ok(Record::A(ARecord {
+                    name,
+                    ttl: rr.ttl,
+                    ip: [rr.rdata[0], rr.rdata[1], rr.rdata[2], rr.rdata[3]],
+                }));
+            }
+            Type::AAAA => {
+                if rr.rdata.len() != 16 {
+                    return Err(format!(
+                        "Invalid AAAA record rdata length {}",
+                        rr.rdata.len()
+                    ));
+                }
+                let mut ip = [0u8; 16];
+                ip.copy_from_slice(rr.rdata.as_slice());
+                return Ok(Record::AAAA(AAAARecord {
+                    name,
+                    ttl: rr.ttl,
+                    ip,
+                }));
+            }
+            Type::NS => {
+                let ns = Hostname::parse(rr.rdata.as_slice())?.hostname;
+                return Ok(Record::NS(NSRecord {
+                    name,
+                    ttl: rr.ttl,
+                    ns: ns.to_string(),
+                }));
+            }
+            Type::CNAME => {
+                let cname = Hostname::parse(rr.rdata.as_slice())?.hostname;
+                return Ok(Record::CNAME(CNAMERecord {
+                    name,
+                    ttl: rr.ttl,
+                    cname: cname.to_string(),
+                }));
+            }
+            Type::DNSKEY => {
+                return DNSKEYRecord::from_rdata(name, rr.ttl, &rr.rdata).map(Record::DNSKEY)
+            }
+            Type::DS => return DSRecord::from_rdata(name, rr.ttl, &rr.rdata).map(Record::DS),
+            Type::RRSIG => {
+                return RRSIGRecord::from_rdata(name, rr.ttl, &rr.rdata).map(Record::RRSIG)
+            }
+            Type::NSEC => return NSECRecord::from_rdata(name, rr.ttl, &rr.rdata).map(Record::NSEC),
+            Type::NSEC3 => {
+                return NSEC3Record::from_rdata(name, rr.ttl, &rr.rdata).map(Record::NSEC3)
+            }
+            Type::NSEC3PARAM => {
+                return NSEC3PARAMRecord::from_rdata(name, rr.ttl, &rr.rdata)
+                    .map(Record::NSEC3PARAM)
+            }
+            Type::SOA => return SOARecord::from_rdata(name, rr.ttl, &rr.rdata).map(Record::SOA),
+            _ => return Err(format!("Unsupported record type {}", rr.rtype)),
         }
     }
+
+    /// Owner name of the record
+    pub fn name(&self) -> &str {
+        return match self {
+            Record::A(record) => &record.name,
+            Record::NS(record) => &record.name,
+            Record::CNAME(record) => &record.name,
+            Record::AAAA(record) => &record.name,
+            Record::DNSKEY(record) => &record.name,
+            Record::DS(record) => &record.name,
+            Record::RRSIG(record) => &record.name,
+            Record::NSEC(record) => &record.name,
+            Record::NSEC3(record) => &record.name,
+            Record::NSEC3PARAM(record) => &record.name,
+            Record::SOA(record) => &record.name,
+        };
+    }
+
+    pub fn rtype(&self) -> Type {
+        return match self {
+            Record::A(_) => Type::A,
+            Record::NS(_) => Type::NS,
+            Record::CNAME(_) => Type::CNAME,
+            Record::AAAA(_) => Type::AAAA,
+            Record::DNSKEY(_) => Type::DNSKEY,
+            Record::DS(_) => Type::DS,
+            Record::RRSIG(_) => Type::RRSIG,
+            Record::NSEC(_) => Type::NSEC,
+            Record::NSEC3(_) => Type::NSEC3,
+            Record::NSEC3PARAM(_) => Type::NSEC3PARAM,
+            Record::SOA(_) => Type::SOA,
+        };
+    }
+}
+
+/// Formats the record the way it would appear in a zone file, e.g. "www.example.com 300 A 10.0.0.1"
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = dnssec::presentation_name(self.name());
+        return match self {
+            Record::A(record) => write!(
+                f,
+                "{} {} A {}",
+                name,
+                record.ttl,
+                std::net::Ipv4Addr::from(record.ip)
+            ),
+            Record::NS(record) => write!(f, "{} {} NS {}", name, record.ttl, record.ns),
+            Record::CNAME(record) => write!(f, "{} {} CNAME {}", name, record.ttl, record.cname),
+            Record::AAAA(record) => write!(
+                f,
+                "{} {} AAAA {}",
+                name,
+                record.ttl,
+                std::net::Ipv6Addr::from(record.ip)
+            ),
+            Record::DNSKEY(r) => write!(f, "{} {} DNSKEY {}", name, r.ttl, r.rdata_to_string()),
+            Record::DS(r) => write!(f, "{} {} DS {}", name, r.ttl, r.rdata_to_string()),
+            Record::RRSIG(r) => write!(f, "{} {} RRSIG {}", name, r.ttl, r.rdata_to_string()),
+            Record::NSEC(r) => write!(f, "{} {} NSEC {}", name, r.ttl, r.rdata_to_string()),
+            Record::NSEC3(r) => write!(f, "{} {} NSEC3 {}", name, r.ttl, r.rdata_to_string()),
+            Record::NSEC3PARAM(r) => {
+                write!(f, "{} {} NSEC3PARAM {}", name, r.ttl, r.rdata_to_string())
+            }
+            Record::SOA(r) => write!(
+                f,
+                "{} {} SOA {} {} {} {} {} {} {}",
+                name,
+                r.ttl,
+                dnssec::presentation_name(&r.mname),
+                dnssec::presentation_name(&r.rname),
+                r.serial,
+                r.refresh,
+                r.retry,
+                r.expire,
+                r.minimum
+            ),
+        };
+    }
 }
 
 impl ARecord {
@@ -60,6 +266,19 @@ impl ARecord {
     }
 }
 
+impl AAAARecord {
+    fn to_rr(&self) -> Result<ResourceRecord, String> {
+        return Ok(ResourceRecord {
+            name: Hostname::from_string(self.name.as_str())?,
+            rtype: Type::AAAA,
+            class: Class::IN,
+            ttl: self.ttl,
+            rdlength: 16,
+            rdata: self.ip.to_vec(),
+        });
+    }
+}
+
 impl NSRecord {
     fn to_rr(&self) -> Result<ResourceRecord, String> {
         let ns_bytes = Hostname::from_string(self.ns.as_str())?.to_bytes();
@@ -74,6 +293,78 @@ impl NSRecord {
     }
 }
 
+impl CNAMERecord {
+    fn to_rr(&self) -> Result<ResourceRecord, String> {
+        let cname_bytes = Hostname::from_string(self.cname.as_str())?.to_bytes();
+        return Ok(ResourceRecord {
+            name: Hostname::from_string(self.name.as_str())?,
+            rtype: Type::CNAME,
+            class: Class::IN,
+            ttl: self.ttl,
+            rdlength: cname_bytes.len() as u16,
+            rdata: cname_bytes,
+        });
+    }
+}
+
+impl SOARecord {
+    fn to_rr(&self) -> Result<ResourceRecord, String> {
+        let mut rdata = Hostname::from_string(self.mname.as_str())?.to_bytes();
+        rdata.extend(Hostname::from_string(self.rname.as_str())?.to_bytes());
+        for value in &[
+            self.serial,
+            self.refresh,
+            self.retry,
+            self.expire,
+            self.minimum,
+        ] {
+            rdata.extend(&value.to_be_bytes());
+        }
+        return Ok(ResourceRecord {
+            name: Hostname::from_string(self.name.as_str())?,
+            rtype: Type::SOA,
+            class: Class::IN,
+            ttl: self.ttl,
+            rdlength: rdata.len() as u16,
+            rdata,
+        });
+    }
+
+    fn from_rdata(name: String, ttl: u32, rdata: &[u8]) -> Result<SOARecord, String> {
+        let mname = Hostname::parse(rdata)?;
+        let offset = mname.parsed_bytes as usize;
+        let rname = match rdata.get(offset..).map(Hostname::parse) {
+            Some(Ok(rname)) => rname,
+            _ => return Err("Invalid SOA record rdata".to_string()),
+        };
+        let offset = offset + rname.parsed_bytes as usize;
+        let values: Vec<u32> = match rdata.get(offset..) {
+            Some(numbers) if numbers.len() == 20 => numbers
+                .chunks(4)
+                .map(|n| u32::from_be_bytes([n[0], n[1], n[2], n[3]]))
+                .collect(),
+            _ => return Err("Invalid SOA record rdata length".to_string()),
+        };
+        return Ok(SOARecord {
+            name,
+            ttl,
+            mname: mname.hostname.to_string(),
+            rname: rname.hostname.to_string(),
+            serial: values[0],
+            refresh: values[1],
+            retry: values[2],
+            expire: values[3],
+            minimum: values[4],
+        });
+    }
+
+    /// How long negative answers from the zone may be cached for, which IETF RFC 2308 sets to the
+    /// lower of the SOA record's own TTL and its minimum field
+    pub fn negative_ttl(&self) -> u32 {
+        return self.ttl.min(self.minimum);
+    }
+}
+
 impl Response {
     pub fn new(query: Message) -> Response {
         return Response {
@@ -85,9 +376,37 @@ impl Response {
             // Setting authoritative bit to true by default in order to make spoofing attacks easier by default
             authoritative_answer: true,
             recursion_available: true,
+            edns: None,
         };
     }
 
+    /// Authoritative NXDOMAIN response saying the queried name does not exist, with the SOA record
+    /// of the zone in the authority section so that it can be negatively cached
+    pub fn nxdomain(query: Message, soa: &SOARecord) -> Result<Response, String> {
+        let mut response = Response::new(query);
+        response.rcode = NXDOMAIN;
+        response.add_negative_soa(soa)?;
+        return Ok(response);
+    }
+
+    /// Authoritative NODATA response saying the queried name exists but has no records of the
+    /// queried type, which IETF RFC 2308 signals with no answers and the SOA record of the zone in
+    /// the authority section
+    pub fn nodata(query: Message, soa: &SOARecord) -> Result<Response, String> {
+        let mut response = Response::new(query);
+        response.add_negative_soa(soa)?;
+        return Ok(response);
+    }
+
+    /// Adds the SOA record of the zone to the authority section of a negative response, with its TTL
+    /// lowered to the time the response may be negatively cached for
+    pub fn add_negative_soa(&mut self, soa: &SOARecord) -> Result<(), String> {
+        return self.add_authority(Record::SOA(SOARecord {
+            ttl: soa.negative_ttl(),
+            ..soa.clone()
+        }));
+    }
+
     pub fn add_answer(&mut self, record: Record) -> Result<(), String> {
         self.answers.push(record.to_rr()?);
         return Ok(());
@@ -113,7 +432,7 @@ impl Response {
         header.rcode = self.rcode;
         header.ancount = self.answers.len() as u16;
         header.nscount = self.authorities.len() as u16;
-        header.arcount = self.additionals.len() as u16;
+        header.arcount = self.additionals.len() as u16 + self.edns.is_some() as u16;
 
         // TODO: reduce amount of cloning in this code
         return Ok(Message {
@@ -122,6 +441,7 @@ impl Response {
             answers: self.answers.clone(),
             authorities: self.authorities.clone(),
             additionals: self.additionals.clone(),
+            edns: self.edns.clone(),
         });
     }
 }
@@ -133,9 +453,13 @@ mod tests {
     use crate::dns::header::{Header, Opcode};
     use crate::dns::hostname::Hostname;
     use crate::dns::message::Message;
+    use crate::dns::query::Query;
     use crate::dns::question::Question;
-    use crate::dns::response::{ARecord, NSRecord, Record, Response};
+    use crate::dns::response::{
+        AAAARecord, ARecord, CNAMERecord, NSRecord, Record, Response, SOARecord,
+    };
     use crate::dns::types::Type;
+    use crate::rng;
 
     #[test]
     fn response_to_message() {
@@ -148,6 +472,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -177,6 +503,8 @@ mod tests {
             rd: true,
             ra: true,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -203,6 +531,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -244,6 +574,8 @@ mod tests {
             rd: true,
             ra: true,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 1,
@@ -277,6 +609,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -329,6 +663,8 @@ mod tests {
             rd: true,
             ra: false,
             z: 0,
+            ad: false,
+            cd: false,
             rcode: 0,
             qdcount: 1,
             ancount: 0,
@@ -358,4 +694,110 @@ mod tests {
 
         assert_eq!(expected_response, response);
     }
+
+    #[test]
+    fn record_from_rr() {
+        let a_record = Record::A(ARecord {
+            name: "www.example.com".to_string(),
+            ttl: 0x1234,
+            ip: [127, 0, 0, 1],
+        });
+
+        let ns_record = Record::NS(NSRecord {
+            name: "example.com".to_string(),
+            ttl: 0x1234,
+            ns: "ns.example.com".to_string(),
+        });
+
+        assert_eq!(
+            a_record,
+            Record::from_rr(&a_record.to_rr().unwrap()).unwrap()
+        );
+        assert_eq!(
+            ns_record,
+            Record::from_rr(&ns_record.to_rr().unwrap()).unwrap()
+        );
+
+        let aaaa_record = Record::AAAA(AAAARecord {
+            name: "www.example.com".to_string(),
+            ttl: 0x1234,
+            ip: [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+        });
+        assert_eq!(
+            aaaa_record,
+            Record::from_rr(&aaaa_record.to_rr().unwrap()).unwrap()
+        );
+
+        let cname_record = Record::CNAME(CNAMERecord {
+            name: "abc.example.com".to_string(),
+            ttl: 0x1234,
+            cname: "www.example.com".to_string(),
+        });
+        assert_eq!(
+            cname_record,
+            Record::from_rr(&cname_record.to_rr().unwrap()).unwrap()
+        );
+
+        let soa_record = Record::SOA(SOARecord {
+            name: "example.com".to_string(),
+            ttl: 3600,
+            mname: "ns1.example.com".to_string(),
+            rname: "hostmaster.example.com".to_string(),
+            serial: 2024010101,
+            refresh: 7200,
+            retry: 900,
+            expire: 1209600,
+            minimum: 300,
+        });
+        assert_eq!(
+            soa_record,
+            Record::from_rr(&soa_record.to_rr().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn negative_responses() {
+        let query = Query::new(vec!["missing.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        let soa = SOARecord {
+            name: "example.com".to_string(),
+            ttl: 3600,
+            mname: "ns1.example.com".to_string(),
+            rname: "hostmaster.example.com".to_string(),
+            serial: 1,
+            refresh: 7200,
+            retry: 900,
+            expire: 1209600,
+            minimum: 300,
+        };
+
+        let nxdomain = Response::nxdomain(query.clone(), &soa)
+            .unwrap()
+            .to_message()
+            .unwrap();
+        assert!(nxdomain.header.aa);
+        assert_eq!(3, nxdomain.rcode());
+        assert!(nxdomain.answers.is_empty());
+        // the SOA record is only cached for as long as its minimum field allows
+        assert_eq!(
+            Record::SOA(SOARecord {
+                ttl: 300,
+                ..soa.clone()
+            }),
+            Record::from_rr(&nxdomain.authorities[0]).unwrap()
+        );
+        assert_eq!(Some(300), nxdomain.negative_ttl());
+
+        let nodata = Response::nodata(query.clone(), &soa)
+            .unwrap()
+            .to_message()
+            .unwrap();
+        assert_eq!((0, Some(300)), (nodata.rcode(), nodata.negative_ttl()));
+
+        // without an SOA record a negative answer cannot be cached
+        let mut response = Response::new(query);
+        response.rcode = 3;
+        assert_eq!(None, response.to_message().unwrap().negative_ttl());
+    }
 }
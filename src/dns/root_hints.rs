@@ -0,0 +1,207 @@
+use crate::dns::hostname::normalize_name;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Root server listed in a root hints file
+#[derive(PartialEq, Debug, Clone)]
+pub struct RootServer {
+    pub name: String,
+    pub ipv4: Vec<Ipv4Addr>,
+    pub ipv6: Vec<Ipv6Addr>,
+}
+
+/// Root hints in the standard named.root format, as distributed by IANA and shipped with BIND
+///
+/// The file is a zone file fragment holding NS records for the root zone along with A and AAAA
+/// records for each of the listed nameservers, e.g.
+///
+/// ```text
+/// .                        3600000      NS    A.ROOT-SERVERS.NET.
+/// A.ROOT-SERVERS.NET.      3600000      A     198.41.0.4
+/// A.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:ba3e::2:30
+/// ```
+///
+/// A lab can point this at its own root by writing a hints file that lists the lab root servers.
+#[derive(PartialEq, Debug, Clone)]
+pub struct RootHints {
+    pub servers: Vec<RootServer>,
+}
+
+impl RootHints {
+    pub fn load(path: &str) -> Result<RootHints, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Err(e) => return Err(format!("Could not read root hints file {}: {}", path, e)),
+            Ok(c) => c,
+        };
+        return RootHints::parse(&contents);
+    }
+
+    pub(crate) fn parse(contents: &str) -> Result<RootHints, String> {
+        let mut servers: Vec<RootServer> = Vec::new();
+        let mut addresses: Vec<(String, String, String)> = Vec::new();
+
+        for (i, line) in contents.lines().enumerate() {
+            // ; starts a comment in zone files
+            let fields: Vec<&str> = line
+                .splitn(2, ';')
+                .next()
+                .unwrap_or("")
+                .split_whitespace()
+                .collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            // the TTL and class are optional, so the type is the first field after the owner that
+            // is neither a number nor the IN class
+            let owner = normalize_name(fields[0]);
+            let rest: Vec<&str> = fields[1..]
+                .iter()
+                .skip_while(|f| f.parse::<u32>().is_ok() || f.eq_ignore_ascii_case("IN"))
+                .cloned()
+                .collect();
+            if rest.len() < 2 {
+                return Err(format!(
+                    "Line {} of the root hints is malformed: {}",
+                    i + 1,
+                    line
+                ));
+            }
+
+            let rtype = rest[0].to_ascii_uppercase();
+            let rdata = rest[1];
+            match rtype.as_str() {
+                "NS" => {
+                    if !owner.is_empty() {
+                        return Err(format!(
+                            "Line {} of the root hints has an NS record for {} rather than the root",
+                            i + 1,
+                            owner
+                        ));
+                    }
+                    servers.push(RootServer {
+                        name: normalize_name(rdata),
+                        ipv4: Vec::new(),
+                        ipv6: Vec::new(),
+                    });
+                }
+                "A" | "AAAA" => addresses.push((owner, rtype, String::from(rdata))),
+                _ => {
+                    return Err(format!(
+                        "Line {} of the root hints has an unsupported record type {}",
+                        i + 1,
+                        rtype
+                    ))
+                }
+            }
+        }
+
+        // addresses are matched up after all NS records are known since files are not required to
+        // list them in any particular order
+        for (owner, rtype, rdata) in addresses {
+            let server = match servers.iter_mut().find(|s| s.name == owner) {
+                None => continue, // addresses for names that are not root servers are not hints
+                Some(s) => s,
+            };
+            let result = if rtype == "A" {
+                Ipv4Addr::from_str(&rdata).map(|ip| server.ipv4.push(ip))
+            } else {
+                Ipv6Addr::from_str(&rdata).map(|ip| server.ipv6.push(ip))
+            };
+            if result.is_err() {
+                return Err(format!("Invalid {} address {} for {}", rtype, rdata, owner));
+            }
+        }
+
+        if servers.is_empty() {
+            return Err("Root hints do not list any root servers".to_string());
+        }
+
+        return Ok(RootHints { servers });
+    }
+
+    pub fn ipv4_addrs(&self) -> Vec<Ipv4Addr> {
+        return self
+            .servers
+            .iter()
+            .flat_map(|s| s.ipv4.iter().cloned())
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::root_hints::{RootHints, RootServer};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_named_root() {
+        let contents = "\
+;       This file holds the information on root name servers needed to
+;       initialize cache of Internet domain name servers
+;
+; FORMERLY NS.INTERNIC.NET
+;
+.                        3600000      NS    A.ROOT-SERVERS.NET.
+A.ROOT-SERVERS.NET.      3600000      A     198.41.0.4
+A.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:ba3e::2:30
+;
+; FORMERLY NS1.ISI.EDU
+;
+.                        3600000      NS    B.ROOT-SERVERS.NET.
+B.ROOT-SERVERS.NET.      3600000      A     170.247.170.2
+B.ROOT-SERVERS.NET.      3600000      AAAA  2801:1b8:10::b
+; End of file";
+
+        let expected = RootHints {
+            servers: vec![
+                RootServer {
+                    name: "a.root-servers.net".to_string(),
+                    ipv4: vec![Ipv4Addr::new(198, 41, 0, 4)],
+                    ipv6: vec![Ipv6Addr::from_str("2001:503:ba3e::2:30").unwrap()],
+                },
+                RootServer {
+                    name: "b.root-servers.net".to_string(),
+                    ipv4: vec![Ipv4Addr::new(170, 247, 170, 2)],
+                    ipv6: vec![Ipv6Addr::from_str("2801:1b8:10::b").unwrap()],
+                },
+            ],
+        };
+
+        let hints = RootHints::parse(contents).unwrap();
+
+        assert_eq!(expected, hints);
+        assert_eq!(
+            vec![
+                Ipv4Addr::new(198, 41, 0, 4),
+                Ipv4Addr::new(170, 247, 170, 2)
+            ],
+            hints.ipv4_addrs()
+        );
+    }
+
+    #[test]
+    fn parse_lab_root_hints() {
+        // lab hints often include the class and list the addresses first
+        let contents = "\
+root.lab.    IN A  10.37.132.53
+.  518400 IN NS root.lab
+";
+
+        let hints = RootHints::parse(contents).unwrap();
+
+        assert_eq!(1, hints.servers.len());
+        assert_eq!("root.lab", hints.servers[0].name);
+        assert_eq!(vec![Ipv4Addr::new(10, 37, 132, 53)], hints.ipv4_addrs());
+    }
+
+    #[test]
+    fn parse_invalid_root_hints() {
+        assert!(RootHints::parse("; nothing here\n").is_err());
+        assert!(
+            RootHints::parse(". NS a.root-servers.net.\na.root-servers.net. A 1.2.3\n").is_err()
+        );
+        assert!(RootHints::parse("com. NS a.gtld-servers.net.\n").is_err());
+    }
+}
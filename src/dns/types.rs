@@ -1,12 +1,112 @@
-use num_derive::FromPrimitive;
+use std::fmt;
+use std::str::FromStr;
 
-#[repr(u16)]
-#[derive(FromPrimitive, PartialEq, Debug, Copy, Clone)]
+#[derive(PartialEq, Debug, Copy, Clone)]
 /// Subset of TYPE values specified in IETF RFC 1035
 pub enum Type {
-    A = 1,
-    NS = 2,
-    CNAME = 5,
-    SOA = 6,
-    TXT = 16,
+    A,
+    NS,
+    CNAME,
+    SOA,
+    PTR,
+    MX,
+    TXT,
+    AAAA, // specified in IETF RFC 3596
+    SRV,  // specified in IETF RFC 2782
+    OPT,  // specified in IETF RFC 6891
+    DS,   // specified in IETF RFC 4034
+    RRSIG,
+    NSEC,
+    DNSKEY,
+    NSEC3, // specified in IETF RFC 5155
+    NSEC3PARAM,
+    ANY, // QTYPE only
+    /// Any other TYPE value, written as TYPEnnn and with opaque RDATA as in IETF RFC 3597
+    Unknown(u16),
+}
+
+/// Names and values of the types above other than Unknown
+const KNOWN_TYPES: [(Type, &str, u16); 17] = [
+    (Type::A, "A", 1),
+    (Type::NS, "NS", 2),
+    (Type::CNAME, "CNAME", 5),
+    (Type::SOA, "SOA", 6),
+    (Type::PTR, "PTR", 12),
+    (Type::MX, "MX", 15),
+    (Type::TXT, "TXT", 16),
+    (Type::AAAA, "AAAA", 28),
+    (Type::SRV, "SRV", 33),
+    (Type::OPT, "OPT", 41),
+    (Type::DS, "DS", 43),
+    (Type::RRSIG, "RRSIG", 46),
+    (Type::NSEC, "NSEC", 47),
+    (Type::DNSKEY, "DNSKEY", 48),
+    (Type::NSEC3, "NSEC3", 50),
+    (Type::NSEC3PARAM, "NSEC3PARAM", 51),
+    (Type::ANY, "ANY", 255),
+];
+
+impl Type {
+    /// Returns the type with the given value, Unknown only if none of the others has it
+    pub fn from_u16(value: u16) -> Type {
+        return match KNOWN_TYPES.iter().find(|(_, _, v)| *v == value) {
+            Some((rtype, _, _)) => *rtype,
+            None => Type::Unknown(value),
+        };
+    }
+
+    pub fn to_u16(self) -> u16 {
+        if let Type::Unknown(value) = self {
+            return value;
+        }
+        return KNOWN_TYPES.iter().find(|(t, _, _)| *t == self).unwrap().2;
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match KNOWN_TYPES.iter().find(|(t, _, _)| t == self) {
+            Some((_, name, _)) => write!(f, "{}", name),
+            None => write!(f, "TYPE{}", self.to_u16()),
+        };
+    }
+}
+
+impl FromStr for Type {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_ascii_uppercase();
+        // OPT is a pseudo type that only appears in the additional section
+        if let Some((rtype, _, _)) = KNOWN_TYPES
+            .iter()
+            .find(|(t, name, _)| *name == upper && *t != Type::OPT)
+        {
+            return Ok(*rtype);
+        }
+        return match upper.strip_prefix("TYPE").map(|n| n.parse::<u16>()) {
+            Some(Ok(value)) => Ok(Type::from_u16(value)),
+            _ => Err(format!("Unsupported record type {}", s)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::Type;
+
+    #[test]
+    fn unknown_types_use_their_number() {
+        assert_eq!(Type::Unknown(65), "TYPE65".parse().unwrap());
+        assert_eq!(Type::Unknown(257), "type257".parse().unwrap());
+        // a number with a name is the named type
+        assert_eq!(Type::NS, "TYPE2".parse().unwrap());
+        assert_eq!(Type::NS, Type::from_u16(2));
+        assert_eq!("TYPE65", Type::from_u16(65).to_string());
+        assert_eq!("AAAA", Type::AAAA.to_string());
+        assert_eq!(257, Type::Unknown(257).to_u16());
+        assert_eq!(28, Type::AAAA.to_u16());
+        assert!("TYPE65536".parse::<Type>().is_err());
+        assert!("HTTPSX".parse::<Type>().is_err());
+    }
 }
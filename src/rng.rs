@@ -0,0 +1,14 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Random number generator for DNS IDs and subdomains
+///
+/// Given a seed, the same sequence of IDs and subdomains is generated on every run so that lab
+/// experiments and tests can be replayed exactly. Sequences are only repeatable for the same
+/// version of the rand crate. Without a seed, the generator is seeded from the operating system.
+pub fn new(seed: Option<u64>) -> StdRng {
+    return match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+}
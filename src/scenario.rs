@@ -0,0 +1,524 @@
+use crate::commands::seconds;
+use crate::dns;
+use crate::kaminsky::AttackOptions;
+use crate::pacer::PacerOptions;
+use crate::payload::{PayloadOptions, PayloadStrategy};
+use crate::rng;
+use crate::scope::Scope;
+use crate::subdomain::{self, SubdomainKind, SubdomainOptions};
+use crate::verify::ProbeSchedule;
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Attack scenario described in a TOML file so that lab runs can be reproduced, e.g.
+///
+/// ```toml
+/// scope = "lab.scope"
+///
+/// [target]
+/// resolver = "10.37.132.7"
+/// domain = "example.com"
+/// attacker_ns = "ns.attacker.lab"
+///
+/// [sources]
+/// addrs = ["10.37.132.53"]
+///
+/// [payload]
+/// strategy = "glue"
+/// glue_addrs = ["10.37.132.66"]
+///
+/// [timing]
+/// duration = 30.0
+/// round_window = 0.5
+///
+/// [probe]
+/// schedule = "each-round"
+///
+/// [output]
+/// json = "results/run.json"
+/// ```
+///
+/// Every section apart from the target is optional and maps onto the options of an attack, with
+/// the same defaults as the attack subcommand. Relative paths are resolved from the directory the
+/// scenario file is in.
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Lab scope file, defaults to the private and documentation address ranges
+    pub scope: Option<String>,
+    /// Seed for the query IDs and random subdomains, so that a run can be replayed exactly
+    pub seed: Option<u64>,
+    pub target: Target,
+    #[serde(default)]
+    pub sources: Sources,
+    #[serde(default)]
+    pub payload: Payload,
+    #[serde(default)]
+    pub subdomains: Subdomains,
+    #[serde(default)]
+    pub ports: Ports,
+    #[serde(default)]
+    pub timing: Timing,
+    pub rate: Option<Rate>,
+    #[serde(default)]
+    pub probe: Probe,
+    #[serde(default)]
+    pub output: Output,
+    /// Directory relative paths in the scenario are resolved from
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+/// Resolver to poison and the domain to poison it for
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    pub resolver: Ipv4Addr,
+    pub domain: String,
+    pub attacker_ns: String,
+}
+
+/// Where spoofed responses come from, in order of precedence
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Sources {
+    #[serde(default)]
+    pub addrs: Vec<Ipv4Addr>,
+    /// Discover the target domain's nameservers, by asking the discovery server or the resolver
+    #[serde(default)]
+    pub discover: bool,
+    pub discovery_server: Option<String>,
+    pub root_hints: Option<String>,
+}
+
+/// Records in the spoofed responses
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Payload {
+    /// One of answer, referral, cname, or glue
+    pub strategy: Option<String>,
+    pub answer_ip: Option<Ipv4Addr>,
+    pub answer_ttl: Option<u32>,
+    pub ns_ttl: Option<u32>,
+    #[serde(default)]
+    pub glue_addrs: Vec<IpAddr>,
+    /// One of any, in, or out
+    pub glue_bailiwick: Option<String>,
+    pub cname_target: Option<String>,
+}
+
+/// Subdomains queried in each round
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Subdomains {
+    /// One of mixed, lowercase, dictionary, or counter
+    pub generator: Option<String>,
+    pub length: Option<usize>,
+    /// Word list for the dictionary generator
+    pub words: Option<String>,
+    /// Existing name under the target domain to query the generated labels below
+    pub parent: Option<String>,
+}
+
+/// UDP ports of the spoofed responses
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Ports {
+    pub spoofed: Option<u16>,
+    pub target: Option<u16>,
+}
+
+/// Durations are given in seconds
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Timing {
+    pub duration: Option<f32>,
+    pub rounds: Option<u32>,
+    pub round_window: Option<f32>,
+    pub delay: Option<f32>,
+}
+
+/// Pacing of the spoofed packets
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Rate {
+    pub packets_per_second: f64,
+    pub burst: Option<u32>,
+    pub ramp_up: Option<f32>,
+    pub ramp_start_rate: Option<f64>,
+}
+
+/// How success is checked
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Probe {
+    /// One of never, each-round, or end
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub cache_only: bool,
+}
+
+/// Where the attack statistics are written
+#[derive(Deserialize, PartialEq, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    /// Whether to print the statistics as JSON on stdout, defaults to true
+    pub stdout: Option<bool>,
+    /// File to write the statistics to as JSON
+    pub json: Option<String>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Err(e) => return Err(format!("Could not read scenario file {}: {}", path, e)),
+            Ok(c) => c,
+        };
+        let mut scenario = Scenario::parse(&contents)?;
+        scenario.base_dir = Path::new(path)
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .unwrap_or_default();
+        return Ok(scenario);
+    }
+
+    fn parse(contents: &str) -> Result<Scenario, String> {
+        return match toml::from_str(contents) {
+            Err(e) => Err(format!("Invalid scenario: {}", e)),
+            Ok(s) => Ok(s),
+        };
+    }
+
+    /// Resolves a path given in the scenario relative to the scenario file
+    fn path(&self, path: &str) -> String {
+        return self.base_dir.join(path).to_string_lossy().into_owned();
+    }
+
+    pub fn scope(&self) -> Result<Scope, String> {
+        return match &self.scope {
+            None => Ok(Scope::default_lab()),
+            Some(path) => Scope::load(&self.path(path)),
+        };
+    }
+
+    pub fn root_hints(&self) -> Option<String> {
+        return self.sources.root_hints.as_ref().map(|path| self.path(path));
+    }
+
+    pub fn discovery_server(&self) -> Option<String> {
+        if !self.sources.discover {
+            return None;
+        }
+        return match &self.sources.discovery_server {
+            Some(server) => Some(server.clone()),
+            None => Some(self.target.resolver.to_string()),
+        };
+    }
+
+    pub fn json_output(&self) -> Option<String> {
+        return self.output.json.as_ref().map(|path| self.path(path));
+    }
+
+    pub fn payload_options(&self) -> Result<PayloadOptions, String> {
+        let strategy = parse_or(&self.payload.strategy, PayloadStrategy::Answer)?;
+        let mut options = PayloadOptions::new(strategy);
+        if let Some(ip) = self.payload.answer_ip {
+            options.answer_ip = ip;
+        }
+        if let Some(ttl) = self.payload.answer_ttl {
+            options.answer_ttl = ttl;
+        }
+        if let Some(ttl) = self.payload.ns_ttl {
+            options.ns_ttl = ttl;
+        }
+        options.glue_addrs = self.payload.glue_addrs.clone();
+        options.glue_bailiwick = parse_or(&self.payload.glue_bailiwick, options.glue_bailiwick)?;
+        options.cname_target = self.payload.cname_target.clone();
+        return Ok(options);
+    }
+
+    pub fn subdomain_options(&self) -> Result<SubdomainOptions, String> {
+        let kind = parse_or(&self.subdomains.generator, SubdomainKind::Mixed)?;
+        let mut options = SubdomainOptions::new(kind);
+        if let Some(length) = self.subdomains.length {
+            options.length = length;
+        }
+        if let Some(path) = &self.subdomains.words {
+            options.words = subdomain::load_words(&self.path(path))?;
+        }
+        options.parent = self.subdomains.parent.clone();
+        options.generator()?;
+        return Ok(options);
+    }
+
+    /// Attack options described by the scenario, spoofing responses from the given addresses
+    pub fn attack_options(&self, spoofed_addrs: Vec<Ipv4Addr>) -> Result<AttackOptions, String> {
+        let mut options = AttackOptions::new(
+            self.target.attacker_ns.clone(),
+            self.target.domain.clone(),
+            self.target.resolver,
+            spoofed_addrs,
+        );
+        if let Some(port) = self.ports.spoofed {
+            options.spoofed_port = port;
+        }
+        if let Some(port) = self.ports.target {
+            options.target_port = port;
+        }
+
+        options.rounds = self.timing.rounds;
+        if let Some(d) = self.timing.duration {
+            options.duration = seconds("timing.duration", d)?;
+        }
+        if let Some(w) = self.timing.round_window {
+            options.round_window = seconds("timing.round_window", w)?;
+        }
+        if let Some(d) = self.timing.delay {
+            options.delay = seconds("timing.delay", d)?;
+        }
+
+        options.payload = self.payload_options()?;
+        options.subdomains = self.subdomain_options()?;
+
+        if let Some(rate) = &self.rate {
+            let ramp_up = match rate.ramp_up {
+                None => Duration::new(0, 0),
+                Some(ramp_up) => seconds("rate.ramp_up", ramp_up)?,
+            };
+            let pacing = match PacerOptions::new(
+                rate.packets_per_second,
+                rate.burst,
+                ramp_up,
+                rate.ramp_start_rate,
+            ) {
+                Err(e) => return Err(format!("Invalid rate: {}", e)),
+                Ok(p) => p,
+            };
+            options.pacing = Some(pacing);
+        }
+
+        options.probe = parse_or(&self.probe.schedule, ProbeSchedule::Never)?;
+        options.probe_recursion_desired = !self.probe.cache_only;
+        options.seed = self.seed;
+
+        return Ok(options);
+    }
+
+    /// Checks everything about the scenario that can be checked without sending any packets
+    ///
+    /// This covers the option values, the lab scope, the root hints, and whether the payload can be
+    /// built. Nameservers that would be discovered are not looked up.
+    pub fn validate(&self) -> Result<(), String> {
+        let scope = self.scope()?;
+        scope.check_ipv4(&self.target.resolver)?;
+        scope.check_domain(&self.target.domain)?;
+        for addr in &self.sources.addrs {
+            scope.check_ipv4(addr)?;
+        }
+
+        let root_hints = match self.root_hints() {
+            None => None,
+            Some(path) => Some(dns::RootHints::load(&path)?),
+        };
+        if self.sources.addrs.is_empty() && !self.sources.discover {
+            match &root_hints {
+                None => return Err("The sources section needs addrs, discover, or root_hints to know where to spoof responses from".to_string()),
+                Some(hints) => {
+                    for addr in hints.ipv4_addrs() {
+                        scope.check_ipv4(&addr)?;
+                    }
+                }
+            }
+        }
+
+        let options = self.attack_options(self.sources.addrs.clone())?;
+
+        // build a response for a made up subdomain to catch payloads that can never be sent
+        let qname = format!("scenario-check.{}", options.target_domain);
+        let query = dns::Query::new(vec![qname.clone()]).to_message(&mut rng::new(options.seed))?;
+        options
+            .payload
+            .build(query, &qname, &options.target_domain, &options.attacker_ns)?;
+
+        return Ok(());
+    }
+}
+
+/// Parses an optional string option, falling back to the default when it is not given
+fn parse_or<T: FromStr<Err = String>>(value: &Option<String>, default: T) -> Result<T, String> {
+    return match value {
+        None => Ok(default),
+        Some(s) => T::from_str(s),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::payload::{GlueBailiwick, PayloadStrategy};
+    use crate::scenario::Scenario;
+    use crate::subdomain::SubdomainKind;
+    use crate::verify::ProbeSchedule;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    const FULL_SCENARIO: &str = r#"
+scope = "lab.scope"
+seed = 7
+
+[target]
+resolver = "10.37.132.7"
+domain = "example.com"
+attacker_ns = "ns.example.com"
+
+[sources]
+addrs = ["10.37.132.53", "10.37.132.54"]
+
+[payload]
+strategy = "glue"
+answer_ip = "10.37.132.66"
+ns_ttl = 600
+glue_addrs = ["10.37.132.66"]
+glue_bailiwick = "in"
+
+[subdomains]
+generator = "counter"
+length = 5
+parent = "www"
+
+[ports]
+spoofed = 5353
+target = 40000
+
+[timing]
+duration = 30.0
+rounds = 20
+round_window = 0.5
+delay = 0.01
+
+[rate]
+packets_per_second = 5000.0
+burst = 50
+
+[probe]
+schedule = "each-round"
+cache_only = true
+
+[output]
+stdout = false
+json = "results/run.json"
+"#;
+
+    #[test]
+    fn full_scenario_to_attack_options() {
+        let scenario = Scenario::parse(FULL_SCENARIO).unwrap();
+        let options = scenario
+            .attack_options(scenario.sources.addrs.clone())
+            .unwrap();
+
+        assert_eq!(Ipv4Addr::new(10, 37, 132, 7), options.target_server_addr);
+        assert_eq!("example.com", options.target_domain);
+        assert_eq!(2, options.spoofed_addrs.len());
+        assert_eq!(5353, options.spoofed_port);
+        assert_eq!(40000, options.target_port);
+        assert_eq!(Some(20), options.rounds);
+        assert_eq!(Duration::from_secs(30), options.duration);
+        assert_eq!(Duration::from_millis(500), options.round_window);
+        assert_eq!(PayloadStrategy::Glue, options.payload.strategy);
+        assert_eq!(Ipv4Addr::new(10, 37, 132, 66), options.payload.answer_ip);
+        assert_eq!(600, options.payload.ns_ttl);
+        assert_eq!(
+            vec![IpAddr::V4(Ipv4Addr::new(10, 37, 132, 66))],
+            options.payload.glue_addrs
+        );
+        assert_eq!(GlueBailiwick::In, options.payload.glue_bailiwick);
+        let pacing = options.pacing.unwrap();
+        assert_eq!(5000.0, pacing.rate);
+        assert_eq!(50, pacing.burst);
+        assert_eq!(ProbeSchedule::EachRound, options.probe);
+        assert!(!options.probe_recursion_desired);
+        assert_eq!(Some(7), options.seed);
+        assert_eq!(SubdomainKind::Counter, options.subdomains.kind);
+        assert_eq!(5, options.subdomains.length);
+        assert_eq!(Some("www".to_string()), options.subdomains.parent);
+        assert_eq!(Some(false), scenario.output.stdout);
+    }
+
+    #[test]
+    fn minimal_scenario_uses_attack_defaults() {
+        let scenario = Scenario::parse(
+            "[target]\nresolver = \"10.0.0.1\"\ndomain = \"example.com\"\nattacker_ns = \"ns.attacker.lab\"\n",
+        )
+        .unwrap();
+        let options = scenario.attack_options(vec![]).unwrap();
+
+        assert_eq!(53, options.spoofed_port);
+        assert_eq!(33333, options.target_port);
+        assert_eq!(None, options.rounds);
+        assert_eq!(PayloadStrategy::Answer, options.payload.strategy);
+        assert_eq!(None, options.pacing);
+        assert_eq!(ProbeSchedule::Never, options.probe);
+        assert_eq!(None, scenario.discovery_server());
+        // without sources there is nothing to spoof from
+        assert!(scenario.validate().is_err());
+    }
+
+    #[test]
+    fn invalid_scenarios() {
+        // typos in keys are caught rather than silently ignored
+        let typo = FULL_SCENARIO.replace("round_window", "round_windwo");
+        assert!(Scenario::parse(&typo).is_err());
+
+        let strategy = Scenario::parse(&FULL_SCENARIO.replace("\"glue\"", "\"bogus\"")).unwrap();
+        assert!(strategy.attack_options(vec![]).is_err());
+
+        let duration = Scenario::parse(&FULL_SCENARIO.replace("30.0", "-1.0")).unwrap();
+        assert!(duration.attack_options(vec![]).is_err());
+        // finite but too many seconds for a Duration
+        let window =
+            Scenario::parse(&FULL_SCENARIO.replace("round_window = 0.5", "round_window = 1e30"))
+                .unwrap();
+        assert!(window.attack_options(vec![]).is_err());
+
+        // a burst of 0 would never let a packet through
+        let burst = Scenario::parse(&FULL_SCENARIO.replace("burst = 50", "burst = 0")).unwrap();
+        assert!(burst.attack_options(vec![]).is_err());
+
+        assert!(Scenario::parse("[target]\nresolver = \"not an ip\"\n").is_err());
+    }
+
+    #[test]
+    fn validate_scenario() {
+        let mut scenario = Scenario::parse(FULL_SCENARIO).unwrap();
+        scenario.scope = None;
+        assert!(scenario.validate().is_ok());
+
+        // the default lab scope does not allow public addresses
+        scenario.target.resolver = Ipv4Addr::new(8, 8, 8, 8);
+        assert!(scenario.validate().is_err());
+
+        // glue for a nameserver outside the target domain cannot be in-bailiwick
+        scenario.target.resolver = Ipv4Addr::new(10, 37, 132, 7);
+        scenario.target.attacker_ns = "ns.attacker.lab".to_string();
+        assert!(scenario.validate().is_err());
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_scenario() {
+        let mut scenario = Scenario::parse(FULL_SCENARIO).unwrap();
+        scenario.base_dir = PathBuf::from("labs/run1");
+
+        assert_eq!(
+            Some(
+                PathBuf::from("labs/run1")
+                    .join("results/run.json")
+                    .to_string_lossy()
+                    .into_owned()
+            ),
+            scenario.json_output()
+        );
+    }
+}
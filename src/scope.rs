@@ -0,0 +1,255 @@
+use crate::dns;
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+
+/// Network given in CIDR notation, e.g. 10.0.0.0/8
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        return match (self.network, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => prefix_matches(
+                u32::from(network) as u128,
+                u32::from(*addr) as u128,
+                32,
+                self.prefix_len,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                prefix_matches(u128::from(network), u128::from(*addr), 128, self.prefix_len)
+            }
+            _ => false,
+        };
+    }
+}
+
+/// Whether the top prefix_len bits of two addresses that are width bits long match
+fn prefix_matches(network: u128, addr: u128, width: u8, prefix_len: u8) -> bool {
+    if prefix_len == 0 {
+        return true;
+    }
+    let shift = width - prefix_len;
+    return network >> shift == addr >> shift;
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let network = match IpAddr::from_str(parts.next().unwrap_or("")) {
+            Err(_) => return Err(format!("Invalid network address in {}", s)),
+            Ok(addr) => addr,
+        };
+        let max_prefix_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match parts.next() {
+            // a bare address is a network containing just that address
+            None => max_prefix_len,
+            Some(len) => match u8::from_str(len) {
+                Ok(len) if len <= max_prefix_len => len,
+                _ => return Err(format!("Invalid prefix length in {}", s)),
+            },
+        };
+        return Ok(Cidr {
+            network,
+            prefix_len,
+        });
+    }
+}
+
+/// Lab scope that packets may be sent to or spoofed from
+///
+/// Raw packets must never be sent to, or spoofed from, addresses outside of the lab. Without a scope
+/// file only private, loopback and documentation ranges are allowed and any domain may be targeted.
+/// A scope file replaces those defaults. Each non-empty line that is not a # comment holds either
+/// a network in CIDR notation, a single address, or a domain, e.g.
+///
+/// ```text
+/// # SEED lab network
+/// 10.37.132.0/24
+/// example.com
+/// ```
+///
+/// Once a scope file is used, only the domains listed in it, and their subdomains, may be targeted.
+#[derive(PartialEq, Debug)]
+pub struct Scope {
+    networks: Vec<Cidr>,
+    /// Domains that may be targeted, None allows any domain
+    domains: Option<Vec<String>>,
+    /// Where the scope came from, used to explain refusals
+    source: String,
+}
+
+impl Scope {
+    /// Scope allowing only private, loopback and documentation address ranges
+    pub fn default_lab() -> Scope {
+        let networks = vec![
+            // RFC 1918 private networks
+            "10.0.0.0/8",
+            "172.16.0.0/12",
+            "192.168.0.0/16",
+            // loopback
+            "127.0.0.0/8",
+            "::1/128",
+            // RFC 5737 documentation networks
+            "192.0.2.0/24",
+            "198.51.100.0/24",
+            "203.0.113.0/24",
+            // RFC 4193 unique local addresses
+            "fc00::/7",
+            // RFC 3849 documentation prefix
+            "2001:db8::/32",
+        ];
+        return Scope {
+            networks: networks
+                .iter()
+                .map(|network| Cidr::from_str(network).unwrap())
+                .collect(),
+            domains: None,
+            source: "the default private and documentation ranges".to_string(),
+        };
+    }
+
+    pub fn load(path: &str) -> Result<Scope, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Err(e) => return Err(format!("Could not read scope file {}: {}", path, e)),
+            Ok(c) => c,
+        };
+        return Scope::parse(&contents, path);
+    }
+
+    fn parse(contents: &str, source: &str) -> Result<Scope, String> {
+        let mut networks = Vec::new();
+        let mut domains = Vec::new();
+
+        for (i, line) in contents.lines().enumerate() {
+            let entry = line.splitn(2, '#').next().unwrap_or("").trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            if entry.contains('/') || IpAddr::from_str(entry).is_ok() {
+                networks.push(Cidr::from_str(entry)?);
+            } else {
+                let domain = entry.trim_end_matches('.');
+                // the root would put every domain in scope
+                if domain.is_empty() || dns::Hostname::from_string(domain).is_err() {
+                    return Err(format!(
+                        "Line {} of {} is neither a network nor a domain: {}",
+                        i + 1,
+                        source,
+                        entry
+                    ));
+                }
+                domains.push(String::from(domain));
+            }
+        }
+
+        return Ok(Scope {
+            networks,
+            domains: Some(domains),
+            source: String::from(source),
+        });
+    }
+
+    /// Checks that packets may be sent to or spoofed from the address
+    pub fn check_addr(&self, addr: &IpAddr) -> Result<(), String> {
+        if self.networks.iter().any(|network| network.contains(addr)) {
+            return Ok(());
+        }
+        return Err(format!(
+            "Refusing to send packets involving {} since it is outside the lab scope allowed by {}",
+            addr, self.source
+        ));
+    }
+
+    pub fn check_ipv4(&self, addr: &Ipv4Addr) -> Result<(), String> {
+        return self.check_addr(&IpAddr::V4(*addr));
+    }
+
+    /// Checks that spoofed records may be sent for the domain
+    pub fn check_domain(&self, domain: &str) -> Result<(), String> {
+        let domains = match &self.domains {
+            None => return Ok(()),
+            Some(d) => d,
+        };
+
+        let hostname = dns::Hostname::from_string(domain.trim_end_matches('.'))?;
+        let allowed = domains
+            .iter()
+            .any(|allowed| match dns::Hostname::from_string(allowed) {
+                Ok(allowed) => hostname.is_subdomain_of(&allowed),
+                Err(_) => false,
+            });
+        if allowed {
+            return Ok(());
+        }
+        return Err(format!(
+            "Refusing to target {} since it is outside the lab scope allowed by {}",
+            domain, self.source
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scope::{Cidr, Scope};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    #[test]
+    fn cidr_contains() {
+        let network = Cidr::from_str("10.37.132.0/24").unwrap();
+
+        assert!(network.contains(&IpAddr::from_str("10.37.132.6").unwrap()));
+        assert!(!network.contains(&IpAddr::from_str("10.37.133.6").unwrap()));
+        assert!(!network.contains(&IpAddr::from_str("2001:db8::1").unwrap()));
+
+        let host = Cidr::from_str("2001:db8::1").unwrap();
+        assert!(host.contains(&IpAddr::from_str("2001:db8::1").unwrap()));
+        assert!(!host.contains(&IpAddr::from_str("2001:db8::2").unwrap()));
+
+        assert!(Cidr::from_str("0.0.0.0/0")
+            .unwrap()
+            .contains(&IpAddr::from_str("8.8.8.8").unwrap()));
+    }
+
+    #[test]
+    fn invalid_cidr() {
+        assert!(Cidr::from_str("10.0.0.0/33").is_err());
+        assert!(Cidr::from_str("10.0.0/8").is_err());
+    }
+
+    #[test]
+    fn default_scope() {
+        let scope = Scope::default_lab();
+
+        assert!(scope.check_ipv4(&Ipv4Addr::new(10, 37, 132, 6)).is_ok());
+        assert!(scope.check_ipv4(&Ipv4Addr::new(192, 0, 2, 1)).is_ok());
+        assert!(scope.check_ipv4(&Ipv4Addr::new(8, 8, 8, 8)).is_err());
+        // a.root-servers.net
+        assert!(scope.check_ipv4(&Ipv4Addr::new(198, 41, 0, 4)).is_err());
+        assert!(scope.check_domain("example.com").is_ok());
+    }
+
+    #[test]
+    fn parse_scope_file() {
+        let contents = "# lab network\n10.37.132.0/24\n\n192.0.2.53 # lab root\nexample.com.\n";
+        let scope = Scope::parse(contents, "scope.conf").unwrap();
+
+        assert!(scope.check_ipv4(&Ipv4Addr::new(10, 37, 132, 7)).is_ok());
+        assert!(scope.check_ipv4(&Ipv4Addr::new(192, 0, 2, 53)).is_ok());
+        assert!(scope.check_ipv4(&Ipv4Addr::new(192, 0, 2, 54)).is_err());
+        assert!(scope.check_ipv4(&Ipv4Addr::new(192, 168, 1, 1)).is_err());
+        assert!(scope.check_domain("www.Example.com").is_ok());
+        assert!(scope.check_domain("example.net").is_err());
+    }
+
+    #[test]
+    fn parse_invalid_scope_file() {
+        assert!(Scope::parse("10.0.0.0/8\nnot a domain!\n", "scope.conf").is_err());
+    }
+}
@@ -0,0 +1,727 @@
+use crate::dns;
+use crate::dns::cookie::{Cookie, CookieSecret, BADCOOKIE};
+use crate::dns::dnssec;
+use crate::dns::hostname::{in_zone, normalize_name};
+use crate::dns::message::Message;
+use crate::zone::Zone;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::mpsc::Sender;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FORMERR: u8 = 1;
+const NXDOMAIN: u8 = 3;
+const NOTIMP: u8 = 4;
+const REFUSED: u8 = 5;
+/// Longest chain of CNAME records followed within the zone when answering
+const MAX_CNAME_CHAIN: usize = 8;
+/// Largest query the server reads, which is the most EDNS lets a client send over UDP
+const MAX_QUERY_SIZE: usize = 65535;
+
+/// Source port and TXID of a query the server received, e.g. an upstream query from a resolver
+#[derive(PartialEq, Debug, Clone)]
+pub struct ReceivedQuery {
+    pub source: SocketAddr,
+    pub id: u16,
+    pub qname: String,
+}
+
+/// Authoritative server for a single zone, e.g. to stand in for the real nameserver of the target
+/// domain in a lab
+///
+/// Answers are built from the zone alone: names in the zone are answered authoritatively, names
+/// under a delegation get a referral with glue, and names outside the zone are refused. Negative
+/// answers carry the SOA record of the zone, if it has one, so that they can be cached. Queries
+/// carrying a DNS cookie get a server cookie minted with the secret back. For a signed zone,
+/// queries with the DO bit set also get the RRSIG records covering the answer, and the NSEC or
+/// NSEC3 records proving a name or type does not exist.
+pub struct Server {
+    zone: Zone,
+    cookie_secret: CookieSecret,
+    /// Refuse queries without a cookie and answer BADCOOKIE to those without a valid server cookie,
+    /// so that only clients that can receive responses at their address get answers
+    pub require_cookies: bool,
+    /// Every query received is sent here, so that the source ports and TXIDs a resolver picks can
+    /// be studied
+    pub observer: Option<Sender<ReceivedQuery>>,
+}
+
+impl Server {
+    pub fn new(zone: Zone, cookie_secret: CookieSecret) -> Server {
+        return Server {
+            zone,
+            cookie_secret,
+            require_cookies: false,
+            observer: None,
+        };
+    }
+
+    /// Answers queries arriving on the socket until receiving fails
+    pub fn serve(&self, socket: &UdpSocket) -> Result<(), String> {
+        let mut buffer = vec![0; MAX_QUERY_SIZE];
+        loop {
+            let (size, peer) = match socket.recv_from(&mut buffer) {
+                Err(e) => return Err(e.to_string()),
+                Ok(received) => received,
+            };
+            let query = match Message::parse(&buffer[..size]) {
+                Err(e) => {
+                    eprintln!("Ignoring malformed query from {}: {}", peer, e);
+                    continue;
+                }
+                Ok(query) if query.header.qr => continue,
+                Ok(query) => query,
+            };
+
+            if let (Some(observer), Some(question)) = (&self.observer, query.questions.first()) {
+                let received = ReceivedQuery {
+                    source: peer,
+                    id: query.header.id,
+                    qname: question.qname.to_string(),
+                };
+                match observer.send(received) {
+                    Err(e) => eprintln!("Could not record the query from {}: {}", peer, e),
+                    _ => (),
+                };
+            }
+
+            let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+                Err(e) => return Err(e.to_string()),
+                Ok(d) => d.as_secs() as u32,
+            };
+            // a query the server cannot answer is dropped so that it cannot stop the server
+            let response = match self.respond(&query, &peer.ip(), now) {
+                Err(e) => {
+                    eprintln!("Could not answer the query from {}: {}", peer, e);
+                    continue;
+                }
+                Ok(response) => response,
+            };
+            match query.questions.first() {
+                None => println!("{} sent a query without a question", peer),
+                Some(question) => println!(
+                    "{} asked for {} {}, rcode {}",
+                    peer,
+                    question.qname,
+                    question.qtype,
+                    response.rcode()
+                ),
+            };
+
+            match socket.send_to(&response.to_bytes(), peer) {
+                Err(e) => eprintln!("Could not answer {}: {}", peer, e),
+                _ => (),
+            };
+        }
+    }
+
+    /// Builds the response to a query from a client at the address, at the time given in seconds
+    /// since the Unix epoch
+    pub fn respond(&self, query: &Message, client: &IpAddr, now: u32) -> Result<Message, String> {
+        let mut response = dns::Response::new(query.clone());
+        response.authoritative_answer = false;
+        response.recursion_available = false;
+
+        // queries with an OPT record get one back, carrying a fresh server cookie if they sent a
+        // client cookie
+        if let Some(query_edns) = &query.edns {
+            let mut edns = dns::Edns::new();
+            edns.dnssec_ok = query_edns.dnssec_ok;
+            match Cookie::find(&query.edns) {
+                None => (),
+                Some(Err(_)) => response.rcode = FORMERR,
+                Some(Ok(cookie)) => {
+                    if self.require_cookies && !self.cookie_secret.verify(&cookie, client, now) {
+                        response.rcode = (BADCOOKIE & 0xf) as u8;
+                        edns.extended_rcode = (BADCOOKIE >> 4) as u8;
+                    }
+                    let server = self.cookie_secret.mint(&cookie.client, client, now);
+                    Cookie {
+                        client: cookie.client,
+                        server,
+                    }
+                    .attach(&mut edns);
+                }
+            };
+            response.edns = Some(edns);
+        }
+        if self.require_cookies && Cookie::find(&query.edns).is_none() {
+            response.rcode = REFUSED;
+        }
+        if response.rcode != 0 {
+            return response.to_message();
+        }
+
+        if query.header.opcode != dns::Opcode::QUERY {
+            response.rcode = NOTIMP;
+            return response.to_message();
+        }
+        let question = match query.questions.as_slice() {
+            [question] => question,
+            _ => {
+                response.rcode = FORMERR;
+                return response.to_message();
+            }
+        };
+        let qname = question.qname.to_string();
+        if !in_zone(&qname, &self.zone.origin) {
+            response.rcode = REFUSED;
+            return response.to_message();
+        }
+
+        self.answer(&mut response, &qname, question.qtype)?;
+        return response.to_message();
+    }
+
+    fn answer(
+        &self,
+        response: &mut dns::Response,
+        qname: &str,
+        qtype: dns::Type,
+    ) -> Result<(), String> {
+        let dnssec_ok = match &response.query.edns {
+            None => false,
+            Some(edns) => edns.dnssec_ok,
+        };
+        let mut name = normalize_name(qname);
+        for _ in 0..MAX_CNAME_CHAIN {
+            if let Some((cut, nameservers)) = self.zone.delegation(&name) {
+                // the DS records of a delegation belong to this zone rather than the child
+                if qtype != dns::Type::DS || cut != name {
+                    // a CNAME into a delegated zone is answered on its own, the client follows it
+                    if response.answers.is_empty() {
+                        self.refer(response, &cut, nameservers, dnssec_ok)?;
+                    }
+                    return Ok(());
+                }
+            }
+
+            response.authoritative_answer = true;
+            let records = self.zone.records_at(&name);
+            let matching: Vec<&dns::Record> = records
+                .iter()
+                .filter(|record| qtype == dns::Type::ANY || record.rtype() == qtype)
+                .cloned()
+                .collect();
+            if !matching.is_empty() {
+                for record in matching {
+                    response.add_answer(record.clone())?;
+                }
+                // the signatures are already among the records answering ANY
+                if dnssec_ok && qtype != dns::Type::ANY {
+                    for rrsig in self.signatures(&name, qtype) {
+                        response.add_answer(rrsig.clone())?;
+                    }
+                }
+                return Ok(());
+            }
+
+            let cname = records.iter().find_map(|record| match record {
+                dns::Record::CNAME(cname) => Some(cname),
+                _ => None,
+            });
+            match cname {
+                Some(cname) => {
+                    response.add_answer(dns::Record::CNAME(cname.clone()))?;
+                    if dnssec_ok {
+                        for rrsig in self.signatures(&name, dns::Type::CNAME) {
+                            response.add_answer(rrsig.clone())?;
+                        }
+                    }
+                    if !in_zone(&cname.cname, &self.zone.origin) {
+                        return Ok(());
+                    }
+                    name = cname.cname.clone();
+                }
+                // names that own other records or have names below them exist, so the answer is
+                // NODATA rather than NXDOMAIN
+                None => {
+                    let exists = self.zone.name_exists(&name);
+                    self.deny(response, exists, dnssec_ok)?;
+                    if dnssec_ok {
+                        self.add_proof(response, self.denial(&name, exists)?)?;
+                    }
+                    return Ok(());
+                }
+            };
+        }
+        return Ok(());
+    }
+
+    /// Makes the response an NXDOMAIN response, or a NODATA one for a name that exists, carrying the
+    /// SOA record of the zone so that resolvers can cache it
+    fn deny(
+        &self,
+        response: &mut dns::Response,
+        exists: bool,
+        dnssec_ok: bool,
+    ) -> Result<(), String> {
+        let soa = match self.zone.soa() {
+            None => {
+                if !exists {
+                    response.rcode = NXDOMAIN;
+                }
+                return Ok(());
+            }
+            Some(soa) => soa,
+        };
+        let negative = if exists {
+            dns::Response::nodata(response.query.clone(), soa)?
+        } else {
+            dns::Response::nxdomain(response.query.clone(), soa)?
+        };
+        response.rcode = negative.rcode;
+        response.authorities.extend(negative.authorities);
+
+        // the signatures of the SOA record are cached for as long as the record itself
+        if dnssec_ok {
+            for record in self.signatures(&soa.name, dns::Type::SOA) {
+                if let dns::Record::RRSIG(rrsig) = record {
+                    response.add_authority(dns::Record::RRSIG(dnssec::RRSIGRecord {
+                        ttl: soa.negative_ttl(),
+                        ..rrsig.clone()
+                    }))?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// Adds a referral to the delegated nameservers, with glue for those inside the zone
+    ///
+    /// With DNSSEC the referral carries the signed DS records of the delegation, or the proof that
+    /// it has none.
+    fn refer(
+        &self,
+        response: &mut dns::Response,
+        cut: &str,
+        nameservers: Vec<&dns::Record>,
+        dnssec_ok: bool,
+    ) -> Result<(), String> {
+        response.authoritative_answer = false;
+        for record in nameservers {
+            response.add_authority(record.clone())?;
+            if let dns::Record::NS(ns) = record {
+                for glue in self.zone.records_at(&ns.ns) {
+                    if glue.rtype() == dns::Type::A || glue.rtype() == dns::Type::AAAA {
+                        response.add_additional(glue.clone())?;
+                    }
+                }
+            }
+        }
+
+        if dnssec_ok {
+            let ds: Vec<&dns::Record> = self
+                .zone
+                .records_at(cut)
+                .into_iter()
+                .filter(|record| record.rtype() == dns::Type::DS)
+                .collect();
+            if ds.is_empty() {
+                self.add_proof(response, self.denial(cut, true)?)?;
+            } else {
+                self.add_proof(response, ds)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// RRSIG records covering the RRset of the name and type
+    fn signatures(&self, name: &str, rtype: dns::Type) -> Vec<&dns::Record> {
+        return self
+            .zone
+            .records_at(name)
+            .into_iter()
+            .filter(|record| match record {
+                dns::Record::RRSIG(rrsig) => rrsig.type_covered == rtype,
+                _ => false,
+            })
+            .collect();
+    }
+
+    /// Adds the records to the authority section along with their signatures
+    fn add_proof(
+        &self,
+        response: &mut dns::Response,
+        records: Vec<&dns::Record>,
+    ) -> Result<(), String> {
+        let mut added: Vec<(&str, dns::Type)> = Vec::new();
+        for record in records {
+            response.add_authority(record.clone())?;
+            if !added.contains(&(record.name(), record.rtype())) {
+                added.push((record.name(), record.rtype()));
+                for rrsig in self.signatures(record.name(), record.rtype()) {
+                    response.add_authority(rrsig.clone())?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /// NSEC or NSEC3 records proving that a name which exists has no records of the queried type,
+    /// or that a name does not exist and no wildcard could have matched it
+    fn denial(&self, name: &str, exists: bool) -> Result<Vec<&dns::Record>, String> {
+        let (encloser, next_closer) = self.closest_encloser(name);
+        let wildcard = match encloser {
+            "" => "*".to_string(),
+            encloser => format!("*.{}", encloser),
+        };
+        let mut proof: Vec<&dns::Record> = Vec::new();
+
+        let nsec3: Vec<(&dns::Record, &dnssec::NSEC3Record)> = self
+            .zone
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                dns::Record::NSEC3(nsec3) => Some((record, nsec3)),
+                _ => None,
+            })
+            .collect();
+        if let Some((_, params)) = nsec3.first() {
+            let hash = |name: &str| dnssec::nsec3_hash(name, &params.salt, params.iterations);
+            let matching = |hash: Vec<u8>| {
+                return nsec3
+                    .iter()
+                    .find(|(_, nsec3)| nsec3.owner_hash() == Ok(hash.clone()))
+                    .map(|(record, _)| *record);
+            };
+            let covering = |hash: Vec<u8>| {
+                return nsec3
+                    .iter()
+                    .find(|(_, nsec3)| nsec3.covers(&hash) == Ok(true))
+                    .map(|(record, _)| *record);
+            };
+            // a name that does not exist needs the closest encloser proof of section 7.2.2 of
+            // IETF RFC 5155
+            let records = if exists {
+                vec![matching(hash(name)?)]
+            } else {
+                vec![
+                    matching(hash(encloser)?),
+                    covering(hash(next_closer)?),
+                    covering(hash(&wildcard)?),
+                ]
+            };
+            for record in records.into_iter().flatten() {
+                if !proof.contains(&record) {
+                    proof.push(record);
+                }
+            }
+            return Ok(proof);
+        }
+
+        for record in &self.zone.records {
+            let nsec = match record {
+                dns::Record::NSEC(nsec) => nsec,
+                _ => continue,
+            };
+            // an empty non-terminal has no NSEC record of its own, but falls between two of them
+            let proves = if exists {
+                nsec.name == name || nsec.covers(name)
+            } else {
+                nsec.covers(name) || nsec.covers(&wildcard)
+            };
+            if proves {
+                proof.push(record);
+            }
+        }
+        return Ok(proof);
+    }
+
+    /// Closest ancestor of the name that exists in the zone, along with the name one label below
+    /// it on the way down to the name
+    fn closest_encloser<'a>(&self, name: &'a str) -> (&'a str, &'a str) {
+        let parent = |name: &'a str| name.split_once('.').map_or("", |(_, parent)| parent);
+        let mut next_closer = name;
+        let mut encloser = name;
+        while encloser != self.zone.origin && !self.zone.name_exists(encloser) {
+            next_closer = encloser;
+            encloser = parent(encloser);
+        }
+        return (encloser, next_closer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns;
+    use crate::dns::cookie::{Cookie, CookieSecret, BADCOOKIE};
+    use crate::dns::dnssec::{self, Algorithm};
+    use crate::dns::message::Message;
+    use crate::rng;
+    use crate::server::Server;
+    use crate::signer::{self, NSEC3Options, SignOptions, SigningKey};
+    use crate::zone::Zone;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7));
+
+    fn server() -> Server {
+        let zone = Zone::parse(
+            "\
+$TTL 300
+@        SOA    ns1 hostmaster 1 7200 900 1209600 60
+@        NS     ns1
+ns1      A      10.0.0.53
+www      A      10.0.0.80
+mail     CNAME  www
+sub      NS     ns.sub
+ns.sub   A      10.0.1.53
+",
+            Some("example.com"),
+        )
+        .unwrap();
+        return Server::new(zone, CookieSecret::new([7; 16]));
+    }
+
+    fn ask(server: &Server, name: &str, qtype: dns::Type) -> Message {
+        let mut query = dns::Query::new(vec![name.to_string()]);
+        query.qtype = qtype;
+        return server
+            .respond(
+                &query.to_message(&mut rng::new(Some(1))).unwrap(),
+                &CLIENT,
+                1_000_000,
+            )
+            .unwrap();
+    }
+
+    fn signed_server(nsec3: Option<NSEC3Options>) -> Server {
+        let key = SigningKey::generate("example.com", Algorithm::ED25519, 0).unwrap();
+        let mut options = SignOptions::new(900_000, 1_100_000);
+        options.nsec3 = nsec3;
+        let zone = signer::sign_zone(&server().zone, &[key], &options).unwrap();
+        return Server::new(zone, CookieSecret::new([7; 16]));
+    }
+
+    fn ask_dnssec(server: &Server, name: &str, qtype: dns::Type) -> Message {
+        let mut query = dns::Query::new(vec![name.to_string()]);
+        query.qtype = qtype;
+        let mut edns = dns::Edns::new();
+        edns.dnssec_ok = true;
+        query.edns = Some(edns);
+        return server
+            .respond(
+                &query.to_message(&mut rng::new(Some(1))).unwrap(),
+                &CLIENT,
+                1_000_000,
+            )
+            .unwrap();
+    }
+
+    /// Authority section as zone file lines, with the signatures cut short
+    fn authorities(response: &Message) -> Vec<String> {
+        return response
+            .authorities
+            .iter()
+            .map(|rr| match dns::Record::from_rr(rr).unwrap() {
+                dns::Record::RRSIG(rrsig) => {
+                    format!("{} RRSIG {}", rrsig.name, rrsig.type_covered)
+                }
+                record => record.to_string(),
+            })
+            .collect();
+    }
+
+    #[test]
+    fn authoritative_answers() {
+        let server = server();
+
+        let response = ask(&server, "www.example.com", dns::Type::A);
+        assert!(response.header.aa);
+        assert_eq!(0, response.rcode());
+        assert_eq!(1, response.answers.len());
+
+        // the CNAME is followed within the zone
+        let response = ask(&server, "MAIL.example.com", dns::Type::A);
+        assert_eq!(2, response.answers.len());
+        assert_eq!(dns::Type::CNAME, response.answers[0].rtype);
+
+        let response = ask(&server, "www.example.com", dns::Type::AAAA);
+        assert_eq!((0, 0), (response.rcode(), response.answers.len()));
+        let response = ask(&server, "missing.example.com", dns::Type::A);
+        assert!(response.header.aa);
+        assert_eq!(3, response.rcode());
+
+        let response = ask(&server, "www.other.com", dns::Type::A);
+        assert!(!response.header.aa);
+        assert_eq!(5, response.rcode());
+    }
+
+    #[test]
+    fn negative_answers_carry_soa() {
+        let server = server();
+
+        // the SOA record's TTL is lowered to its minimum field, which is what the answer is cached for
+        let response = ask(&server, "missing.example.com", dns::Type::A);
+        assert_eq!(3, response.rcode());
+        assert_eq!(
+            vec!["example.com 60 SOA ns1.example.com hostmaster.example.com 1 7200 900 1209600 60"],
+            authorities(&response)
+        );
+        assert_eq!(Some(60), response.negative_ttl());
+
+        let response = ask(&server, "www.example.com", dns::Type::AAAA);
+        assert_eq!((0, Some(60)), (response.rcode(), response.negative_ttl()));
+        // a referral is neither NXDOMAIN nor NODATA
+        let response = ask(&server, "www.sub.example.com", dns::Type::A);
+        assert_eq!(None, response.negative_ttl());
+        let response = ask(&server, "www.example.com", dns::Type::A);
+        assert_eq!(None, response.negative_ttl());
+    }
+
+    #[test]
+    fn referrals_include_glue() {
+        let response = ask(&server(), "www.sub.example.com", dns::Type::A);
+        assert!(!response.header.aa);
+        assert_eq!(0, response.answers.len());
+        assert_eq!(1, response.authorities.len());
+        assert_eq!(1, response.additionals.len());
+    }
+
+    #[test]
+    fn server_cookies_are_minted() {
+        let server = server();
+        let mut query = dns::Query::new(vec!["www.example.com".to_string()]);
+        let mut edns = dns::Edns::new();
+        Cookie {
+            client: [1; 8],
+            server: Vec::new(),
+        }
+        .attach(&mut edns);
+        query.edns = Some(edns);
+
+        let response = server
+            .respond(
+                &query.to_message(&mut rng::new(Some(1))).unwrap(),
+                &CLIENT,
+                1_000_000,
+            )
+            .unwrap();
+        let cookie = Cookie::find(&response.edns).unwrap().unwrap();
+        assert_eq!([1; 8], cookie.client);
+        assert!(CookieSecret::new([7; 16]).verify(&cookie, &CLIENT, 1_000_000));
+    }
+
+    #[test]
+    fn required_cookies() {
+        let mut server = server();
+        server.require_cookies = true;
+        assert_eq!(5, ask(&server, "www.example.com", dns::Type::A).rcode());
+
+        let mut query = dns::Query::new(vec!["www.example.com".to_string()]);
+        let mut cookie = Cookie {
+            client: [1; 8],
+            server: Vec::new(),
+        };
+        let mut edns = dns::Edns::new();
+        cookie.attach(&mut edns);
+        query.edns = Some(edns.clone());
+        let response = server
+            .respond(
+                &query.to_message(&mut rng::new(Some(1))).unwrap(),
+                &CLIENT,
+                1_000_000,
+            )
+            .unwrap();
+        assert_eq!(BADCOOKIE, response.rcode());
+        assert!(response.answers.is_empty());
+
+        // retrying with the server cookie from the BADCOOKIE response gets an answer
+        cookie = Cookie::find(&response.edns).unwrap().unwrap();
+        cookie.attach(&mut edns);
+        query.edns = Some(edns);
+        let response = server
+            .respond(
+                &query.to_message(&mut rng::new(Some(1))).unwrap(),
+                &CLIENT,
+                1_000_010,
+            )
+            .unwrap();
+        assert_eq!(0, response.rcode());
+        assert_eq!(1, response.answers.len());
+    }
+
+    #[test]
+    fn signed_answers() {
+        let server = signed_server(None);
+        let response = ask_dnssec(&server, "www.example.com", dns::Type::A);
+        let types: Vec<dns::Type> = response.answers.iter().map(|rr| rr.rtype).collect();
+        assert_eq!(vec![dns::Type::A, dns::Type::RRSIG], types);
+        // the signatures are only sent when asked for
+        assert_eq!(
+            1,
+            ask(&server, "www.example.com", dns::Type::A).answers.len()
+        );
+
+        // the name falls between mail and ns1, and the wildcard between the apex and mail
+        let response = ask_dnssec(&server, "missing.example.com", dns::Type::A);
+        assert_eq!(3, response.rcode());
+        assert_eq!(
+            vec![
+                "example.com 60 SOA ns1.example.com hostmaster.example.com 1 7200 900 1209600 60",
+                "example.com RRSIG SOA",
+                "example.com 60 NSEC mail.example.com NS SOA RRSIG NSEC DNSKEY",
+                "example.com RRSIG NSEC",
+                "mail.example.com 60 NSEC ns1.example.com CNAME RRSIG NSEC",
+                "mail.example.com RRSIG NSEC",
+            ],
+            authorities(&response)
+        );
+
+        let response = ask_dnssec(&server, "www.example.com", dns::Type::AAAA);
+        assert_eq!((0, 0), (response.rcode(), response.answers.len()));
+        assert_eq!(
+            vec![
+                "example.com 60 SOA ns1.example.com hostmaster.example.com 1 7200 900 1209600 60",
+                "example.com RRSIG SOA",
+                "www.example.com 60 NSEC example.com A RRSIG NSEC",
+                "www.example.com RRSIG NSEC",
+            ],
+            authorities(&response)
+        );
+
+        // a referral to an unsigned child proves that there is no DS record
+        let response = ask_dnssec(&server, "www.sub.example.com", dns::Type::A);
+        assert_eq!(
+            vec![
+                "sub.example.com 300 NS ns.sub.example.com",
+                "sub.example.com 60 NSEC www.example.com NS RRSIG NSEC",
+                "sub.example.com RRSIG NSEC",
+            ],
+            authorities(&response)
+        );
+    }
+
+    #[test]
+    fn nsec3_denial() {
+        let server = signed_server(Some(NSEC3Options {
+            iterations: 0,
+            salt: Vec::new(),
+        }));
+        let response = ask_dnssec(&server, "a.missing.example.com", dns::Type::A);
+        assert_eq!(3, response.rcode());
+        let nsec3: Vec<dnssec::NSEC3Record> = response
+            .authorities
+            .iter()
+            .filter_map(|rr| match dns::Record::from_rr(rr).unwrap() {
+                dns::Record::NSEC3(nsec3) => Some(nsec3),
+                _ => None,
+            })
+            .collect();
+        let hash = |name: &str| dnssec::nsec3_hash(name, &[], 0).unwrap();
+
+        // the closest encloser proof: the apex exists, while the next closer name and the
+        // wildcard below the apex do not
+        let apex = hash("example.com");
+        assert!(nsec3.iter().any(|n| n.owner_hash().unwrap() == apex));
+        let next_closer = hash("missing.example.com");
+        assert!(nsec3.iter().any(|n| n.covers(&next_closer).unwrap()));
+        let wildcard = hash("*.example.com");
+        assert!(nsec3.iter().any(|n| n.covers(&wildcard).unwrap()));
+        assert_eq!(
+            2 * nsec3.len() + 2,
+            response.authorities.len(),
+            "the SOA record and each NSEC3 record come with their signatures"
+        );
+    }
+}
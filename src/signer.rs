@@ -0,0 +1,766 @@
+use crate::dns;
+use crate::dns::dnssec::{
+    self, Algorithm, DNSKEYRecord, NSEC3PARAMRecord, NSEC3Record, NSECRecord, RRSIGRecord,
+};
+use crate::dns::hostname::normalize_name;
+use crate::zone::Zone;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{self, KeyPair as _};
+use std::cmp::Ordering;
+
+/// TTL given to keys read from key files, which usually leave it out
+const KEY_FILE_TTL: u32 = 3600;
+/// Fields of a private key file that hold key material
+const PRIVATE_KEY_FIELDS: [&str; 9] = [
+    "PrivateKey",
+    "Modulus",
+    "PublicExponent",
+    "PrivateExponent",
+    "Prime1",
+    "Prime2",
+    "Exponent1",
+    "Exponent2",
+    "Coefficient",
+];
+
+/// Private key of a zone, along with the DNSKEY record publishing its public key
+pub struct SigningKey {
+    pub dnskey: DNSKEYRecord,
+    /// Key material from the private key file, kept so the key can be written back out
+    private_fields: Vec<(String, Vec<u8>)>,
+    pair: KeyPair,
+}
+
+enum KeyPair {
+    RSA(signature::RsaKeyPair),
+    ECDSA(signature::EcdsaKeyPair),
+    Ed25519(signature::Ed25519KeyPair),
+}
+
+/// Options for signing a zone
+pub struct SignOptions {
+    /// Time the signatures become valid, in seconds since the Unix epoch
+    pub inception: u32,
+    /// Time the signatures expire, in seconds since the Unix epoch
+    pub expiration: u32,
+    /// TTL of the DNSKEY and NSEC3PARAM records added to the zone, and of the NSEC or NSEC3 records
+    /// when the zone has no SOA record to take the negative caching TTL from
+    pub ttl: u32,
+    /// Parameters of an NSEC3 chain to deny the existence of names with, instead of NSEC records
+    pub nsec3: Option<NSEC3Options>,
+}
+
+/// Parameters of an NSEC3 chain, as specified in IETF RFC 5155
+pub struct NSEC3Options {
+    /// Extra rounds of hashing, which RFC 9276 recommends leaving at 0
+    pub iterations: u16,
+    pub salt: Vec<u8>,
+}
+
+impl SignOptions {
+    pub fn new(inception: u32, expiration: u32) -> SignOptions {
+        return SignOptions {
+            inception,
+            expiration,
+            ttl: 3600,
+            nsec3: None,
+        };
+    }
+}
+
+impl SigningKey {
+    /// Loads a key pair in the format written by BIND's dnssec-keygen, with the DNSKEY record in
+    /// `<path>.key` and the private key in `<path>.private`
+    ///
+    /// The path may also name either of the two files, e.g. Kexample.com.+015+12345.key
+    pub fn load(path: &str) -> Result<SigningKey, String> {
+        let base = path
+            .strip_suffix(".key")
+            .or_else(|| path.strip_suffix(".private"))
+            .unwrap_or(path);
+
+        let public = read(&format!("{}.key", base))?;
+        let zone = Zone::parse(&format!("$TTL {}\n{}", KEY_FILE_TTL, public), Some("."))?;
+        let dnskey = match zone.records.into_iter().find_map(|record| match record {
+            dns::Record::DNSKEY(dnskey) => Some(dnskey),
+            _ => None,
+        }) {
+            None => return Err(format!("{}.key has no DNSKEY record", base)),
+            Some(dnskey) => dnskey,
+        };
+
+        let mut private_fields = Vec::new();
+        for line in read(&format!("{}.private", base))?.lines() {
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) if PRIVATE_KEY_FIELDS.contains(&field.trim()) => {
+                    (field.trim(), value.trim())
+                }
+                _ => continue,
+            };
+            match data_encoding::BASE64.decode(value.as_bytes()) {
+                Err(_) => return Err(format!("{}.private has an invalid {}", base, field)),
+                Ok(decoded) => private_fields.push((field.to_string(), decoded)),
+            };
+        }
+        return SigningKey::new(dnskey, private_fields);
+    }
+
+    /// Generates a new key for the zone, a key signing key if the flags have the secure entry
+    /// point bit set
+    ///
+    /// Only ECDSAP256SHA256 and ED25519 keys can be generated.
+    pub fn generate(zone: &str, algorithm: Algorithm, flags: u16) -> Result<SigningKey, String> {
+        let rng = SystemRandom::new();
+        let (public_key, private_key) = match algorithm {
+            Algorithm::RSASHA256 => {
+                return Err(
+                    "RSASHA256 keys cannot be generated, please use ECDSAP256SHA256 or ED25519"
+                        .to_string(),
+                )
+            }
+            Algorithm::ECDSAP256SHA256 => {
+                let alg = &signature::ECDSA_P256_SHA256_FIXED_SIGNING;
+                let pkcs8 = match signature::EcdsaKeyPair::generate_pkcs8(alg, &rng) {
+                    Err(_) => return Err("Could not generate an ECDSA key".to_string()),
+                    Ok(pkcs8) => pkcs8,
+                };
+                let pair = match signature::EcdsaKeyPair::from_pkcs8(alg, pkcs8.as_ref(), &rng) {
+                    Err(e) => return Err(format!("Could not generate an ECDSA key: {}", e)),
+                    Ok(pair) => pair,
+                };
+                // the private key is the 32 byte octet string following the version 1 of the
+                // ECPrivateKey structure of IETF RFC 5915 inside the PKCS#8 document
+                let marker = [0x02, 0x01, 0x01, 0x04, 0x20];
+                let start = match pkcs8
+                    .as_ref()
+                    .windows(marker.len())
+                    .position(|window| window == marker)
+                {
+                    None => return Err("Could not generate an ECDSA key".to_string()),
+                    Some(position) => position + marker.len(),
+                };
+                (
+                    pair.public_key().as_ref()[1..].to_vec(),
+                    pkcs8.as_ref()[start..start + 32].to_vec(),
+                )
+            }
+            Algorithm::ED25519 => {
+                let mut seed = [0; 32];
+                if rng.fill(&mut seed).is_err() {
+                    return Err("Could not generate an Ed25519 key".to_string());
+                }
+                let pair = match signature::Ed25519KeyPair::from_seed_unchecked(&seed) {
+                    Err(e) => return Err(format!("Could not generate an Ed25519 key: {}", e)),
+                    Ok(pair) => pair,
+                };
+                (pair.public_key().as_ref().to_vec(), seed.to_vec())
+            }
+        };
+
+        let dnskey = DNSKEYRecord {
+            name: normalize_name(zone),
+            ttl: KEY_FILE_TTL,
+            flags: flags | dnssec::ZONE_KEY,
+            protocol: 3,
+            algorithm: algorithm as u8,
+            public_key,
+        };
+        return SigningKey::new(dnskey, vec![("PrivateKey".to_string(), private_key)]);
+    }
+
+    fn new(
+        dnskey: DNSKEYRecord,
+        private_fields: Vec<(String, Vec<u8>)>,
+    ) -> Result<SigningKey, String> {
+        let tag = dnskey.key_tag();
+        let field = |name: &str| -> Result<&[u8], String> {
+            return match private_fields.iter().find(|(field, _)| field == name) {
+                None => Err(format!("Private key {} has no {} field", tag, name)),
+                Some((_, value)) => Ok(value),
+            };
+        };
+        let rejected = |e: ring::error::KeyRejected| {
+            return format!("Private key {} does not match its DNSKEY: {}", tag, e);
+        };
+
+        let pair = match Algorithm::from_number(dnskey.algorithm) {
+            None => {
+                return Err(format!(
+                    "Key {} has unsupported algorithm {}",
+                    tag, dnskey.algorithm
+                ))
+            }
+            Some(Algorithm::RSASHA256) => {
+                let components = ring::rsa::KeyPairComponents {
+                    public_key: ring::rsa::PublicKeyComponents {
+                        n: field("Modulus")?,
+                        e: field("PublicExponent")?,
+                    },
+                    d: field("PrivateExponent")?,
+                    p: field("Prime1")?,
+                    q: field("Prime2")?,
+                    dP: field("Exponent1")?,
+                    dQ: field("Exponent2")?,
+                    qInv: field("Coefficient")?,
+                };
+                match signature::RsaKeyPair::from_components(&components) {
+                    Err(e) => return Err(rejected(e)),
+                    Ok(pair) => KeyPair::RSA(pair),
+                }
+            }
+            Some(Algorithm::ECDSAP256SHA256) => {
+                let mut point = vec![0x04];
+                point.extend(&dnskey.public_key);
+                match signature::EcdsaKeyPair::from_private_key_and_public_key(
+                    &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+                    field("PrivateKey")?,
+                    &point,
+                    &SystemRandom::new(),
+                ) {
+                    Err(e) => return Err(rejected(e)),
+                    Ok(pair) => KeyPair::ECDSA(pair),
+                }
+            }
+            Some(Algorithm::ED25519) => {
+                match signature::Ed25519KeyPair::from_seed_and_public_key(
+                    field("PrivateKey")?,
+                    &dnskey.public_key,
+                ) {
+                    Err(e) => return Err(rejected(e)),
+                    Ok(pair) => KeyPair::Ed25519(pair),
+                }
+            }
+        };
+        return Ok(SigningKey {
+            dnskey,
+            private_fields,
+            pair,
+        });
+    }
+
+    /// Writes the key pair to the directory in the format of BIND's dnssec-keygen, returning the
+    /// path of the files without their extension
+    pub fn save(&self, directory: &str) -> Result<String, String> {
+        let name = match self.dnskey.name.as_str() {
+            "" => ".".to_string(),
+            name => format!("{}.", name),
+        };
+        let algorithm = match Algorithm::from_number(self.dnskey.algorithm) {
+            None => return Err(format!("Unsupported algorithm {}", self.dnskey.algorithm)),
+            Some(algorithm) => algorithm,
+        };
+        let base = std::path::Path::new(directory)
+            .join(format!(
+                "K{}+{:03}+{:05}",
+                name,
+                self.dnskey.algorithm,
+                self.dnskey.key_tag()
+            ))
+            .to_string_lossy()
+            .to_string();
+
+        let kind = if self.dnskey.flags & dnssec::SECURE_ENTRY_POINT != 0 {
+            "key-signing"
+        } else {
+            "zone-signing"
+        };
+        let public = format!(
+            "; This is a {} key, keyid {}, for {}\n{} IN DNSKEY {}\n",
+            kind,
+            self.dnskey.key_tag(),
+            name,
+            name,
+            self.dnskey.rdata_to_string()
+        );
+        let mut private = format!(
+            "Private-key-format: v1.3\nAlgorithm: {} ({:?})\n",
+            self.dnskey.algorithm, algorithm
+        );
+        for (field, value) in &self.private_fields {
+            private.push_str(&format!(
+                "{}: {}\n",
+                field,
+                data_encoding::BASE64.encode(value)
+            ));
+        }
+
+        write(&format!("{}.key", base), &public)?;
+        write(&format!("{}.private", base), &private)?;
+        return Ok(base);
+    }
+
+    /// Whether the key only signs the DNSKEY RRset when there are zone signing keys as well
+    fn is_key_signing_key(&self) -> bool {
+        return self.dnskey.flags & dnssec::SECURE_ENTRY_POINT != 0;
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let rng = SystemRandom::new();
+        return match &self.pair {
+            KeyPair::RSA(pair) => {
+                let mut signature = vec![0; pair.public().modulus_len()];
+                match pair.sign(&signature::RSA_PKCS1_SHA256, &rng, data, &mut signature) {
+                    Err(_) => Err(format!("Could not sign with key {}", self.dnskey.key_tag())),
+                    Ok(()) => Ok(signature),
+                }
+            }
+            KeyPair::ECDSA(pair) => match pair.sign(&rng, data) {
+                Err(_) => Err(format!("Could not sign with key {}", self.dnskey.key_tag())),
+                Ok(signature) => Ok(signature.as_ref().to_vec()),
+            },
+            KeyPair::Ed25519(pair) => Ok(pair.sign(data).as_ref().to_vec()),
+        };
+    }
+}
+
+/// Signs an RRset of records sharing a name and type with the key
+pub fn sign_rrset(
+    rrset: &[&dns::Record],
+    key: &SigningKey,
+    inception: u32,
+    expiration: u32,
+) -> Result<RRSIGRecord, String> {
+    let mut rrs = Vec::new();
+    for record in rrset {
+        rrs.push(record.to_rr()?);
+    }
+    let first = match rrset.first() {
+        None => return Err("Cannot sign an empty RRset".to_string()),
+        Some(first) => first,
+    };
+    let mut rrsig = RRSIGRecord {
+        name: first.name().to_string(),
+        ttl: rrs[0].ttl,
+        type_covered: first.rtype(),
+        algorithm: key.dnskey.algorithm,
+        labels: dns::Hostname::from_string(first.name())?.label_count(),
+        original_ttl: rrs[0].ttl,
+        expiration,
+        inception,
+        key_tag: key.dnskey.key_tag(),
+        signer_name: key.dnskey.name.clone(),
+        signature: Vec::new(),
+    };
+    rrsig.signature = key.sign(&rrsig.signed_data(&rrs)?)?;
+    return Ok(rrsig);
+}
+
+/// Signs the zone with the keys, returning the zone with DNSKEY, RRSIG and NSEC or NSEC3 records
+/// added
+///
+/// Signatures and denial of existence records already in the zone are replaced. Key signing keys
+/// only sign the DNSKEY RRset unless no zone signing keys are given, and at delegations only the
+/// DS records are signed, since the NS records and glue belong to the child zone. The records come
+/// out in the canonical order of their owner names.
+pub fn sign_zone(zone: &Zone, keys: &[SigningKey], options: &SignOptions) -> Result<Zone, String> {
+    if keys.is_empty() {
+        return Err("At least one key is needed to sign the zone".to_string());
+    }
+    for key in keys {
+        if key.dnskey.name != zone.origin {
+            return Err(format!(
+                "Key {} belongs to {} rather than {}",
+                key.dnskey.key_tag(),
+                dnssec::presentation_name(&key.dnskey.name),
+                dnssec::presentation_name(&zone.origin)
+            ));
+        }
+    }
+
+    let mut records: Vec<dns::Record> = zone
+        .records
+        .iter()
+        .filter(|record| match record.rtype() {
+            dns::Type::RRSIG | dns::Type::NSEC | dns::Type::NSEC3 | dns::Type::NSEC3PARAM => false,
+            _ => true,
+        })
+        .cloned()
+        .collect();
+    for key in keys {
+        let published = records.iter().any(|record| match record {
+            dns::Record::DNSKEY(dnskey) => dnskey.rdata() == key.dnskey.rdata(),
+            _ => false,
+        });
+        if !published {
+            records.push(dns::Record::DNSKEY(DNSKEYRecord {
+                ttl: options.ttl,
+                ..key.dnskey.clone()
+            }));
+        }
+    }
+    if let Some(nsec3) = &options.nsec3 {
+        records.push(dns::Record::NSEC3PARAM(NSEC3PARAMRecord {
+            name: zone.origin.clone(),
+            ttl: options.ttl,
+            hash_algorithm: dnssec::NSEC3_SHA1,
+            flags: 0,
+            iterations: nsec3.iterations,
+            salt: nsec3.salt.clone(),
+        }));
+    }
+    let unsigned = Zone {
+        origin: zone.origin.clone(),
+        records,
+    };
+
+    // glue below a delegation is neither signed nor part of the chain
+    let mut owners: Vec<&str> = unsigned
+        .records
+        .iter()
+        .map(|record| record.name())
+        .filter(|name| match unsigned.delegation(name) {
+            None => true,
+            Some((cut, _)) => cut == *name,
+        })
+        .collect();
+    owners.sort_by(|a, b| dnssec::compare_names(a, b));
+    owners.dedup();
+
+    let mut signed = unsigned.records.clone();
+    let sign = |rrset: &[&dns::Record], signed: &mut Vec<dns::Record>| {
+        for key in signers(keys, rrset[0].rtype()) {
+            let rrsig = sign_rrset(rrset, key, options.inception, options.expiration)?;
+            signed.push(dns::Record::RRSIG(rrsig));
+        }
+        return Ok::<(), String>(());
+    };
+    for owner in &owners {
+        let delegation = unsigned.delegation(owner).is_some();
+        for rrset in rrsets(&unsigned.records, owner) {
+            if !delegation || rrset[0].rtype() == dns::Type::DS {
+                sign(&rrset, &mut signed)?;
+            }
+        }
+    }
+
+    // the denial records are cached as long as the negative answers they prove, see RFC 9077
+    let denial_ttl = match unsigned.soa() {
+        None => options.ttl,
+        Some(soa) => soa.negative_ttl(),
+    };
+    let chain = match &options.nsec3 {
+        None => nsec_chain(&unsigned, &owners, denial_ttl),
+        Some(nsec3) => nsec3_chain(&unsigned, &owners, nsec3, denial_ttl)?,
+    };
+    for record in chain {
+        sign(&[&record], &mut signed)?;
+        signed.push(record);
+    }
+
+    signed.sort_by(zone_order);
+    return Ok(Zone {
+        origin: zone.origin.clone(),
+        records: signed,
+    });
+}
+
+/// Keys that sign RRsets of the type
+fn signers(keys: &[SigningKey], rtype: dns::Type) -> Vec<&SigningKey> {
+    let zone_signing_keys: Vec<&SigningKey> = keys
+        .iter()
+        .filter(|key| !key.is_key_signing_key())
+        .collect();
+    if rtype == dns::Type::DNSKEY || zone_signing_keys.is_empty() {
+        return keys.iter().collect();
+    }
+    return zone_signing_keys;
+}
+
+/// RRsets owned by the name, ordered by type
+fn rrsets<'a>(records: &'a [dns::Record], owner: &str) -> Vec<Vec<&'a dns::Record>> {
+    let mut types: Vec<dns::Type> = records
+        .iter()
+        .filter(|record| record.name() == owner)
+        .map(|record| record.rtype())
+        .collect();
+    types.sort_by_key(|rtype| rtype.to_u16());
+    types.dedup();
+    return types
+        .into_iter()
+        .map(|rtype| {
+            return records
+                .iter()
+                .filter(|record| record.name() == owner && record.rtype() == rtype)
+                .collect();
+        })
+        .collect();
+}
+
+/// Types listed in the NSEC or NSEC3 record of the owner, apart from the record itself
+///
+/// At a delegation only the NS and DS records belong to the zone, and only the DS records are
+/// signed.
+fn owned_types(zone: &Zone, owner: &str) -> Vec<dns::Type> {
+    let delegation = zone.delegation(owner).is_some();
+    let mut types: Vec<dns::Type> = rrsets(&zone.records, owner)
+        .iter()
+        .map(|rrset| rrset[0].rtype())
+        .filter(|rtype| !delegation || *rtype == dns::Type::NS || *rtype == dns::Type::DS)
+        .collect();
+    if !delegation || types.contains(&dns::Type::DS) {
+        types.push(dns::Type::RRSIG);
+    }
+    return types;
+}
+
+fn nsec_chain(zone: &Zone, owners: &[&str], ttl: u32) -> Vec<dns::Record> {
+    let mut chain = Vec::new();
+    for (i, owner) in owners.iter().enumerate() {
+        let mut types = owned_types(zone, owner);
+        // the NSEC record itself is always signed
+        if !types.contains(&dns::Type::RRSIG) {
+            types.push(dns::Type::RRSIG);
+        }
+        types.push(dns::Type::NSEC);
+        types.sort_by_key(|rtype| rtype.to_u16());
+        chain.push(dns::Record::NSEC(NSECRecord {
+            name: owner.to_string(),
+            ttl,
+            next_name: owners[(i + 1) % owners.len()].to_string(),
+            types,
+        }));
+    }
+    return chain;
+}
+
+fn nsec3_chain(
+    zone: &Zone,
+    owners: &[&str],
+    options: &NSEC3Options,
+    ttl: u32,
+) -> Result<Vec<dns::Record>, String> {
+    let mut names: Vec<(String, Vec<dns::Type>)> = Vec::new();
+    for owner in owners {
+        names.push((owner.to_string(), owned_types(zone, owner)));
+    }
+    // empty non-terminals between the owners and the apex get NSEC3 records too, as specified in
+    // section 7.1 of IETF RFC 5155
+    for owner in owners {
+        let mut name = *owner;
+        while name != zone.origin && !name.is_empty() {
+            name = name.split_once('.').map_or("", |(_, parent)| parent);
+            if !owners.contains(&name) && !names.iter().any(|(existing, _)| existing == name) {
+                names.push((name.to_string(), Vec::new()));
+            }
+        }
+    }
+
+    let mut hashed = Vec::new();
+    for (name, types) in names {
+        hashed.push((
+            dnssec::nsec3_hash(&name, &options.salt, options.iterations)?,
+            types,
+        ));
+    }
+    hashed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut chain = Vec::new();
+    for (i, (hash, types)) in hashed.iter().enumerate() {
+        chain.push(dns::Record::NSEC3(NSEC3Record {
+            name: dnssec::nsec3_owner(hash, &zone.origin),
+            ttl,
+            hash_algorithm: dnssec::NSEC3_SHA1,
+            flags: 0,
+            iterations: options.iterations,
+            salt: options.salt.clone(),
+            next_hashed_owner: hashed[(i + 1) % hashed.len()].0.clone(),
+            types: types.clone(),
+        }));
+    }
+    return Ok(chain);
+}
+
+/// Orders records by owner name and then by type, with RRSIG records after the RRset they cover
+fn zone_order(a: &dns::Record, b: &dns::Record) -> Ordering {
+    let position = |record: &dns::Record| match record {
+        dns::Record::RRSIG(rrsig) => (rrsig.type_covered.to_u16(), 1),
+        _ => (record.rtype().to_u16(), 0),
+    };
+    return dnssec::compare_names(a.name(), b.name()).then(position(a).cmp(&position(b)));
+}
+
+fn read(path: &str) -> Result<String, String> {
+    return match std::fs::read_to_string(path) {
+        Err(e) => Err(format!("Could not read key file {}: {}", path, e)),
+        Ok(contents) => Ok(contents),
+    };
+}
+
+fn write(path: &str, contents: &str) -> Result<(), String> {
+    return match std::fs::write(path, contents) {
+        Err(e) => Err(format!("Could not write key file {}: {}", path, e)),
+        Ok(()) => Ok(()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns;
+    use crate::dns::dnssec::{self, Algorithm, SECURE_ENTRY_POINT};
+    use crate::signer::{sign_zone, NSEC3Options, SignOptions, SigningKey};
+    use crate::validator::{verify_rrset, RRset};
+    use crate::zone::Zone;
+
+    const NOW: u32 = 1_700_000_000;
+    const ZONE: &str = "\
+$ORIGIN example.com.
+$TTL 3600
+@          NS     ns1
+ns1        A      10.0.0.53
+www        A      10.0.0.80
+           AAAA   fd00::80
+a.b.c      A      10.0.0.81
+sub        NS     ns.sub
+ns.sub     A      10.0.1.53
+";
+
+    fn signed(keys: &[SigningKey], nsec3: Option<NSEC3Options>) -> Zone {
+        let zone = Zone::parse(ZONE, None).unwrap();
+        let mut options = SignOptions::new(NOW - 3600, NOW + 86400);
+        options.nsec3 = nsec3;
+        return sign_zone(&zone, keys, &options).unwrap();
+    }
+
+    fn rrsets(zone: &Zone) -> Vec<RRset> {
+        let rrs: Vec<dns::ResourceRecord> = zone
+            .records
+            .iter()
+            .map(|record| record.to_rr().unwrap())
+            .collect();
+        return RRset::group(&rrs);
+    }
+
+    #[test]
+    fn signed_rrsets_verify() {
+        let ksk =
+            SigningKey::generate("example.com", Algorithm::ED25519, SECURE_ENTRY_POINT).unwrap();
+        let zsk = SigningKey::generate("example.com.", Algorithm::ECDSAP256SHA256, 0).unwrap();
+        let zone = signed(&[ksk, zsk], None);
+        let keys: Vec<dnssec::DNSKEYRecord> = zone
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                dns::Record::DNSKEY(key) => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(2, keys.len());
+
+        for rrset in rrsets(&zone) {
+            // the NS records of the delegation and the glue belong to the child zone
+            if rrset.name == "sub.example.com" && rrset.rtype == dns::Type::NS
+                || rrset.name == "ns.sub.example.com"
+            {
+                assert!(rrset.rrsigs.is_empty());
+                continue;
+            }
+            assert_eq!(Ok(()), verify_rrset(&rrset, &keys, NOW), "{:?}", rrset);
+            // every key signs the DNSKEY RRset, and only the zone signing key signs the rest
+            if rrset.rtype == dns::Type::DNSKEY {
+                assert_eq!(2, rrset.rrsigs.len());
+                continue;
+            }
+            for rrsig in &rrset.rrsigs {
+                let signer = keys.iter().find(|key| key.key_tag() == rrsig.key_tag);
+                assert_eq!(256, signer.unwrap().flags);
+            }
+        }
+    }
+
+    #[test]
+    fn nsec_chain() {
+        let key = SigningKey::generate("example.com", Algorithm::ED25519, 0).unwrap();
+        let zone = signed(&[key], None);
+        let chain: Vec<String> = zone
+            .records
+            .iter()
+            .filter(|record| record.rtype() == dns::Type::NSEC)
+            .map(|record| record.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "example.com 3600 NSEC a.b.c.example.com NS RRSIG NSEC DNSKEY",
+                "a.b.c.example.com 3600 NSEC ns1.example.com A RRSIG NSEC",
+                "ns1.example.com 3600 NSEC sub.example.com A RRSIG NSEC",
+                "sub.example.com 3600 NSEC www.example.com NS RRSIG NSEC",
+                "www.example.com 3600 NSEC example.com A AAAA RRSIG NSEC",
+            ],
+            chain
+        );
+
+        // the signed zone can be written out and loaded again
+        let reloaded = Zone::parse(&zone.to_string(), None).unwrap();
+        assert_eq!(zone, reloaded);
+    }
+
+    #[test]
+    fn nsec3_chain() {
+        let key = SigningKey::generate("example.com", Algorithm::ED25519, 0).unwrap();
+        let salt = vec![0xaa, 0xbb];
+        let zone = signed(
+            &[key],
+            Some(NSEC3Options {
+                iterations: 1,
+                salt: salt.clone(),
+            }),
+        );
+        let chain: Vec<&dnssec::NSEC3Record> = zone
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                dns::Record::NSEC3(nsec3) => Some(nsec3),
+                _ => None,
+            })
+            .collect();
+        // the apex, ns1, www, sub, a.b.c and the empty non-terminals b.c and c
+        assert_eq!(7, chain.len());
+        for (i, nsec3) in chain.iter().enumerate() {
+            let next = chain[(i + 1) % chain.len()];
+            assert_eq!(next.owner_hash().unwrap(), nsec3.next_hashed_owner);
+        }
+
+        let hash = dnssec::nsec3_hash("b.c.example.com", &salt, 1).unwrap();
+        let empty = chain
+            .iter()
+            .find(|nsec3| nsec3.owner_hash().unwrap() == hash)
+            .unwrap();
+        assert!(empty.types.is_empty());
+        let hash = dnssec::nsec3_hash("sub.example.com", &salt, 1).unwrap();
+        let delegation = chain
+            .iter()
+            .find(|nsec3| nsec3.owner_hash().unwrap() == hash)
+            .unwrap();
+        assert_eq!(vec![dns::Type::NS], delegation.types);
+        assert!(!zone
+            .records
+            .iter()
+            .any(|record| record.rtype() == dns::Type::NSEC));
+        assert_eq!(
+            1,
+            zone.records_at("example.com")
+                .iter()
+                .filter(|record| record.rtype() == dns::Type::NSEC3PARAM)
+                .count()
+        );
+    }
+
+    #[test]
+    fn key_files() {
+        let directory = std::env::temp_dir().join(format!("sign-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let key = SigningKey::generate("example.com", Algorithm::ECDSAP256SHA256, 1).unwrap();
+        let base = key.save(&directory.to_string_lossy()).unwrap();
+        assert!(base.ends_with(&format!("Kexample.com.+013+{:05}", key.dnskey.key_tag())));
+
+        let loaded = SigningKey::load(&format!("{}.private", base)).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(key.dnskey, loaded.dnskey);
+        let signature = loaded.sign(b"data").unwrap();
+        assert_eq!(Ok(()), key.dnskey.verify(b"data", &signature));
+
+        // keys only sign their own zone
+        let zone = Zone::parse(ZONE, None).unwrap();
+        let other = SigningKey::generate("other.com", Algorithm::ED25519, 0).unwrap();
+        assert!(sign_zone(&zone, &[other], &SignOptions::new(NOW, NOW + 1)).is_err());
+    }
+}
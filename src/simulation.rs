@@ -0,0 +1,491 @@
+use crate::dns;
+use crate::dns::cookie::Cookie;
+use crate::dns::hostname::{normalize_name, same_name};
+use crate::dns::message::Message;
+use crate::payload::PayloadOptions;
+use crate::subdomain::{SubdomainGenerator, SubdomainOptions};
+use rand::{Rng, RngCore};
+use std::collections::HashMap;
+
+/// Validation a simulated resolver applies to responses before accepting them
+#[derive(PartialEq, Debug, Clone)]
+pub struct ResolverModel {
+    /// Lowest source port the resolver sends upstream queries from
+    pub first_port: u16,
+    /// Number of source ports the resolver picks from at random, 1 for a fixed port
+    pub ports: u16,
+    /// Whether upstream queries use 0x20 encoding and responses must echo the case of the question
+    pub case_randomization: bool,
+    /// Whether upstream queries carry a DNS cookie and responses must echo the client cookie
+    pub cookies: bool,
+}
+
+impl ResolverModel {
+    /// Resolver that queries from the fixed port 33333 like the SEED lab resolver
+    pub fn new() -> ResolverModel {
+        return ResolverModel {
+            first_port: 33333,
+            ports: 1,
+            case_randomization: false,
+            cookies: false,
+        };
+    }
+}
+
+/// Query the simulated resolver has sent upstream and is waiting on an answer for
+#[derive(PartialEq, Debug, Clone)]
+pub struct PendingQuery {
+    pub message: Message,
+    pub port: u16,
+}
+
+/// What the simulated resolver did with a response
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+pub enum Verdict {
+    /// the response was accepted and its records cached
+    Accepted,
+    /// no query for the name is outstanding, e.g. because the real answer already arrived
+    Unsolicited,
+    WrongPort,
+    WrongId,
+    /// the question was not echoed exactly, which 0x20 encoding relies on
+    WrongCase,
+    /// the response did not echo the client cookie of the query
+    WrongCookie,
+}
+
+/// Model of a recursive resolver's handling of upstream queries and responses
+///
+/// No packets are sent. The resolver only tracks its outstanding queries, caches the nameservers
+/// from the authority section of the first matching response, which is all a Kaminsky attack has
+/// to get past, and negatively caches the names that matching NXDOMAIN and NODATA responses deny.
+/// Cached entries never expire, since a simulation covers less time than typical TTLs.
+pub struct SimulatedResolver {
+    model: ResolverModel,
+    /// Outstanding queries by lowercase name
+    pending: HashMap<String, PendingQuery>,
+    /// Cached nameservers by lowercase zone
+    nameservers: HashMap<String, Vec<String>>,
+    /// TTLs of cached negative answers by lowercase name
+    negative: HashMap<String, u32>,
+}
+
+impl SimulatedResolver {
+    pub fn new(model: ResolverModel) -> SimulatedResolver {
+        return SimulatedResolver {
+            model,
+            pending: HashMap::new(),
+            nameservers: HashMap::new(),
+            negative: HashMap::new(),
+        };
+    }
+
+    /// Sends an upstream query for the name, picking a random TXID and source port
+    pub fn send_query(
+        &mut self,
+        qname: &str,
+        rng: &mut dyn RngCore,
+    ) -> Result<PendingQuery, String> {
+        let mut query = dns::Query::new(vec![qname.to_string()]);
+        query.recursion_desired = false;
+        query.case_randomization = self.model.case_randomization;
+
+        if self.model.cookies {
+            let mut cookie = Cookie {
+                client: [0; 8],
+                server: Vec::new(),
+            };
+            rng.fill_bytes(&mut cookie.client);
+            let mut edns = dns::Edns::new();
+            cookie.attach(&mut edns);
+            query.edns = Some(edns);
+        }
+
+        let port_offset = rng.gen_range(0, self.model.ports.max(1) as u32) as u16;
+        let pending = PendingQuery {
+            message: query.to_message(rng)?,
+            port: self.model.first_port.wrapping_add(port_offset),
+        };
+        self.pending
+            .insert(qname.to_ascii_lowercase(), pending.clone());
+        return Ok(pending);
+    }
+
+    /// Receives a response on the port, accepting it if it matches an outstanding query
+    pub fn receive(&mut self, port: u16, response: &Message) -> Verdict {
+        let qname = match response.questions.first() {
+            None => return Verdict::Unsolicited,
+            Some(question) => question.qname.to_string().to_ascii_lowercase(),
+        };
+        let pending = match self.pending.get(&qname) {
+            None => return Verdict::Unsolicited,
+            Some(p) => p,
+        };
+
+        if port != pending.port {
+            return Verdict::WrongPort;
+        }
+        if response.header.id != pending.message.header.id {
+            return Verdict::WrongId;
+        }
+        if self.model.case_randomization && pending.message.check_echo(response).is_err() {
+            return Verdict::WrongCase;
+        }
+        if self.model.cookies && !echoes_cookie(&pending.message, response) {
+            return Verdict::WrongCookie;
+        }
+
+        self.pending.remove(&qname);
+        // a TTL of 0 allows the answer to be used but not cached, as per RFC 2308
+        match response.negative_ttl() {
+            Some(ttl) if ttl > 0 => {
+                self.negative.insert(qname, ttl);
+            }
+            _ => (),
+        };
+        for rr in &response.authorities {
+            if let Ok(dns::Record::NS(ns)) = dns::Record::from_rr(rr) {
+                let zone = normalize_name(&ns.name);
+                let nameservers = self.nameservers.entry(zone).or_insert_with(Vec::new);
+                if !nameservers.contains(&ns.ns) {
+                    nameservers.push(ns.ns);
+                }
+            }
+        }
+        return Verdict::Accepted;
+    }
+
+    /// TTL of the negative answer cached for the name, if any
+    ///
+    /// The resolver answers queries for such names from its cache without asking upstream, so
+    /// there is no query for an attacker to race.
+    pub fn negative_ttl(&self, qname: &str) -> Option<u32> {
+        return self.negative.get(&normalize_name(qname)).cloned();
+    }
+
+    /// Nameservers cached for the zone
+    pub fn nameservers(&self, zone: &str) -> &[String] {
+        return match self.nameservers.get(&normalize_name(zone)) {
+            None => &[],
+            Some(nameservers) => nameservers,
+        };
+    }
+}
+
+/// Whether the response carries the client cookie the query was sent with
+fn echoes_cookie(query: &Message, response: &Message) -> bool {
+    return match (Cookie::find(&query.edns), Cookie::find(&response.edns)) {
+        (Some(Ok(sent)), Some(Ok(received))) => sent.client == received.client,
+        _ => false,
+    };
+}
+
+/// Options for simulating rounds of a Kaminsky attack against a simulated resolver
+pub struct SimulationOptions {
+    pub model: ResolverModel,
+    pub target_domain: String,
+    pub attacker_ns: String,
+    /// Source port the attacker sends spoofed responses to
+    pub target_port: u16,
+    /// Spoofed responses that arrive before the real answer in each round
+    pub responses_per_round: u32,
+    pub payload: PayloadOptions,
+    pub subdomains: SubdomainOptions,
+    /// Minimum field and TTL of the target zone's SOA record, carried by the real NXDOMAIN answers
+    pub negative_ttl: u32,
+}
+
+/// Outcome of one simulated round
+#[derive(PartialEq, Debug)]
+pub struct RoundOutcome {
+    pub poisoned: bool,
+    /// Whether the resolver answered the name from its negative cache, so that no query was sent
+    /// and there was nothing to race
+    pub negatively_cached: bool,
+    /// Bits of the query the attacker had to guess: the TXID, the port, the case of each letter,
+    /// and the client cookie
+    pub entropy_bits: f64,
+    /// Number of spoofed responses the resolver turned away, by the check they failed
+    pub rejected: HashMap<Verdict, u32>,
+}
+
+/// Simulates one round of the attack as `kaminsky::attack` runs it
+///
+/// The resolver queries the next subdomain from the generator, which lasts for the whole attack
+/// like the one of `kaminsky::attack`, the attacker sends its spoofed responses with TXIDs
+/// counting up from 0 to the target port, and then the real answer arrives, which is NXDOMAIN since
+/// the subdomain does not exist. The round is won if a spoofed response gets the attacker
+/// nameserver cached for the target domain. Names the resolver has already cached as not existing
+/// are answered without a query, so the round is lost without a race.
+pub fn simulate_round(
+    options: &SimulationOptions,
+    resolver: &mut SimulatedResolver,
+    subdomains: &mut dyn SubdomainGenerator,
+    rng: &mut dyn RngCore,
+) -> Result<RoundOutcome, String> {
+    let qname = format!("{}.{}", subdomains.next_name(rng), options.target_domain);
+    let entropy_bits = entropy_bits(&options.model, &qname);
+
+    if resolver.negative_ttl(&qname).is_some() {
+        return Ok(RoundOutcome {
+            poisoned: false,
+            negatively_cached: true,
+            entropy_bits,
+            rejected: HashMap::new(),
+        });
+    }
+    let pending = resolver.send_query(&qname, rng)?;
+
+    // the attacker only knows the name it triggered the query for, not how the resolver encoded it
+    let attacker_query = dns::Query::new(vec![qname.clone()]).to_message(rng)?;
+    let mut spoofed = options
+        .payload
+        .build(
+            attacker_query,
+            &qname,
+            &options.target_domain,
+            &options.attacker_ns,
+        )?
+        .to_message()?;
+    let mut accepted = false;
+    let mut rejected = HashMap::new();
+    for id in 0..options.responses_per_round.min(u16::max_value() as u32 + 1) {
+        spoofed.header.id = id as u16;
+        match resolver.receive(options.target_port, &spoofed) {
+            Verdict::Accepted => {
+                accepted = true;
+                break;
+            }
+            verdict => *rejected.entry(verdict).or_insert(0) += 1,
+        };
+    }
+
+    // the real answer is a response to the resolver's own query and echoes its cookie
+    let mut real = dns::Response::nxdomain(pending.message.clone(), &soa(options))?;
+    real.edns = pending.message.edns.clone();
+    let real = real.to_message()?;
+    resolver.receive(pending.port, &real);
+
+    let poisoned = accepted
+        && resolver
+            .nameservers(&options.target_domain)
+            .iter()
+            .any(|ns| same_name(ns, &options.attacker_ns));
+    return Ok(RoundOutcome {
+        poisoned,
+        negatively_cached: false,
+        entropy_bits,
+        rejected,
+    });
+}
+
+/// SOA record of the target zone as served by its real nameservers
+fn soa(options: &SimulationOptions) -> dns::SOARecord {
+    return dns::SOARecord {
+        name: options.target_domain.clone(),
+        ttl: options.negative_ttl,
+        mname: format!("ns1.{}", options.target_domain),
+        rname: format!("hostmaster.{}", options.target_domain),
+        serial: 1,
+        refresh: 7200,
+        retry: 900,
+        expire: 1209600,
+        minimum: options.negative_ttl,
+    };
+}
+
+/// Bits an off-path attacker has to guess to match the resolver's query for the name
+pub fn entropy_bits(model: &ResolverModel, qname: &str) -> f64 {
+    let mut bits = 16.0 + (model.ports.max(1) as f64).log2();
+    if model.case_randomization {
+        bits += qname.chars().filter(|c| c.is_ascii_alphabetic()).count() as f64;
+    }
+    if model.cookies {
+        bits += 64.0;
+    }
+    return bits;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns;
+    use crate::payload::{PayloadOptions, PayloadStrategy};
+    use crate::rng;
+    use crate::simulation::{
+        entropy_bits, simulate_round, ResolverModel, SimulatedResolver, SimulationOptions, Verdict,
+    };
+    use crate::subdomain::{SubdomainGenerator, SubdomainKind, SubdomainOptions};
+    use rand::RngCore;
+
+    fn options(model: ResolverModel, responses_per_round: u32) -> SimulationOptions {
+        return SimulationOptions {
+            model,
+            target_domain: "example.com".to_string(),
+            attacker_ns: "ns.attacker.lab".to_string(),
+            target_port: 33333,
+            responses_per_round,
+            payload: PayloadOptions::new(PayloadStrategy::Answer),
+            subdomains: SubdomainOptions::new(SubdomainKind::Lowercase),
+            negative_ttl: 3600,
+        };
+    }
+
+    #[test]
+    fn resolver_checks_port_id_and_case() {
+        let mut model = ResolverModel::new();
+        model.case_randomization = true;
+        let mut resolver = SimulatedResolver::new(model);
+        let mut rng = rng::new(Some(3));
+        let pending = resolver
+            .send_query("abcdefg.example.com", &mut rng)
+            .unwrap();
+
+        let mut response = dns::Response::new(pending.message.clone())
+            .to_message()
+            .unwrap();
+        assert_eq!(Verdict::WrongPort, resolver.receive(53, &response));
+
+        response.header.id = pending.message.header.id.wrapping_add(1);
+        assert_eq!(Verdict::WrongId, resolver.receive(pending.port, &response));
+
+        let lowercase = dns::Query::new(vec!["abcdefg.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        let mut guessed = dns::Response::new(lowercase).to_message().unwrap();
+        guessed.header.id = pending.message.header.id;
+        if pending.message.questions[0].qname != guessed.questions[0].qname {
+            assert_eq!(Verdict::WrongCase, resolver.receive(pending.port, &guessed));
+        }
+
+        response.header.id = pending.message.header.id;
+        assert_eq!(Verdict::Accepted, resolver.receive(pending.port, &response));
+        // the race is over once an answer has been accepted
+        assert_eq!(
+            Verdict::Unsolicited,
+            resolver.receive(pending.port, &response)
+        );
+    }
+
+    #[test]
+    fn covering_every_txid_poisons_a_fixed_port_resolver() {
+        let options = options(ResolverModel::new(), 65536);
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        let mut subdomains = options.subdomains.generator().unwrap();
+        let outcome = simulate_round(
+            &options,
+            &mut resolver,
+            subdomains.as_mut(),
+            &mut rng::new(Some(1)),
+        )
+        .unwrap();
+        assert!(outcome.poisoned);
+        assert_eq!(16.0, outcome.entropy_bits);
+    }
+
+    #[test]
+    fn case_randomization_defeats_a_full_txid_sweep() {
+        let mut model = ResolverModel::new();
+        model.case_randomization = true;
+        let options = options(model, 65536);
+
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        let mut subdomains = options.subdomains.generator().unwrap();
+        let mut rng = rng::new(Some(1));
+        let poisoned = (0..5)
+            .filter(|_| {
+                simulate_round(&options, &mut resolver, subdomains.as_mut(), &mut rng)
+                    .unwrap()
+                    .poisoned
+            })
+            .count();
+        // 17 letters leave a 1 in 131072 chance of guessing the case of each round's query
+        assert_eq!(0, poisoned);
+    }
+
+    #[test]
+    fn cookies_defeat_a_full_txid_sweep() {
+        let mut model = ResolverModel::new();
+        model.cookies = true;
+        let options = options(model, 65536);
+
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        let mut subdomains = options.subdomains.generator().unwrap();
+        let outcome = simulate_round(
+            &options,
+            &mut resolver,
+            subdomains.as_mut(),
+            &mut rng::new(Some(1)),
+        )
+        .unwrap();
+        assert!(!outcome.poisoned);
+        // exactly one spoofed response guesses the TXID, and it lacks the client cookie
+        assert_eq!(Some(&1), outcome.rejected.get(&Verdict::WrongCookie));
+        assert_eq!(Some(&65535), outcome.rejected.get(&Verdict::WrongId));
+        assert_eq!(80.0, outcome.entropy_bits);
+    }
+
+    #[test]
+    fn generator_lasts_for_every_round() {
+        let mut options = options(ResolverModel::new(), 100);
+        options.subdomains = SubdomainOptions::new(SubdomainKind::Counter);
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        let mut subdomains = options.subdomains.generator().unwrap();
+        let mut rng = rng::new(Some(1));
+
+        // like the attack, the counter keeps counting from one round to the next
+        for _ in 0..3 {
+            let outcome =
+                simulate_round(&options, &mut resolver, subdomains.as_mut(), &mut rng).unwrap();
+            assert!(!outcome.negatively_cached);
+        }
+        assert_eq!(Some(3600), resolver.negative_ttl("0000000.example.com"));
+        assert_eq!(Some(3600), resolver.negative_ttl("0000002.example.com"));
+        assert_eq!(None, resolver.negative_ttl("0000003.example.com"));
+    }
+
+    /// Generator that queries the same name every round, like an attack restarted with the same
+    /// seed
+    struct Repeat(&'static str);
+
+    impl SubdomainGenerator for Repeat {
+        fn next_name(&mut self, _rng: &mut dyn RngCore) -> String {
+            return self.0.to_string();
+        }
+    }
+
+    #[test]
+    fn real_nxdomain_answers_are_negatively_cached() {
+        let mut options = options(ResolverModel::new(), 100);
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        let mut repeat = Repeat("again");
+        let mut rng = rng::new(Some(1));
+
+        // the race for again.example.com is lost to the real NXDOMAIN answer
+        let outcome = simulate_round(&options, &mut resolver, &mut repeat, &mut rng).unwrap();
+        assert!(!outcome.poisoned && !outcome.negatively_cached);
+        assert_eq!(Some(3600), resolver.negative_ttl("again.example.com"));
+
+        // repeating the name finds it already cached, so nothing is sent upstream to race
+        let outcome = simulate_round(&options, &mut resolver, &mut repeat, &mut rng).unwrap();
+        assert!(outcome.negatively_cached);
+        assert!(outcome.rejected.is_empty());
+
+        // without a negative caching TTL the name is queried again
+        options.negative_ttl = 0;
+        let mut resolver = SimulatedResolver::new(options.model.clone());
+        for _ in 0..2 {
+            let outcome = simulate_round(&options, &mut resolver, &mut repeat, &mut rng).unwrap();
+            assert!(!outcome.negatively_cached);
+        }
+        assert_eq!(None, resolver.negative_ttl("again.example.com"));
+    }
+
+    #[test]
+    fn entropy_of_randomized_queries() {
+        let mut model = ResolverModel::new();
+        model.ports = 1024;
+        assert_eq!(26.0, entropy_bits(&model, "abc.example.com"));
+        model.case_randomization = true;
+        assert_eq!(39.0, entropy_bits(&model, "abc.example.com"));
+    }
+}
@@ -60,6 +60,12 @@ impl Spoofer<'_> {
         });
     }
 
+    /// Sets the UDP source port the packets are spoofed from and the destination port they are sent to
+    pub fn set_ports(&mut self, spoofed_port: u16, target_port: u16) {
+        self.spoofed_port = spoofed_port;
+        self.target_port = target_port;
+    }
+
     pub fn send_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
         let mut data: Vec<u8> = Vec::new();
         data.extend(&[0u8; UDP_HEADER_BYTES]);
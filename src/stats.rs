@@ -0,0 +1,250 @@
+use serde::Serialize;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Metrics collected while running an attack
+#[derive(PartialEq, Debug)]
+pub struct AttackStats {
+    /// Number of spoofed packets successfully handed to the network
+    pub packets_sent: u64,
+    /// Number of spoofed packets that could not be sent
+    pub send_errors: u64,
+    /// Number of rounds that were started
+    pub rounds: u32,
+    /// Number of distinct TXIDs covered by the spoofed responses in each round
+    pub txids_per_round: Vec<u32>,
+    /// How long the attack has been running for
+    pub elapsed: Duration,
+    /// Round in which the attack was detected to have succeeded, if any
+    pub success_round: Option<u32>,
+    /// How packets were paced, if they were
+    pub pacing: Option<PacingStats>,
+}
+
+/// Pacing configuration along with how long sending was held back to respect it
+#[derive(PartialEq, Debug, Clone)]
+pub struct PacingStats {
+    pub rate: f64,
+    pub burst: u32,
+    pub ramp_up: Duration,
+    pub throttled: Duration,
+}
+
+/// Final summary of an attack, serialized as JSON so runs can be compared across lab setups
+#[derive(Serialize, PartialEq, Debug)]
+struct AttackSummary {
+    packets_sent: u64,
+    packets_per_second: f64,
+    send_errors: u64,
+    rounds: u32,
+    txids_per_round: Vec<u32>,
+    elapsed_secs: f64,
+    success_round: Option<u32>,
+    pacing: Option<PacingSummary>,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+struct PacingSummary {
+    rate: f64,
+    burst: u32,
+    ramp_up_secs: f64,
+    throttled_secs: f64,
+}
+
+impl AttackStats {
+    pub fn new() -> AttackStats {
+        return AttackStats {
+            packets_sent: 0,
+            send_errors: 0,
+            rounds: 0,
+            txids_per_round: Vec::new(),
+            elapsed: Duration::new(0, 0),
+            success_round: None,
+            pacing: None,
+        };
+    }
+
+    pub fn packets_per_second(&self) -> f64 {
+        if self.elapsed.as_secs_f64() == 0.0 {
+            return 0.0;
+        }
+        return self.packets_sent as f64 / self.elapsed.as_secs_f64();
+    }
+
+    fn summary(&self) -> AttackSummary {
+        return AttackSummary {
+            packets_sent: self.packets_sent,
+            packets_per_second: self.packets_per_second(),
+            send_errors: self.send_errors,
+            rounds: self.rounds,
+            txids_per_round: self.txids_per_round.clone(),
+            elapsed_secs: self.elapsed.as_secs_f64(),
+            success_round: self.success_round,
+            pacing: self.pacing.as_ref().map(|pacing| PacingSummary {
+                rate: pacing.rate,
+                burst: pacing.burst,
+                ramp_up_secs: pacing.ramp_up.as_secs_f64(),
+                throttled_secs: pacing.throttled.as_secs_f64(),
+            }),
+        };
+    }
+
+    pub fn to_json(&self) -> String {
+        // serializing a struct of plain numbers cannot fail
+        return serde_json::to_string_pretty(&self.summary()).unwrap();
+    }
+}
+
+/// Records metrics while an attack is running and keeps a live progress line on stderr up to date
+pub struct Recorder {
+    stats: AttackStats,
+    start: Instant,
+    last_print: Option<Instant>,
+    print_interval: Duration,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        return Recorder {
+            stats: AttackStats::new(),
+            start: Instant::now(),
+            last_print: None,
+            print_interval: Duration::from_millis(250),
+        };
+    }
+
+    pub fn start_round(&mut self) {
+        self.stats.rounds += 1;
+        self.stats.txids_per_round.push(0);
+        self.update();
+    }
+
+    pub fn record_send(&mut self, succeeded: bool) {
+        if succeeded {
+            self.stats.packets_sent += 1;
+        } else {
+            self.stats.send_errors += 1;
+        }
+        self.update();
+    }
+
+    /// Records that the given number of distinct TXIDs were covered in the current round
+    ///
+    /// Responses are spoofed from several addresses over the same TXID range, so only the best
+    /// coverage seen in a round is kept.
+    pub fn record_txids(&mut self, covered: u32) {
+        if let Some(current) = self.stats.txids_per_round.last_mut() {
+            *current = std::cmp::max(*current, covered);
+        }
+    }
+
+    pub fn record_success(&mut self, round: u32) {
+        self.stats.success_round = Some(round);
+    }
+
+    pub fn record_pacing(&mut self, pacing: PacingStats) {
+        self.stats.pacing = Some(pacing);
+    }
+
+    /// Prints the final state of the progress line and returns the collected metrics
+    pub fn finish(mut self) -> AttackStats {
+        self.last_print = None;
+        self.update();
+        eprintln!();
+        return self.stats;
+    }
+
+    /// Updates the progress line if enough time has passed since it was last printed
+    fn update(&mut self) {
+        self.stats.elapsed = self.start.elapsed();
+
+        if let Some(last) = self.last_print {
+            if last.elapsed() < self.print_interval {
+                return;
+            }
+        }
+        self.last_print = Some(Instant::now());
+
+        eprint!(
+            "\rround {} | {} packets | {:.0} pkt/s | {} errors | {:.1}s ",
+            self.stats.rounds,
+            self.stats.packets_sent,
+            self.stats.packets_per_second(),
+            self.stats.send_errors,
+            self.stats.elapsed.as_secs_f32()
+        );
+        std::io::stderr().flush().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{AttackStats, PacingStats, Recorder};
+    use std::time::Duration;
+
+    #[test]
+    fn packets_per_second() {
+        let stats = AttackStats {
+            packets_sent: 500,
+            elapsed: Duration::from_millis(250),
+            ..AttackStats::new()
+        };
+
+        assert_eq!(2000.0, stats.packets_per_second());
+        assert_eq!(0.0, AttackStats::new().packets_per_second());
+    }
+
+    #[test]
+    fn record_txids_per_round() {
+        let mut recorder = Recorder::new();
+        recorder.record_txids(10);
+        recorder.start_round();
+        recorder.record_txids(10);
+        recorder.record_txids(5);
+        recorder.start_round();
+        recorder.record_txids(7);
+        recorder.record_send(true);
+        recorder.record_send(false);
+        recorder.record_success(2);
+
+        let stats = recorder.finish();
+
+        assert_eq!(2, stats.rounds);
+        assert_eq!(vec![10, 7], stats.txids_per_round);
+        assert_eq!(1, stats.packets_sent);
+        assert_eq!(1, stats.send_errors);
+        assert_eq!(Some(2), stats.success_round);
+    }
+
+    #[test]
+    fn stats_to_json() {
+        let stats = AttackStats {
+            packets_sent: 10,
+            send_errors: 1,
+            rounds: 2,
+            txids_per_round: vec![4, 6],
+            elapsed: Duration::from_secs(2),
+            success_round: Some(2),
+            pacing: Some(PacingStats {
+                rate: 100.0,
+                burst: 10,
+                ramp_up: Duration::from_secs(1),
+                throttled: Duration::from_millis(500),
+            }),
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+
+        assert_eq!(10, json["packets_sent"]);
+        assert_eq!(5.0, json["packets_per_second"]);
+        assert_eq!(1, json["send_errors"]);
+        assert_eq!(2, json["rounds"]);
+        assert_eq!(serde_json::json!([4, 6]), json["txids_per_round"]);
+        assert_eq!(2.0, json["elapsed_secs"]);
+        assert_eq!(2, json["success_round"]);
+        assert_eq!(100.0, json["pacing"]["rate"]);
+        assert_eq!(10, json["pacing"]["burst"]);
+        assert_eq!(1.0, json["pacing"]["ramp_up_secs"]);
+        assert_eq!(0.5, json["pacing"]["throttled_secs"]);
+    }
+}
@@ -0,0 +1,287 @@
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use std::str::FromStr;
+
+/// Longest label allowed by IETF RFC 1035
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Generates the random subdomains of the target domain that are queried in each round
+///
+/// Every round needs a name the target resolver has not cached yet, so that it has to ask the
+/// target domain's nameservers and the race can be run again. How the names are chosen decides
+/// how they interact with negative caching, wildcards, and 0x20 encoding.
+pub trait SubdomainGenerator {
+    /// Returns the next name to query, relative to the target domain
+    fn next_name(&mut self, rng: &mut dyn RngCore) -> String;
+}
+
+/// How the labels of the queried subdomains are chosen
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum SubdomainKind {
+    /// random letters in mixed case and digits, as used by the original attack
+    Mixed,
+    /// random lowercase letters and digits, which leaves the case free for 0x20 encoding
+    Lowercase,
+    /// random words from a word list joined with hyphens
+    Dictionary,
+    /// zero padded decimal numbers counting up from 0
+    Counter,
+}
+
+impl FromStr for SubdomainKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "mixed" => Ok(SubdomainKind::Mixed),
+            "lowercase" => Ok(SubdomainKind::Lowercase),
+            "dictionary" => Ok(SubdomainKind::Dictionary),
+            "counter" => Ok(SubdomainKind::Counter),
+            _ => Err(format!(
+                "Unknown subdomain generator {}, please enter either mixed, lowercase, dictionary, or counter",
+                s
+            )),
+        };
+    }
+}
+
+/// Options that control which subdomains are queried during an attack
+#[derive(PartialEq, Debug, Clone)]
+pub struct SubdomainOptions {
+    pub kind: SubdomainKind,
+    /// Length of the generated label, dictionary labels are at least this long
+    pub length: usize,
+    /// Words used by the dictionary generator
+    pub words: Vec<String>,
+    /// Existing name under the target domain to put the generated labels below
+    ///
+    /// A wildcard does not match names below a name that exists in the zone (IETF RFC 4592), so
+    /// placing the labels below an existing name keeps them from being answered by a wildcard.
+    pub parent: Option<String>,
+}
+
+impl SubdomainOptions {
+    pub fn new(kind: SubdomainKind) -> SubdomainOptions {
+        return SubdomainOptions {
+            kind,
+            length: 7,
+            words: Vec::new(),
+            parent: None,
+        };
+    }
+
+    /// Builds the generator described by the options
+    pub fn generator(&self) -> Result<Box<dyn SubdomainGenerator>, String> {
+        if self.length == 0 || self.length > MAX_LABEL_LENGTH {
+            return Err(format!(
+                "Subdomain labels must be between 1 and {} characters long",
+                MAX_LABEL_LENGTH
+            ));
+        }
+
+        let generator: Box<dyn SubdomainGenerator> = match self.kind {
+            SubdomainKind::Mixed => Box::new(RandomLabel {
+                alphabet: ('a'..='z').chain('A'..='Z').chain('0'..='9').collect(),
+                length: self.length,
+            }),
+            SubdomainKind::Lowercase => Box::new(RandomLabel {
+                alphabet: ('a'..='z').chain('0'..='9').collect(),
+                length: self.length,
+            }),
+            SubdomainKind::Dictionary => Box::new(Dictionary::new(&self.words, self.length)?),
+            SubdomainKind::Counter => Box::new(Counter {
+                next: 0,
+                length: self.length,
+            }),
+        };
+
+        return match &self.parent {
+            None => Ok(generator),
+            Some(parent) => Ok(Box::new(BelowParent {
+                inner: generator,
+                parent: parent.trim_end_matches('.').to_string(),
+            })),
+        };
+    }
+}
+
+/// Loads a word list with one word per line, where # starts a comment
+pub fn load_words(path: &str) -> Result<Vec<String>, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Err(e) => return Err(format!("Could not read word list {}: {}", path, e)),
+        Ok(c) => c,
+    };
+    return Ok(contents
+        .lines()
+        .map(|line| line.splitn(2, '#').next().unwrap_or("").trim().to_string())
+        .filter(|word| !word.is_empty())
+        .collect());
+}
+
+fn rand_label(alphabet: &[char], length: usize, rng: &mut dyn RngCore) -> String {
+    return (0..length).map(|_| alphabet.choose(rng).unwrap()).collect();
+}
+
+struct RandomLabel {
+    alphabet: Vec<char>,
+    length: usize,
+}
+
+impl SubdomainGenerator for RandomLabel {
+    fn next_name(&mut self, rng: &mut dyn RngCore) -> String {
+        return rand_label(&self.alphabet, self.length, rng);
+    }
+}
+
+struct Dictionary {
+    words: Vec<String>,
+    length: usize,
+}
+
+impl Dictionary {
+    fn new(words: &[String], length: usize) -> Result<Dictionary, String> {
+        if words.is_empty() {
+            return Err("The dictionary subdomain generator needs a word list".to_string());
+        }
+        let mut lowercase = Vec::new();
+        for word in words {
+            let valid = word.len() <= MAX_LABEL_LENGTH
+                && !word.starts_with('-')
+                && !word.ends_with('-')
+                && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+            if !valid {
+                return Err(format!("{} cannot be used in a DNS label", word));
+            }
+            lowercase.push(word.to_ascii_lowercase());
+        }
+        return Ok(Dictionary {
+            words: lowercase,
+            length,
+        });
+    }
+}
+
+impl SubdomainGenerator for Dictionary {
+    fn next_name(&mut self, rng: &mut dyn RngCore) -> String {
+        let mut label = self.words.choose(rng).unwrap().clone();
+        while label.len() < self.length {
+            let word = self.words.choose(rng).unwrap();
+            if label.len() + 1 + word.len() > MAX_LABEL_LENGTH {
+                break;
+            }
+            label.push('-');
+            label.push_str(word);
+        }
+        return label;
+    }
+}
+
+struct Counter {
+    next: u64,
+    length: usize,
+}
+
+impl SubdomainGenerator for Counter {
+    fn next_name(&mut self, _rng: &mut dyn RngCore) -> String {
+        let label = format!("{:0width$}", self.next, width = self.length);
+        self.next += 1;
+        return label;
+    }
+}
+
+/// Puts the names of another generator below an existing name in the target domain
+struct BelowParent {
+    inner: Box<dyn SubdomainGenerator>,
+    parent: String,
+}
+
+impl SubdomainGenerator for BelowParent {
+    fn next_name(&mut self, rng: &mut dyn RngCore) -> String {
+        return format!("{}.{}", self.inner.next_name(rng), self.parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng;
+    use crate::subdomain::{SubdomainKind, SubdomainOptions};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_random_string_reuses_chars() {
+        // number of alphanumeric characters
+        const NUM_CHARS: usize = 62;
+        let mut options = SubdomainOptions::new(SubdomainKind::Mixed);
+        options.length = NUM_CHARS + 1;
+        let string = options.generator().unwrap().next_name(&mut rng::new(None));
+        let unique_chars: HashSet<char> = string.chars().collect();
+
+        assert_eq!(NUM_CHARS + 1, string.chars().count());
+        assert!(unique_chars.len() < string.chars().count());
+    }
+
+    #[test]
+    fn lowercase_labels() {
+        let mut options = SubdomainOptions::new(SubdomainKind::Lowercase);
+        options.length = 20;
+        let mut generator = options.generator().unwrap();
+        let mut rng = rng::new(Some(1));
+
+        for _ in 0..100 {
+            let name = generator.next_name(&mut rng);
+            assert_eq!(20, name.len());
+            assert!(!name.chars().any(|c| c.is_ascii_uppercase()));
+        }
+    }
+
+    #[test]
+    fn dictionary_labels() {
+        let mut options = SubdomainOptions::new(SubdomainKind::Dictionary);
+        assert!(options.generator().is_err());
+
+        options.words = vec!["Apple".to_string(), "kiwi".to_string()];
+        options.length = 10;
+        let mut generator = options.generator().unwrap();
+        let mut rng = rng::new(Some(1));
+        for _ in 0..20 {
+            let name = generator.next_name(&mut rng);
+            assert!(name.len() >= 10);
+            assert!(name
+                .split('-')
+                .all(|word| word == "apple" || word == "kiwi"));
+        }
+
+        options.words = vec!["not a label".to_string()];
+        assert!(options.generator().is_err());
+    }
+
+    #[test]
+    fn counter_labels() {
+        let mut options = SubdomainOptions::new(SubdomainKind::Counter);
+        options.length = 4;
+        let mut generator = options.generator().unwrap();
+        let mut rng = rng::new(None);
+
+        assert_eq!("0000", generator.next_name(&mut rng));
+        assert_eq!("0001", generator.next_name(&mut rng));
+    }
+
+    #[test]
+    fn labels_below_parent() {
+        let mut options = SubdomainOptions::new(SubdomainKind::Counter);
+        options.length = 2;
+        options.parent = Some("www.".to_string());
+        let mut generator = options.generator().unwrap();
+
+        assert_eq!("00.www", generator.next_name(&mut rng::new(None)));
+    }
+
+    #[test]
+    fn invalid_label_length() {
+        let mut options = SubdomainOptions::new(SubdomainKind::Lowercase);
+        options.length = 0;
+        assert!(options.generator().is_err());
+        options.length = 64;
+        assert!(options.generator().is_err());
+    }
+}
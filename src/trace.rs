@@ -0,0 +1,465 @@
+use crate::discovery::Nameserver;
+use crate::dns;
+use crate::dns::hostname::{in_zone, same_name};
+use crate::dns::message::Message;
+use rand::RngCore;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+/// Maximum number of servers asked while resolving a single name, guards against referral loops
+const MAX_STEPS: u32 = 32;
+/// Maximum number of CNAMEs followed while resolving a single name
+const MAX_CNAMES: u32 = 8;
+/// Maximum nesting of lookups for the addresses of nameservers that were referred to without glue
+const MAX_DEPTH: usize = 4;
+
+/// What a nameserver's response means for an iterative lookup
+#[derive(PartialEq, Debug)]
+enum Step {
+    /// Records of the queried type, along with any CNAMEs that led to them
+    Answer(Vec<dns::Record>),
+    /// CNAMEs that end at a name the server did not give records for, the lookup has to restart
+    /// from the root for the last name in the chain
+    Cname(Vec<dns::Record>, String),
+    /// Delegation to the nameservers of a zone closer to the queried name
+    Referral(String, Vec<Nameserver>),
+    /// The name exists but has no records of the queried type
+    NoData,
+    /// The name does not exist
+    NxDomain,
+    /// The server is not a working authority for the zone it was asked about, the reason is given
+    Lame(String),
+}
+
+/// Resolves the hostname iteratively starting from the root servers in the hints, printing each
+/// server that is asked along with what it answered
+///
+/// Queries are sent with RD=0 and referrals are followed down to the authoritative servers, the
+/// same way a recursive resolver does. This is the delegation path that a Kaminsky attack hijacks
+/// by injecting a referral to the attacker's nameserver. Only the IPv4 addresses of nameservers
+/// are used.
+pub fn trace(
+    hints: &dns::RootHints,
+    hostname: &str,
+    qtype: dns::Type,
+    rng: &mut dyn RngCore,
+) -> Result<Vec<dns::Record>, String> {
+    return resolve(hints, hostname, qtype, 0, rng);
+}
+
+fn resolve(
+    hints: &dns::RootHints,
+    hostname: &str,
+    qtype: dns::Type,
+    depth: usize,
+    rng: &mut dyn RngCore,
+) -> Result<Vec<dns::Record>, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!(
+            "Gave up resolving {} since nameserver lookups are nested too deeply",
+            hostname
+        ));
+    }
+    let indent = "    ".repeat(depth);
+
+    let mut qname = String::from(hostname);
+    let mut chain: Vec<dns::Record> = Vec::new();
+    let mut cnames = 0;
+    let mut zone = String::from(".");
+    let mut nameservers = root_nameservers(hints);
+
+    for _ in 0..MAX_STEPS {
+        let step = match ask(hints, &nameservers, &zone, &qname, qtype, depth, rng) {
+            None => {
+                return Err(format!(
+                    "None of the nameservers for {} gave a usable response for {}",
+                    zone, qname
+                ))
+            }
+            Some(s) => s,
+        };
+
+        match step {
+            Step::Answer(records) => {
+                for record in &records {
+                    println!("{}  answer: {}", indent, record);
+                }
+                chain.extend(records);
+                return Ok(chain);
+            }
+            Step::Cname(records, target) => {
+                for record in &records {
+                    println!("{}  answer: {}", indent, record);
+                }
+                cnames += 1;
+                if cnames > MAX_CNAMES {
+                    return Err(format!("Gave up following CNAMEs at {}", target));
+                }
+                println!(
+                    "{}  following the CNAME to {} from the root",
+                    indent, target
+                );
+                chain.extend(records);
+                qname = target;
+                zone = String::from(".");
+                nameservers = root_nameservers(hints);
+            }
+            Step::Referral(child, referred) => {
+                println!("{}  referral to {}", indent, child);
+                for ns in &referred {
+                    let glue: Vec<String> = ns.ipv4.iter().map(|ip| ip.to_string()).collect();
+                    if glue.is_empty() {
+                        println!("{}    NS {} (no glue)", indent, ns.name);
+                    } else {
+                        println!("{}    NS {} -> {}", indent, ns.name, glue.join(", "));
+                    }
+                }
+                zone = child;
+                nameservers = referred;
+            }
+            Step::NoData => {
+                println!("{}  {} has no {} records", indent, qname, qtype);
+                return Ok(chain);
+            }
+            Step::NxDomain => {
+                return Err(format!("{} does not exist (NXDOMAIN)", qname));
+            }
+            Step::Lame(_) => unreachable!("lame responses are skipped when asking nameservers"),
+        }
+    }
+
+    return Err(format!(
+        "Gave up resolving {} after asking {} servers",
+        hostname, MAX_STEPS
+    ));
+}
+
+/// Asks each of the zone's nameservers in turn until one gives a usable response
+///
+/// Nameservers that were referred to without glue have their addresses looked up first.
+fn ask(
+    hints: &dns::RootHints,
+    nameservers: &[Nameserver],
+    zone: &str,
+    qname: &str,
+    qtype: dns::Type,
+    depth: usize,
+    rng: &mut dyn RngCore,
+) -> Option<Step> {
+    const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+    let indent = "    ".repeat(depth);
+
+    for ns in nameservers {
+        let addrs = if !ns.ipv4.is_empty() {
+            ns.ipv4.clone()
+        } else {
+            println!("{}  looking up the address of {}", indent, ns.name);
+            match resolve(hints, &ns.name, dns::Type::A, depth + 1, rng) {
+                Err(e) => {
+                    println!(
+                        "{}  could not find the address of {}: {}",
+                        indent, ns.name, e
+                    );
+                    continue;
+                }
+                Ok(records) => ipv4_addrs(&records),
+            }
+        };
+
+        for addr in addrs {
+            println!(
+                "{}asking {} ({}) for {} {} in zone {}",
+                indent, ns.name, addr, qname, qtype, zone
+            );
+
+            let mut client = dns::Client::new(addr.to_string());
+            client.set_timeout(QUERY_TIMEOUT);
+            let mut request = dns::Query::new(vec![String::from(qname)]);
+            request.qtype = qtype;
+            request.recursion_desired = false;
+
+            let response = match client.query(request, rng) {
+                Err(e) => {
+                    println!("{}  no usable response: {}", indent, e);
+                    continue;
+                }
+                Ok(r) => r,
+            };
+
+            match classify(&response, qname, qtype, zone) {
+                Step::Lame(reason) => {
+                    println!(
+                        "{}  lame delegation: {}, trying the next server",
+                        indent, reason
+                    );
+                }
+                step => return Some(step),
+            }
+        }
+    }
+
+    return None;
+}
+
+/// Works out what a response from a nameserver for the zone means for the lookup of qname
+///
+/// Only records that fall within the zone are trusted. This is the same bailiwick rule resolvers use
+/// to stop a server from injecting records for names it is not responsible for.
+fn classify(response: &Message, qname: &str, qtype: dns::Type, zone: &str) -> Step {
+    const NXDOMAIN: u8 = 3;
+
+    if response.header.rcode == NXDOMAIN {
+        if response.header.aa {
+            return Step::NxDomain;
+        }
+        return Step::Lame("non-authoritative NXDOMAIN".to_string());
+    }
+    if response.header.rcode != 0 {
+        return Step::Lame(format!("response code {}", response.header.rcode));
+    }
+
+    let answers: Vec<dns::Record> = response
+        .answers
+        .iter()
+        .filter_map(|rr| dns::Record::from_rr(rr).ok())
+        .filter(|record| in_zone(record.name(), zone))
+        .collect();
+
+    // follow any CNAMEs within the answer section
+    let mut chain: Vec<dns::Record> = Vec::new();
+    let mut name = String::from(qname);
+    for _ in 0..=answers.len() {
+        let matching: Vec<dns::Record> = answers
+            .iter()
+            .filter(|record| same_name(record.name(), &name) && record.rtype() == qtype)
+            .cloned()
+            .collect();
+        if !matching.is_empty() {
+            chain.extend(matching);
+            return Step::Answer(chain);
+        }
+
+        let cname = answers.iter().find_map(|record| match record {
+            dns::Record::CNAME(cname) if same_name(&cname.name, &name) => Some(cname.clone()),
+            _ => None,
+        });
+        match cname {
+            None => break,
+            Some(cname) => {
+                name = cname.cname.clone();
+                chain.push(dns::Record::CNAME(cname));
+            }
+        }
+    }
+    if !chain.is_empty() {
+        return Step::Cname(chain, name);
+    }
+
+    let referral: Vec<dns::NSRecord> = response
+        .authorities
+        .iter()
+        .filter_map(|rr| match dns::Record::from_rr(rr) {
+            Ok(dns::Record::NS(ns)) => Some(ns),
+            _ => None,
+        })
+        .collect();
+    if let Some(first) = referral.first() {
+        let child = first.name.clone();
+        // a referral must move strictly closer to the queried name, anything else would loop
+        if same_name(&child, zone) || !in_zone(&child, zone) || !in_zone(qname, &child) {
+            return Step::Lame(format!(
+                "referral to {} does not lead from {} towards {}",
+                child, zone, qname
+            ));
+        }
+
+        let nameservers = referral
+            .iter()
+            .filter(|ns| same_name(&ns.name, &child))
+            .map(|ns| Nameserver {
+                name: ns.ns.clone(),
+                ipv4: glue(response, &ns.ns, zone),
+                ipv6: Vec::new(),
+            })
+            .collect();
+        return Step::Referral(child, nameservers);
+    }
+
+    if response.header.aa {
+        return Step::NoData;
+    }
+    return Step::Lame("neither an answer nor a referral".to_string());
+}
+
+/// IPv4 glue for the nameserver from the additional section, as long as the glue is within the zone
+/// of the server that sent it
+fn glue(response: &Message, nameserver: &str, zone: &str) -> Vec<Ipv4Addr> {
+    if !in_zone(nameserver, zone) {
+        return Vec::new();
+    }
+    return response
+        .additionals
+        .iter()
+        .filter_map(|rr| match dns::Record::from_rr(rr) {
+            Ok(dns::Record::A(a)) if same_name(&a.name, nameserver) => Some(Ipv4Addr::from(a.ip)),
+            _ => None,
+        })
+        .collect();
+}
+
+fn root_nameservers(hints: &dns::RootHints) -> Vec<Nameserver> {
+    return hints
+        .servers
+        .iter()
+        .map(|server| Nameserver {
+            name: server.name.clone(),
+            ipv4: server.ipv4.clone(),
+            ipv6: server.ipv6.clone(),
+        })
+        .collect();
+}
+
+fn ipv4_addrs(records: &[dns::Record]) -> Vec<Ipv4Addr> {
+    return records
+        .iter()
+        .filter_map(|record| match record {
+            dns::Record::A(a) => Some(Ipv4Addr::from(a.ip)),
+            _ => None,
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discovery::Nameserver;
+    use crate::dns;
+    use crate::dns::message::Message;
+    use crate::rng;
+    use crate::trace::{classify, Step};
+    use std::net::Ipv4Addr;
+
+    fn response(aa: bool, build: impl Fn(&mut dns::Response)) -> Message {
+        let query = dns::Query::new(vec!["www.example.com".to_string()])
+            .to_message(&mut rng::new(Some(1)))
+            .unwrap();
+        let mut response = dns::Response::new(query);
+        response.authoritative_answer = aa;
+        build(&mut response);
+        return response.to_message().unwrap();
+    }
+
+    fn ns(name: &str, ns: &str) -> dns::Record {
+        return dns::Record::NS(dns::NSRecord {
+            name: name.to_string(),
+            ttl: 3600,
+            ns: ns.to_string(),
+        });
+    }
+
+    fn a(name: &str, ip: [u8; 4]) -> dns::Record {
+        return dns::Record::A(dns::ARecord {
+            name: name.to_string(),
+            ttl: 3600,
+            ip,
+        });
+    }
+
+    #[test]
+    fn classify_referral_with_glue() {
+        let message = response(false, |r| {
+            r.add_authority(ns("example.com", "ns1.example.com"))
+                .unwrap();
+            r.add_authority(ns("example.com", "ns.other.net")).unwrap();
+            r.add_additional(a("ns1.example.com", [10, 0, 0, 1]))
+                .unwrap();
+            r.add_additional(a("ns.other.net", [10, 0, 0, 2])).unwrap();
+        });
+
+        // glue for ns.other.net is outside the com zone so it is not trusted
+        let expected = Step::Referral(
+            "example.com".to_string(),
+            vec![
+                Nameserver {
+                    name: "ns1.example.com".to_string(),
+                    ipv4: vec![Ipv4Addr::new(10, 0, 0, 1)],
+                    ipv6: vec![],
+                },
+                Nameserver {
+                    name: "ns.other.net".to_string(),
+                    ipv4: vec![],
+                    ipv6: vec![],
+                },
+            ],
+        );
+        assert_eq!(
+            expected,
+            classify(&message, "www.example.com", dns::Type::A, "com")
+        );
+    }
+
+    #[test]
+    fn classify_answer_through_cname() {
+        let cname = dns::Record::CNAME(dns::CNAMERecord {
+            name: "www.example.com".to_string(),
+            ttl: 3600,
+            cname: "web.example.com".to_string(),
+        });
+        let message = response(true, |r| {
+            r.add_answer(cname.clone()).unwrap();
+            r.add_answer(a("web.example.com", [10, 0, 0, 3])).unwrap();
+        });
+
+        assert_eq!(
+            Step::Answer(vec![cname.clone(), a("web.example.com", [10, 0, 0, 3])]),
+            classify(&message, "www.example.com", dns::Type::A, "example.com")
+        );
+
+        // records for a name outside the zone must be looked up from the root
+        let message = response(true, |r| {
+            r.add_answer(dns::Record::CNAME(dns::CNAMERecord {
+                name: "www.example.com".to_string(),
+                ttl: 3600,
+                cname: "www.example.net".to_string(),
+            }))
+            .unwrap();
+            r.add_answer(a("www.example.net", [10, 0, 0, 4])).unwrap();
+        });
+        match classify(&message, "www.example.com", dns::Type::A, "example.com") {
+            Step::Cname(chain, target) => {
+                assert_eq!(1, chain.len());
+                assert_eq!("www.example.net", target);
+            }
+            step => panic!("Expected a CNAME but got {:?}", step),
+        }
+    }
+
+    #[test]
+    fn classify_lame_and_negative_responses() {
+        // a referral back up the tree is a sign of a lame delegation
+        let upward = response(false, |r| {
+            r.add_authority(ns("com", "a.gtld-servers.net")).unwrap();
+        });
+        assert!(matches!(
+            classify(&upward, "www.example.com", dns::Type::A, "example.com"),
+            Step::Lame(_)
+        ));
+
+        let refused = response(false, |r| r.rcode = 5);
+        assert!(matches!(
+            classify(&refused, "www.example.com", dns::Type::A, "example.com"),
+            Step::Lame(_)
+        ));
+
+        let nxdomain = response(true, |r| r.rcode = 3);
+        assert_eq!(
+            Step::NxDomain,
+            classify(&nxdomain, "www.example.com", dns::Type::A, "example.com")
+        );
+
+        let nodata = response(true, |_| ());
+        assert_eq!(
+            Step::NoData,
+            classify(&nodata, "www.example.com", dns::Type::A, "example.com")
+        );
+    }
+}
@@ -0,0 +1,508 @@
+use crate::dns;
+use crate::dns::dnssec::{self, DNSKEYRecord, DSRecord, RRSIGRecord};
+use crate::dns::hostname::{in_zone, normalize_name};
+use crate::dns::message::Message;
+use crate::zone::Zone;
+use std::collections::HashMap;
+
+/// Most zones walked up from a signer towards a trust anchor before giving up
+const MAX_CHAIN_LENGTH: usize = 16;
+
+/// Records of one name and type from a message section, along with the RRSIG records covering them
+#[derive(PartialEq, Debug, Clone)]
+pub struct RRset {
+    pub name: String,
+    pub rtype: dns::Type,
+    pub records: Vec<dns::Record>,
+    pub rrsigs: Vec<RRSIGRecord>,
+}
+
+impl RRset {
+    /// Groups the records of a message section into RRsets, in the order they first appear
+    ///
+    /// Records that cannot be converted are left out, so they can never be reported as secure.
+    pub fn group(section: &[dns::ResourceRecord]) -> Vec<RRset> {
+        let mut rrsets: Vec<RRset> = Vec::new();
+        let mut rrsigs = Vec::new();
+        for rr in section {
+            match dns::Record::from_rr(rr) {
+                Ok(dns::Record::RRSIG(rrsig)) => rrsigs.push(rrsig),
+                Ok(record) => {
+                    let name = normalize_name(record.name());
+                    match rrsets
+                        .iter_mut()
+                        .find(|rrset| rrset.name == name && rrset.rtype == record.rtype())
+                    {
+                        Some(rrset) => rrset.records.push(record),
+                        None => rrsets.push(RRset {
+                            name,
+                            rtype: record.rtype(),
+                            records: vec![record],
+                            rrsigs: Vec::new(),
+                        }),
+                    };
+                }
+                Err(_) => (),
+            };
+        }
+        for rrsig in rrsigs {
+            let name = normalize_name(&rrsig.name);
+            if let Some(rrset) = rrsets
+                .iter_mut()
+                .find(|rrset| rrset.name == name && rrset.rtype == rrsig.type_covered)
+            {
+                rrset.rrsigs.push(rrsig);
+            }
+        }
+        return rrsets;
+    }
+}
+
+/// Checks that one of the RRSIG records of the RRset is a valid signature by one of the keys
+///
+/// A signature is valid if it was made by a zone key of the signer, the signer is the owner or one
+/// of its parents, and the time falls between its inception and expiration.
+pub fn verify_rrset(rrset: &RRset, keys: &[DNSKEYRecord], now: u32) -> Result<(), String> {
+    if rrset.rrsigs.is_empty() {
+        return Err(format!("{} {} has no RRSIG", rrset.name, rrset.rtype));
+    }
+    let mut rrs = Vec::new();
+    for record in &rrset.records {
+        rrs.push(record.to_rr()?);
+    }
+
+    let mut error = String::new();
+    for rrsig in &rrset.rrsigs {
+        if !in_zone(&rrset.name, &rrsig.signer_name) {
+            error = format!(
+                "RRSIG signer {} is not a parent of {}",
+                rrsig.signer_name, rrset.name
+            );
+            continue;
+        }
+        // signature times use serial number arithmetic so that they keep working after 2106
+        if (now.wrapping_sub(rrsig.inception) as i32) < 0
+            || (rrsig.expiration.wrapping_sub(now) as i32) < 0
+        {
+            error = format!(
+                "RRSIG by key {} is only valid from {} to {}",
+                rrsig.key_tag,
+                dnssec::format_time(rrsig.inception),
+                dnssec::format_time(rrsig.expiration)
+            );
+            continue;
+        }
+
+        let candidates = keys.iter().filter(|key| {
+            key.flags & dnssec::ZONE_KEY != 0
+                && key.algorithm == rrsig.algorithm
+                && key.key_tag() == rrsig.key_tag
+                && normalize_name(&key.name) == normalize_name(&rrsig.signer_name)
+        });
+        let data = rrsig.signed_data(&rrs)?;
+        error = format!("No trusted key {} for {}", rrsig.key_tag, rrsig.signer_name);
+        for key in candidates {
+            match key.verify(&data, &rrsig.signature) {
+                Ok(()) => return Ok(()),
+                Err(e) => error = e,
+            };
+        }
+    }
+    return Err(error);
+}
+
+/// DNSSEC validator that builds a chain of trust from locally configured trust anchors
+///
+/// Trust anchors are DS or DNSKEY records for a zone, e.g. the lab root or the target domain. The
+/// keys of a zone are trusted once its DNSKEY RRset is signed by a key that matches a trust anchor
+/// or a DS record that was validated in turn with the keys of the parent zone.
+pub struct Validator {
+    anchors: Vec<dns::Record>,
+    /// Keys of the zones with a validated DNSKEY RRset, by zone
+    trusted: HashMap<String, Vec<DNSKEYRecord>>,
+}
+
+impl Validator {
+    pub fn new(anchors: Vec<dns::Record>) -> Result<Validator, String> {
+        for anchor in &anchors {
+            match anchor {
+                dns::Record::DS(_) | dns::Record::DNSKEY(_) => (),
+                _ => {
+                    return Err(format!(
+                        "Trust anchors must be DS or DNSKEY records, not {}",
+                        anchor.rtype()
+                    ))
+                }
+            };
+        }
+        return Ok(Validator {
+            anchors,
+            trusted: HashMap::new(),
+        });
+    }
+
+    /// Loads trust anchors from a file of DS or DNSKEY records in the zone file format, with fully
+    /// qualified owner names
+    pub fn load(path: &str) -> Result<Validator, String> {
+        let zone = Zone::load(path, Some("."))?;
+        if zone.records.is_empty() {
+            return Err(format!("Trust anchor file {} has no records", path));
+        }
+        return Validator::new(zone.records);
+    }
+
+    /// Trusts the keys in the DNSKEY RRset of the zone if it is signed by a key that matches one of
+    /// the trust anchors or one of the DS records, which must already have been validated
+    pub fn trust_keys(
+        &mut self,
+        zone: &str,
+        dnskeys: &RRset,
+        ds: &[DSRecord],
+        now: u32,
+    ) -> Result<(), String> {
+        let zone = normalize_name(zone);
+        let keys: Vec<DNSKEYRecord> = dnskeys
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                dns::Record::DNSKEY(key) if normalize_name(&key.name) == zone => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut ds: Vec<&DSRecord> = ds.iter().collect();
+        let mut anchor_keys = Vec::new();
+        for anchor in &self.anchors {
+            match anchor {
+                dns::Record::DS(anchor) if normalize_name(&anchor.name) == zone => ds.push(anchor),
+                dns::Record::DNSKEY(anchor) if normalize_name(&anchor.name) == zone => {
+                    anchor_keys.push(anchor)
+                }
+                _ => (),
+            };
+        }
+        let entry_points: Vec<DNSKEYRecord> = keys
+            .iter()
+            .filter(|key| {
+                ds.iter().any(|ds| key.matches(ds))
+                    || anchor_keys.iter().any(|anchor| {
+                        anchor.flags == key.flags
+                            && anchor.algorithm == key.algorithm
+                            && anchor.public_key == key.public_key
+                    })
+            })
+            .cloned()
+            .collect();
+        if entry_points.is_empty() {
+            return Err(format!(
+                "No DNSKEY of {} matches a trust anchor or DS record",
+                dnssec::presentation_name(&zone)
+            ));
+        }
+
+        verify_rrset(dnskeys, &entry_points, now)?;
+        self.trusted.insert(zone, keys);
+        return Ok(());
+    }
+
+    /// Validates the RRset, fetching the DNSKEY and DS records needed to build a chain of trust from
+    /// one of the trust anchors to its signer
+    pub fn validate(
+        &mut self,
+        rrset: &RRset,
+        now: u32,
+        fetch: &mut dyn FnMut(&str, dns::Type) -> Result<Message, String>,
+    ) -> Result<(), String> {
+        let signer = match rrset.rrsigs.first() {
+            None => return Err(format!("{} {} has no RRSIG", rrset.name, rrset.rtype)),
+            Some(rrsig) => normalize_name(&rrsig.signer_name),
+        };
+        self.secure_zone(&signer, now, fetch, 0)?;
+        return verify_rrset(rrset, &self.trusted[&signer], now);
+    }
+
+    fn secure_zone(
+        &mut self,
+        zone: &str,
+        now: u32,
+        fetch: &mut dyn FnMut(&str, dns::Type) -> Result<Message, String>,
+        depth: usize,
+    ) -> Result<(), String> {
+        if self.trusted.contains_key(zone) {
+            return Ok(());
+        }
+        if depth > MAX_CHAIN_LENGTH {
+            return Err(format!(
+                "No chain of trust to {} within {} zones",
+                dnssec::presentation_name(zone),
+                MAX_CHAIN_LENGTH
+            ));
+        }
+
+        let anchored = self
+            .anchors
+            .iter()
+            .any(|anchor| normalize_name(anchor.name()) == zone);
+        let mut ds = Vec::new();
+        if !anchored {
+            // the DS RRset lives in the parent zone and is signed with the parent's keys
+            let ds_rrset = find_rrset(
+                &fetch(dnssec::presentation_name(zone), dns::Type::DS)?,
+                zone,
+                dns::Type::DS,
+            )?;
+            let parent = match ds_rrset.rrsigs.first() {
+                None => {
+                    return Err(format!(
+                        "DS records of {} are not signed",
+                        dnssec::presentation_name(zone)
+                    ))
+                }
+                Some(rrsig) => normalize_name(&rrsig.signer_name),
+            };
+            if parent == zone || !in_zone(zone, &parent) {
+                return Err(format!(
+                    "DS records of {} are signed by {}, which is not its parent",
+                    dnssec::presentation_name(zone),
+                    dnssec::presentation_name(&parent)
+                ));
+            }
+            self.secure_zone(&parent, now, fetch, depth + 1)?;
+            verify_rrset(&ds_rrset, &self.trusted[&parent], now)?;
+            for record in ds_rrset.records {
+                if let dns::Record::DS(record) = record {
+                    ds.push(record);
+                }
+            }
+        }
+
+        let dnskeys = find_rrset(
+            &fetch(dnssec::presentation_name(zone), dns::Type::DNSKEY)?,
+            zone,
+            dns::Type::DNSKEY,
+        )?;
+        return self.trust_keys(zone, &dnskeys, &ds, now);
+    }
+}
+
+fn find_rrset(response: &Message, name: &str, rtype: dns::Type) -> Result<RRset, String> {
+    return match RRset::group(&response.answers)
+        .into_iter()
+        .find(|rrset| rrset.name == name && rrset.rtype == rtype)
+    {
+        None => Err(format!(
+            "No {} records for {}",
+            rtype,
+            dnssec::presentation_name(name)
+        )),
+        Some(rrset) => Ok(rrset),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns;
+    use crate::dns::dnssec::{DNSKEYRecord, RRSIGRecord};
+    use crate::dns::message::Message;
+    use crate::rng;
+    use crate::validator::{verify_rrset, RRset, Validator};
+    use ring::rand::SystemRandom;
+    use ring::signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair};
+
+    const NOW: u32 = 1_700_000_000;
+
+    fn ns(name: &str, ns: &str) -> dns::Record {
+        return dns::Record::NS(dns::NSRecord {
+            name: name.to_string(),
+            ttl: 3600,
+            ns: ns.to_string(),
+        });
+    }
+
+    fn rrset(records: Vec<dns::Record>) -> RRset {
+        return RRset {
+            name: records[0].name().to_ascii_lowercase(),
+            rtype: records[0].rtype(),
+            records,
+            rrsigs: Vec::new(),
+        };
+    }
+
+    /// Signs the RRset with the signing function as the key
+    fn sign(rrset: &mut RRset, key: &DNSKEYRecord, sign: &dyn Fn(&[u8]) -> Vec<u8>) {
+        let mut rrsig = RRSIGRecord {
+            name: rrset.name.clone(),
+            ttl: 3600,
+            type_covered: rrset.rtype,
+            algorithm: key.algorithm,
+            labels: dns::Hostname::from_string(&rrset.name)
+                .unwrap()
+                .label_count(),
+            original_ttl: 3600,
+            expiration: NOW + 86400,
+            inception: NOW - 86400,
+            key_tag: key.key_tag(),
+            signer_name: key.name.clone(),
+            signature: Vec::new(),
+        };
+        let rrs: Vec<_> = rrset.records.iter().map(|r| r.to_rr().unwrap()).collect();
+        rrsig.signature = sign(&rrsig.signed_data(&rrs).unwrap());
+        rrset.rrsigs.push(rrsig);
+    }
+
+    fn ed25519_key(zone: &str, seed: u8) -> (DNSKEYRecord, Ed25519KeyPair) {
+        let pair = Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap();
+        let key = DNSKEYRecord {
+            name: zone.to_string(),
+            ttl: 3600,
+            flags: 257,
+            protocol: 3,
+            algorithm: 15,
+            public_key: pair.public_key().as_ref().to_vec(),
+        };
+        return (key, pair);
+    }
+
+    #[test]
+    fn signed_ns_records_defeat_forgeries() {
+        let (key, pair) = ed25519_key("example.com", 1);
+        let mut real = rrset(vec![
+            ns("example.com", "ns1.example.com"),
+            ns("example.com", "ns2.example.com"),
+        ]);
+        sign(&mut real, &key, &|data| pair.sign(data).as_ref().to_vec());
+        assert_eq!(Ok(()), verify_rrset(&real, std::slice::from_ref(&key), NOW));
+
+        // the order and case of the records does not matter
+        let mut reordered = real.clone();
+        reordered.records = vec![
+            ns("EXAMPLE.com", "ns2.example.com"),
+            ns("example.com", "NS1.example.com"),
+        ];
+        assert_eq!(
+            Ok(()),
+            verify_rrset(&reordered, std::slice::from_ref(&key), NOW)
+        );
+
+        // the forged NS records of a Kaminsky attack cannot carry a valid signature
+        let mut forged = real.clone();
+        forged.records = vec![ns("example.com", "ns.attacker.lab")];
+        assert!(verify_rrset(&forged, std::slice::from_ref(&key), NOW).is_err());
+        forged.rrsigs.clear();
+        assert!(verify_rrset(&forged, std::slice::from_ref(&key), NOW).is_err());
+
+        // expired signatures and signatures by untrusted keys are rejected too
+        assert!(verify_rrset(&real, std::slice::from_ref(&key), NOW + 2 * 86400).is_err());
+        let (other, _) = ed25519_key("example.com", 2);
+        assert!(verify_rrset(&real, &[other], NOW).is_err());
+    }
+
+    #[test]
+    fn ecdsa_signatures() {
+        let random = SystemRandom::new();
+        let algorithm = &signature::ECDSA_P256_SHA256_FIXED_SIGNING;
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(algorithm, &random).unwrap();
+        let pair = EcdsaKeyPair::from_pkcs8(algorithm, pkcs8.as_ref(), &random).unwrap();
+        let key = DNSKEYRecord {
+            name: "example.com".to_string(),
+            ttl: 3600,
+            flags: 256,
+            protocol: 3,
+            algorithm: 13,
+            public_key: pair.public_key().as_ref()[1..].to_vec(),
+        };
+
+        let mut real = rrset(vec![ns("example.com", "ns1.example.com")]);
+        sign(&mut real, &key, &|data| {
+            pair.sign(&random, data).unwrap().as_ref().to_vec()
+        });
+        assert_eq!(Ok(()), verify_rrset(&real, &[key], NOW));
+    }
+
+    #[test]
+    fn chain_of_trust_from_a_ds_anchor() {
+        let (root_key, root_pair) = ed25519_key(".", 3);
+        let (zone_key, zone_pair) = ed25519_key("example.com", 4);
+
+        let mut root_keys = rrset(vec![dns::Record::DNSKEY(root_key.clone())]);
+        sign(&mut root_keys, &root_key, &|data| {
+            root_pair.sign(data).as_ref().to_vec()
+        });
+        let mut ds = rrset(vec![dns::Record::DS(zone_key.ds().unwrap())]);
+        sign(&mut ds, &root_key, &|data| {
+            root_pair.sign(data).as_ref().to_vec()
+        });
+        let mut zone_keys = rrset(vec![dns::Record::DNSKEY(zone_key.clone())]);
+        sign(&mut zone_keys, &zone_key, &|data| {
+            zone_pair.sign(data).as_ref().to_vec()
+        });
+        let mut answer = rrset(vec![ns("example.com", "ns1.example.com")]);
+        sign(&mut answer, &zone_key, &|data| {
+            zone_pair.sign(data).as_ref().to_vec()
+        });
+
+        // responses carry the RRset followed by its RRSIGs
+        let respond = |rrset: &RRset| {
+            let mut message = dns::Query::new(vec!["example.com".to_string()])
+                .to_message(&mut rng::new(Some(1)))
+                .unwrap();
+            for record in &rrset.records {
+                message.answers.push(record.to_rr().unwrap());
+            }
+            for rrsig in &rrset.rrsigs {
+                message
+                    .answers
+                    .push(dns::Record::RRSIG(rrsig.clone()).to_rr().unwrap());
+            }
+            return message;
+        };
+        let mut fetched = Vec::new();
+        let mut fetch = |name: &str, rtype: dns::Type| -> Result<Message, String> {
+            fetched.push((name.to_string(), rtype));
+            return match (name, rtype) {
+                (".", dns::Type::DNSKEY) => Ok(respond(&root_keys)),
+                ("example.com", dns::Type::DS) => Ok(respond(&ds)),
+                ("example.com", dns::Type::DNSKEY) => Ok(respond(&zone_keys)),
+                _ => Err(format!("Unexpected query for {} {}", name, rtype)),
+            };
+        };
+
+        let mut validator = Validator::new(vec![dns::Record::DS(root_key.ds().unwrap())]).unwrap();
+        assert_eq!(Ok(()), validator.validate(&answer, NOW, &mut fetch));
+
+        let mut forged = answer.clone();
+        forged.records = vec![ns("example.com", "ns.attacker.lab")];
+        assert!(validator.validate(&forged, NOW, &mut fetch).is_err());
+        // the keys are only fetched once
+        assert_eq!(3, fetched.len());
+
+        // keys that do not match the anchor are never trusted
+        let mut validator = Validator::new(vec![dns::Record::DS(zone_key.ds().unwrap())]).unwrap();
+        assert!(validator.trust_keys(".", &root_keys, &[], NOW).is_err());
+    }
+
+    #[test]
+    fn group_rrsets() {
+        let (key, pair) = ed25519_key("example.com", 1);
+        let mut signed = rrset(vec![ns("example.com", "ns1.example.com")]);
+        sign(&mut signed, &key, &|data| pair.sign(data).as_ref().to_vec());
+
+        let section = vec![
+            ns("example.com", "ns1.example.com").to_rr().unwrap(),
+            dns::Record::RRSIG(signed.rrsigs[0].clone())
+                .to_rr()
+                .unwrap(),
+            dns::Record::A(dns::ARecord {
+                name: "ns1.example.com".to_string(),
+                ttl: 3600,
+                ip: [10, 0, 0, 53],
+            })
+            .to_rr()
+            .unwrap(),
+        ];
+        let rrsets = RRset::group(&section);
+        assert_eq!(2, rrsets.len());
+        assert_eq!(signed, rrsets[0]);
+        assert!(rrsets[1].rrsigs.is_empty());
+    }
+}
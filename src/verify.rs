@@ -0,0 +1,99 @@
+use crate::dns;
+use crate::dns::hostname::same_name;
+use rand::RngCore;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// When to probe the target resolver to check whether an attack has succeeded
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ProbeSchedule {
+    /// never probe the target resolver
+    Never,
+    /// probe after every round, stopping the attack early on success
+    EachRound,
+    /// probe once the attack has finished
+    AtEnd,
+}
+
+impl FromStr for ProbeSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "never" => Ok(ProbeSchedule::Never),
+            "each-round" => Ok(ProbeSchedule::EachRound),
+            "end" => Ok(ProbeSchedule::AtEnd),
+            _ => Err(format!(
+                "Unknown probe schedule {}, please enter either never, each-round, or end",
+                s
+            )),
+        };
+    }
+}
+
+/// Result of probing the target resolver for the target domain's nameservers
+#[derive(PartialEq, Debug)]
+pub struct ProbeReport {
+    /// Nameservers the target resolver returned for the target domain
+    pub nameservers: Vec<String>,
+    /// Whether the attacker nameserver is one of the returned nameservers
+    pub poisoned: bool,
+}
+
+/// Queries the target resolver for the NS records of the target domain and reports whether the
+/// attacker nameserver was adopted
+///
+/// If recursion_desired is false the query is sent with RD=0 so that the resolver only answers from
+/// its cache rather than going out and fetching the real nameservers.
+pub fn probe(
+    target_server_addr: &Ipv4Addr,
+    target_domain: &str,
+    attacker_ns: &str,
+    recursion_desired: bool,
+    rng: &mut dyn RngCore,
+) -> Result<ProbeReport, String> {
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+    let mut client = dns::Client::new(target_server_addr.to_string());
+    client.set_timeout(PROBE_TIMEOUT);
+
+    let mut request = dns::Query::new(vec![String::from(target_domain)]);
+    request.qtype = dns::Type::NS;
+    request.recursion_desired = recursion_desired;
+
+    let response = client.query(request, rng)?;
+
+    // A cached delegation may come back either as an answer or as a referral in the authority section
+    let nameservers: Vec<String> = response
+        .answers
+        .iter()
+        .chain(response.authorities.iter())
+        .filter_map(|rr| match dns::Record::from_rr(rr) {
+            Ok(dns::Record::NS(ns)) if same_name(&ns.name, target_domain) => Some(ns.ns),
+            _ => None,
+        })
+        .collect();
+
+    let poisoned = nameservers.iter().any(|ns| same_name(ns, attacker_ns));
+
+    return Ok(ProbeReport {
+        nameservers,
+        poisoned,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verify::ProbeSchedule;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_probe_schedule() {
+        assert_eq!(
+            ProbeSchedule::EachRound,
+            ProbeSchedule::from_str("each-round").unwrap()
+        );
+        assert!(ProbeSchedule::from_str("sometimes").is_err());
+    }
+}
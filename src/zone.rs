@@ -0,0 +1,605 @@
+use crate::dns;
+use crate::dns::dnssec;
+use crate::dns::hostname::{in_zone, normalize_name};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Zone loaded from a master file in the format specified in IETF RFC 1035
+///
+/// Supports the `$ORIGIN` and `$TTL` directives, `@` for the origin, names relative to the
+/// origin, owners left blank to repeat the previous owner, `;` comments, and parentheses to spread
+/// a record over several lines, e.g.
+///
+/// ```text
+/// $ORIGIN example.com.
+/// $TTL 3600
+/// @        IN NS    ns1
+/// ns1      IN A     10.0.0.53
+/// www         A     10.0.0.80
+///             AAAA  fd00::80
+/// ```
+///
+/// Names are kept lowercase and without a trailing dot so they can be compared directly.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Zone {
+    /// Apex of the zone
+    pub origin: String,
+    pub records: Vec<dns::Record>,
+}
+
+impl Zone {
+    /// Loads the zone from a file, with names relative to the origin unless the file sets its own
+    pub fn load(path: &str, origin: Option<&str>) -> Result<Zone, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Err(e) => return Err(format!("Could not read zone file {}: {}", path, e)),
+            Ok(c) => c,
+        };
+        return Zone::parse(&contents, origin);
+    }
+
+    pub(crate) fn parse(contents: &str, origin: Option<&str>) -> Result<Zone, String> {
+        let mut apex = origin.map(normalize_name);
+        let mut current_origin = apex.clone();
+        let mut default_ttl: Option<u32> = None;
+        let mut previous_owner: Option<String> = None;
+        let mut records = Vec::new();
+
+        for (line, entry) in entries(contents)? {
+            let fields: Vec<&str> = entry.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            match fields[0].to_ascii_uppercase().as_str() {
+                "$ORIGIN" => {
+                    let name = match fields.get(1) {
+                        None => return Err(format!("Line {} of the zone has no origin", line)),
+                        Some(name) => absolute_name(name, &current_origin, line)?,
+                    };
+                    if apex.is_none() {
+                        apex = Some(name.clone());
+                    }
+                    current_origin = Some(name);
+                    continue;
+                }
+                "$TTL" => {
+                    default_ttl = match fields.get(1).map(|ttl| ttl.parse::<u32>()) {
+                        Some(Ok(ttl)) => Some(ttl),
+                        _ => return Err(format!("Line {} of the zone has an invalid TTL", line)),
+                    };
+                    continue;
+                }
+                directive if directive.starts_with('$') => {
+                    return Err(format!(
+                        "Line {} of the zone has an unsupported directive {}",
+                        line, fields[0]
+                    ))
+                }
+                _ => (),
+            };
+
+            // an entry that starts with whitespace belongs to the previous owner
+            let (owner, rest) = if entry.starts_with(char::is_whitespace) {
+                match &previous_owner {
+                    None => return Err(format!("Line {} of the zone has no owner", line)),
+                    Some(owner) => (owner.clone(), &fields[..]),
+                }
+            } else {
+                (
+                    absolute_name(fields[0], &current_origin, line)?,
+                    &fields[1..],
+                )
+            };
+
+            // the TTL and class are optional and may come in either order before the type
+            let mut ttl = default_ttl;
+            let mut rest = rest;
+            while let Some(field) = rest.first() {
+                if let Ok(value) = field.parse::<u32>() {
+                    ttl = Some(value);
+                } else if !field.eq_ignore_ascii_case("IN") {
+                    break;
+                }
+                rest = &rest[1..];
+            }
+            let ttl = match ttl {
+                None => return Err(format!("Line {} of the zone has no TTL", line)),
+                Some(ttl) => ttl,
+            };
+            if rest.len() < 2 {
+                return Err(format!("Line {} of the zone is malformed: {}", line, entry));
+            }
+
+            let record = record(&owner, ttl, rest[0], &rest[1..], &current_origin, line)?;
+            match &apex {
+                Some(apex) if in_zone(&owner, apex) => (),
+                _ => {
+                    return Err(format!(
+                        "Line {} of the zone has a record for {} outside the zone",
+                        line, owner
+                    ))
+                }
+            };
+            // a TTL given on a record carries over to the following ones when there is no $TTL
+            if default_ttl.is_none() {
+                default_ttl = Some(ttl);
+            }
+            previous_owner = Some(owner);
+            records.push(record);
+        }
+
+        return match apex {
+            None => Err("Zone has no origin, please set $ORIGIN or pass one in".to_string()),
+            Some(origin) => Ok(Zone { origin, records }),
+        };
+    }
+
+    /// Records owned by the name
+    pub fn records_at(&self, name: &str) -> Vec<&dns::Record> {
+        let name = normalize_name(name);
+        return self
+            .records
+            .iter()
+            .filter(|record| record.name() == name)
+            .collect();
+    }
+
+    /// SOA record at the apex, which negative answers from the zone carry
+    pub fn soa(&self) -> Option<&dns::SOARecord> {
+        return self.records.iter().find_map(|record| match record {
+            dns::Record::SOA(soa) if soa.name == self.origin => Some(soa),
+            _ => None,
+        });
+    }
+
+    /// Whether the name owns records or has names with records below it
+    pub fn name_exists(&self, name: &str) -> bool {
+        let name = normalize_name(name);
+        return self
+            .records
+            .iter()
+            .any(|record| in_zone(record.name(), &name));
+    }
+
+    /// Closest delegation below the apex that the name falls under, along with its NS records
+    pub fn delegation(&self, name: &str) -> Option<(String, Vec<&dns::Record>)> {
+        let name = normalize_name(name);
+        let mut cut: Option<String> = None;
+        for record in &self.records {
+            let owner = record.name();
+            if record.rtype() != dns::Type::NS || owner == self.origin || !in_zone(&name, owner) {
+                continue;
+            }
+            // the cut closest to the apex wins, since anything below it is not part of the zone
+            if cut.as_ref().is_none_or(|cut| cut.len() > owner.len()) {
+                cut = Some(owner.to_string());
+            }
+        }
+        let cut = cut?;
+        let nameservers = self
+            .records_at(&cut)
+            .into_iter()
+            .filter(|record| record.rtype() == dns::Type::NS)
+            .collect();
+        return Some((cut, nameservers));
+    }
+}
+
+/// Writes the zone in the master file format with every name in full, so that it can be loaded
+/// again or served by other nameservers
+impl std::fmt::Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the first $ORIGIN gives the apex of the zone, and the second one makes the names of the
+        // records, which are written without a trailing dot, relative to the root
+        match self.origin.as_str() {
+            "" => writeln!(f, "$ORIGIN .")?,
+            origin => writeln!(f, "$ORIGIN {}.", origin)?,
+        };
+        writeln!(f, "$ORIGIN .")?;
+        for record in &self.records {
+            writeln!(f, "{}", record)?;
+        }
+        return Ok(());
+    }
+}
+
+/// Splits the contents into entries with comments removed and parenthesized lines joined, along
+/// with the line each entry starts on
+fn entries(contents: &str) -> Result<Vec<(usize, String)>, String> {
+    let mut entries = Vec::new();
+    let mut entry = String::new();
+    let mut start = 0;
+    let mut depth = 0;
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.splitn(2, ';').next().unwrap_or("");
+        if depth == 0 {
+            start = i + 1;
+            entry.clear();
+        }
+        for c in line.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => {
+                    return Err(format!("Line {} of the zone has an unmatched )", i + 1))
+                }
+                ')' => depth -= 1,
+                _ => entry.push(c),
+            };
+        }
+        entry.push(' ');
+        if depth == 0 && !entry.trim().is_empty() {
+            entries.push((start, entry.clone()));
+        }
+    }
+    if depth != 0 {
+        return Err(format!("Line {} of the zone has an unmatched (", start));
+    }
+    return Ok(entries);
+}
+
+fn record(
+    owner: &str,
+    ttl: u32,
+    rtype: &str,
+    rdata: &[&str],
+    origin: &Option<String>,
+    line: usize,
+) -> Result<dns::Record, String> {
+    let name = owner.to_string();
+    let invalid = |kind: &str| format!("Line {} of the zone has an invalid {} record", line, kind);
+    return match rtype.to_ascii_uppercase().as_str() {
+        "A" => match Ipv4Addr::from_str(rdata[0]) {
+            Err(_) => Err(invalid("A")),
+            Ok(ip) => Ok(dns::Record::A(dns::ARecord {
+                name,
+                ttl,
+                ip: ip.octets(),
+            })),
+        },
+        "AAAA" => match Ipv6Addr::from_str(rdata[0]) {
+            Err(_) => Err(invalid("AAAA")),
+            Ok(ip) => Ok(dns::Record::AAAA(dns::AAAARecord {
+                name,
+                ttl,
+                ip: ip.octets(),
+            })),
+        },
+        "NS" => Ok(dns::Record::NS(dns::NSRecord {
+            name,
+            ttl,
+            ns: absolute_name(rdata[0], origin, line)?,
+        })),
+        "CNAME" => Ok(dns::Record::CNAME(dns::CNAMERecord {
+            name,
+            ttl,
+            cname: absolute_name(rdata[0], origin, line)?,
+        })),
+        "SOA" => {
+            if rdata.len() != 7 {
+                return Err(invalid("SOA"));
+            }
+            Ok(dns::Record::SOA(dns::SOARecord {
+                name,
+                ttl,
+                mname: absolute_name(rdata[0], origin, line)?,
+                rname: absolute_name(rdata[1], origin, line)?,
+                serial: field(rdata, 2, "SOA", line)?,
+                refresh: field(rdata, 3, "SOA", line)?,
+                retry: field(rdata, 4, "SOA", line)?,
+                expire: field(rdata, 5, "SOA", line)?,
+                minimum: field(rdata, 6, "SOA", line)?,
+            }))
+        }
+        "DNSKEY" => {
+            let public_key =
+                decode_base64(&rdata.get(3..).unwrap_or(&[]).concat(), "DNSKEY", line)?;
+            Ok(dns::Record::DNSKEY(dnssec::DNSKEYRecord {
+                name,
+                ttl,
+                flags: field(rdata, 0, "DNSKEY", line)?,
+                protocol: field(rdata, 1, "DNSKEY", line)?,
+                algorithm: algorithm(rdata, 2, "DNSKEY", line)?,
+                public_key,
+            }))
+        }
+        "DS" => {
+            let digest = rdata.get(3..).unwrap_or(&[]).concat();
+            Ok(dns::Record::DS(dnssec::DSRecord {
+                name,
+                ttl,
+                key_tag: field(rdata, 0, "DS", line)?,
+                algorithm: algorithm(rdata, 1, "DS", line)?,
+                digest_type: field(rdata, 2, "DS", line)?,
+                digest: match data_encoding::HEXLOWER_PERMISSIVE.decode(digest.as_bytes()) {
+                    Err(_) => return Err(invalid("DS")),
+                    Ok(digest) => digest,
+                },
+            }))
+        }
+        "RRSIG" => {
+            let time = |i: usize| match rdata.get(i).map(|t| dnssec::parse_time(t)) {
+                Some(Ok(time)) => Ok(time),
+                _ => Err(invalid("RRSIG")),
+            };
+            let signer = match rdata.get(7) {
+                None => return Err(invalid("RRSIG")),
+                Some(signer) => absolute_name(signer, origin, line)?,
+            };
+            Ok(dns::Record::RRSIG(dnssec::RRSIGRecord {
+                name,
+                ttl,
+                type_covered: field(rdata, 0, "RRSIG", line)?,
+                algorithm: algorithm(rdata, 1, "RRSIG", line)?,
+                labels: field(rdata, 2, "RRSIG", line)?,
+                original_ttl: field(rdata, 3, "RRSIG", line)?,
+                expiration: time(4)?,
+                inception: time(5)?,
+                key_tag: field(rdata, 6, "RRSIG", line)?,
+                signer_name: signer,
+                signature: decode_base64(&rdata.get(8..).unwrap_or(&[]).concat(), "RRSIG", line)?,
+            }))
+        }
+        "NSEC" => Ok(dns::Record::NSEC(dnssec::NSECRecord {
+            name,
+            ttl,
+            next_name: absolute_name(rdata[0], origin, line)?,
+            types: types(&rdata[1..], "NSEC", line)?,
+        })),
+        "NSEC3" => {
+            let next_hashed_owner = match rdata.get(4).map(|hash| {
+                data_encoding::BASE32HEX_NOPAD.decode(hash.to_ascii_uppercase().as_bytes())
+            }) {
+                Some(Ok(hash)) => hash,
+                _ => return Err(invalid("NSEC3")),
+            };
+            Ok(dns::Record::NSEC3(dnssec::NSEC3Record {
+                name,
+                ttl,
+                hash_algorithm: field(rdata, 0, "NSEC3", line)?,
+                flags: field(rdata, 1, "NSEC3", line)?,
+                iterations: field(rdata, 2, "NSEC3", line)?,
+                salt: salt(rdata, 3, "NSEC3", line)?,
+                next_hashed_owner,
+                types: types(rdata.get(5..).unwrap_or(&[]), "NSEC3", line)?,
+            }))
+        }
+        "NSEC3PARAM" => Ok(dns::Record::NSEC3PARAM(dnssec::NSEC3PARAMRecord {
+            name,
+            ttl,
+            hash_algorithm: field(rdata, 0, "NSEC3PARAM", line)?,
+            flags: field(rdata, 1, "NSEC3PARAM", line)?,
+            iterations: field(rdata, 2, "NSEC3PARAM", line)?,
+            salt: salt(rdata, 3, "NSEC3PARAM", line)?,
+        })),
+        _ => Err(format!(
+            "Line {} of the zone has an unsupported record type {}",
+            line, rtype
+        )),
+    };
+}
+
+/// Parses one of the rdata fields of a record
+fn field<T: FromStr>(rdata: &[&str], i: usize, rtype: &str, line: usize) -> Result<T, String> {
+    return match rdata.get(i).map(|f| f.parse::<T>()) {
+        Some(Ok(value)) => Ok(value),
+        _ => Err(format!(
+            "Line {} of the zone has an invalid {} record",
+            line, rtype
+        )),
+    };
+}
+
+/// Parses an algorithm field, which can be given as a number or as a mnemonic like ED25519
+fn algorithm(rdata: &[&str], i: usize, rtype: &str, line: usize) -> Result<u8, String> {
+    if let Some(Ok(algorithm)) = rdata.get(i).map(|f| dnssec::Algorithm::from_str(f)) {
+        return Ok(algorithm as u8);
+    }
+    return field(rdata, i, rtype, line);
+}
+
+/// Parses the salt of NSEC3 and NSEC3PARAM records, where - stands for no salt
+fn salt(rdata: &[&str], i: usize, rtype: &str, line: usize) -> Result<Vec<u8>, String> {
+    return match rdata.get(i) {
+        Some(&"-") => Ok(Vec::new()),
+        Some(salt) => match data_encoding::HEXLOWER_PERMISSIVE.decode(salt.as_bytes()) {
+            Err(_) => Err(format!(
+                "Line {} of the zone has an invalid {} record",
+                line, rtype
+            )),
+            Ok(salt) => Ok(salt),
+        },
+        None => Err(format!(
+            "Line {} of the zone has an invalid {} record",
+            line, rtype
+        )),
+    };
+}
+
+fn types(fields: &[&str], rtype: &str, line: usize) -> Result<Vec<dns::Type>, String> {
+    let mut types = Vec::new();
+    for i in 0..fields.len() {
+        types.push(field(fields, i, rtype, line)?);
+    }
+    return Ok(types);
+}
+
+fn decode_base64(data: &str, rtype: &str, line: usize) -> Result<Vec<u8>, String> {
+    return match data_encoding::BASE64.decode(data.as_bytes()) {
+        Err(_) => Err(format!(
+            "Line {} of the zone has invalid base64 in a {} record",
+            line, rtype
+        )),
+        Ok(decoded) => Ok(decoded),
+    };
+}
+
+/// Resolves a name from the zone file against the origin
+fn absolute_name(name: &str, origin: &Option<String>, line: usize) -> Result<String, String> {
+    if name == "@" || !name.ends_with('.') {
+        let origin = match origin {
+            None => {
+                return Err(format!(
+                    "Line {} of the zone has the relative name {} but no origin is set",
+                    line, name
+                ))
+            }
+            Some(origin) => origin,
+        };
+        if name == "@" {
+            return Ok(origin.clone());
+        }
+        if origin.is_empty() {
+            return Ok(normalize_name(name));
+        }
+        return Ok(format!("{}.{}", normalize_name(name), origin));
+    }
+    return Ok(normalize_name(name));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns;
+    use crate::zone::Zone;
+
+    const ZONE: &str = "\
+$ORIGIN Example.COM.
+$TTL 3600
+@          IN NS   ns1          ; the lab nameserver
+ns1        IN A    10.0.0.53
+www   300     A    10.0.0.80
+              AAAA fd00::80
+mail       CNAME   www.example.com.
+sub        NS      (
+                   ns.sub
+                   )
+ns.sub     A       10.0.1.53
+";
+
+    #[test]
+    fn parse_zone_file() {
+        let zone = Zone::parse(ZONE, None).unwrap();
+        assert_eq!("example.com", zone.origin);
+        assert_eq!(7, zone.records.len());
+        assert_eq!(
+            dns::Record::NS(dns::NSRecord {
+                name: "example.com".to_string(),
+                ttl: 3600,
+                ns: "ns1.example.com".to_string(),
+            }),
+            zone.records[0]
+        );
+        assert_eq!(
+            dns::Record::AAAA(dns::AAAARecord {
+                name: "www.example.com".to_string(),
+                ttl: 3600,
+                ip: [0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80],
+            }),
+            zone.records[3]
+        );
+        assert_eq!(
+            dns::Record::NS(dns::NSRecord {
+                name: "sub.example.com".to_string(),
+                ttl: 3600,
+                ns: "ns.sub.example.com".to_string(),
+            }),
+            zone.records[5]
+        );
+    }
+
+    #[test]
+    fn lookups() {
+        let zone = Zone::parse(ZONE, None).unwrap();
+        assert_eq!(2, zone.records_at("WWW.example.com.").len());
+        assert!(zone.name_exists("sub.example.com"));
+        assert!(!zone.name_exists("missing.example.com"));
+
+        let (cut, nameservers) = zone.delegation("a.b.sub.example.com").unwrap();
+        assert_eq!("sub.example.com", cut);
+        assert_eq!(1, nameservers.len());
+        assert_eq!(None, zone.delegation("www.example.com"));
+    }
+
+    #[test]
+    fn parse_soa_record() {
+        let zone = Zone::parse(
+            "\
+$TTL 3600
+@  SOA  ns1 hostmaster (
+        2024010101 ; serial
+        7200 900 1209600
+        300 )
+",
+            Some("example.com"),
+        )
+        .unwrap();
+        let soa = zone.soa().unwrap();
+        assert_eq!("ns1.example.com", soa.mname);
+        assert_eq!("hostmaster.example.com", soa.rname);
+        assert_eq!((2024010101, 300), (soa.serial, soa.minimum));
+        assert_eq!(300, soa.negative_ttl());
+        assert_eq!(
+            "example.com 3600 SOA ns1.example.com hostmaster.example.com 2024010101 7200 900 1209600 300",
+            zone.records[0].to_string()
+        );
+        assert!(Zone::parse("@ 3600 SOA ns1 hostmaster 1 2 3", Some("example.com")).is_err());
+    }
+
+    #[test]
+    fn invalid_zone_files() {
+        // no origin to resolve relative names against
+        assert!(Zone::parse("www 300 A 10.0.0.80", None).is_err());
+        assert!(Zone::parse("www 300 A 10.0.0.80", Some("example.com")).is_ok());
+        // no TTL
+        assert!(Zone::parse("www A 10.0.0.80", Some("example.com")).is_err());
+        // outside the zone
+        assert!(Zone::parse("www.other.com. 300 A 10.0.0.80", Some("example.com")).is_err());
+        assert!(Zone::parse("www 300 MX 10 mail", Some("example.com")).is_err());
+        assert!(Zone::parse("www 300 A (10.0.0.80", Some("example.com")).is_err());
+    }
+
+    #[test]
+    fn parse_dnssec_records() {
+        // the trust anchor of the root zone, as a DS record and as the DNSKEY split over lines
+        let anchors = "\
+. 172800 IN DS 20326 8 2 E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D
+. 172800 IN DNSKEY 257 3 RSASHA256 (
+        AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3
+        +/4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMtNROxVQuCaSnIDdD5
+        LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwVN8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+
+        sqqls3eNbuv7pr+eoZG+SrDK6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfh
+        HdY6+cn8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU= ) ; KSK-2017
+";
+        let zone = Zone::parse(anchors, Some(".")).unwrap();
+        let (ds, key) = match (&zone.records[0], &zone.records[1]) {
+            (dns::Record::DS(ds), dns::Record::DNSKEY(key)) => (ds, key),
+            _ => panic!("Expected a DS and a DNSKEY record"),
+        };
+        assert_eq!(20326, key.key_tag());
+        assert!(key.matches(ds));
+
+        let zone = Zone::parse(
+            "\
+$ORIGIN example.com.
+@ 3600 RRSIG NS 15 2 3600 20231115221320 1700000000 12345 @ AQID
+@ 3600 NSEC www NS SOA RRSIG NSEC DNSKEY
+@ 3600 NSEC3 1 0 0 - 0S3GE1O70S3GE1O70S3GE1O70S3GE1O7 A RRSIG
+",
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            "example.com 3600 RRSIG NS 15 2 3600 20231115221320 20231114221320 12345 example.com AQID",
+            zone.records[0].to_string()
+        );
+        assert_eq!(
+            "example.com 3600 NSEC www.example.com NS SOA RRSIG NSEC DNSKEY",
+            zone.records[1].to_string()
+        );
+        assert_eq!(
+            "example.com 3600 NSEC3 1 0 0 - 0S3GE1O70S3GE1O70S3GE1O70S3GE1O7 A RRSIG",
+            zone.records[2].to_string()
+        );
+        assert!(Zone::parse("@ 3600 DS 1 13 2 XYZ", Some("example.com")).is_err());
+    }
+}